@@ -0,0 +1,496 @@
+use chrono::{DateTime, Duration, FixedOffset, Timelike, Utc};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use std::collections::{BTreeSet, VecDeque};
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
+use umya_spreadsheet::{Alignment, HorizontalAlignmentValues, NumberingFormat, Style, Worksheet};
+
+use crate::{
+    db_op::{decimal_from_i64, get_character_name, get_corporation_name, get_linked_character_ids},
+    locale::{Lang, Text},
+    report::HIGHLIGHT_RED,
+    statement::{excel_datetime, format_isk_text},
+};
+use db_wallet::{
+    JournalRefType,
+    entities::corporation_wallet_journal::{
+        Column as CCorporationWalletJournal, Entity as ECorporationWalletJournal,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AlertKind {
+    LargeWithdrawal,
+    UnknownCounterparty,
+    UnusualHour,
+    BalanceDrop,
+}
+
+impl AlertKind {
+    pub fn text(&self) -> Text {
+        match self {
+            AlertKind::LargeWithdrawal => Text::LargeWithdrawal,
+            AlertKind::UnknownCounterparty => Text::UnknownCounterparty,
+            AlertKind::UnusualHour => Text::UnusualHour,
+            AlertKind::BalanceDrop => Text::BalanceDrop,
+        }
+    }
+}
+
+// 告警说明, 输出时按语言格式化
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AlertDetail {
+    AboveThreshold(Decimal),                // 大额支出阈值
+    UnlinkedRecipient,                      // 收款方不是已关联用户的角色
+    LocalTime(u32, FixedOffset),            // 发生时的小时与时区
+    BalanceWithin24Hours(Decimal, Decimal), // 24小时内的余额峰值与当前余额
+}
+
+impl AlertDetail {
+    pub fn to_text(&self, lang: Lang) -> String {
+        match self {
+            AlertDetail::AboveThreshold(threshold) => format!(
+                "{} {}",
+                lang.text(Text::AboveThreshold),
+                format_isk_text(*threshold)
+            ),
+            AlertDetail::UnlinkedRecipient => lang.text(Text::UnlinkedRecipient).to_string(),
+            AlertDetail::LocalTime(hour, offset) => format!(
+                "{} {:02}:00 (UTC{})",
+                lang.text(Text::LocalTime),
+                hour,
+                offset
+            ),
+            AlertDetail::BalanceWithin24Hours(peak, balance) => format!(
+                "{} {} -> {}",
+                lang.text(Text::BalanceWithin24Hours),
+                format_isk_text(*peak),
+                format_isk_text(*balance)
+            ),
+        }
+    }
+}
+
+pub struct AnomalyConfig {
+    pub large_withdrawal: Decimal, // 大额支出阈值, 单位 isk
+    pub quiet_hours: (u32, u32),   // 异常时段 [起始小时, 结束小时), 报表时区, 允许跨越零点
+    pub balance_drop: Decimal,     // 24小时内余额下降比例阈值, 0 ~ 1
+}
+
+impl AnomalyConfig {
+    fn is_quiet_hour(&self, hour: u32) -> bool {
+        let (start, end) = self.quiet_hours;
+        if start <= end {
+            start <= hour && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+// 解析异常时段, 例如 "16-22"
+pub fn parse_quiet_hours(s: &str) -> Result<(u32, u32), String> {
+    let (start, end) = s.split_once('-').ok_or("illegal format".to_string())?;
+    let start = start.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    let end = end.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    if start > 23 || end > 24 {
+        return Err(format!("invalid hours: {}", s));
+    }
+    Ok((start, end))
+}
+
+pub struct JournalRow {
+    pub id: i64,
+    pub date_time: DateTime<FixedOffset>, // 报表时区的时间
+    pub ref_type: JournalRefType,
+    pub amount: Decimal,
+    pub balance: Decimal,
+    pub counterparty_id: Option<i64>,
+}
+
+pub struct Alert {
+    kind: AlertKind,
+    journal_id: i64,
+    date_time: DateTime<FixedOffset>,
+    ref_type: JournalRefType,
+    amount: Decimal,
+    balance: Decimal,
+    counterparty_id: Option<i64>,
+    detail: AlertDetail,
+}
+
+impl Alert {
+    fn new(kind: AlertKind, row: &JournalRow, detail: AlertDetail) -> Self {
+        Self {
+            kind,
+            journal_id: row.id,
+            date_time: row.date_time,
+            ref_type: row.ref_type,
+            amount: row.amount,
+            balance: row.balance,
+            counterparty_id: row.counterparty_id,
+            detail,
+        }
+    }
+}
+
+fn is_withdrawal(row: &JournalRow) -> bool {
+    row.amount.is_sign_negative()
+        && (row.ref_type == JournalRefType::CorporationAccountWithdrawal
+            || row.ref_type == JournalRefType::CorporationDividendPayment)
+}
+
+// 检测异常流水, rows 需按时间升序
+// linked_ids 为已关联用户的角色ID
+pub fn detect(
+    rows: &[JournalRow],
+    config: &AnomalyConfig,
+    linked_ids: &BTreeSet<i64>,
+) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+
+    // 最近24小时内的 (时间, 余额), 用于检测余额骤降
+    let mut window: VecDeque<(DateTime<FixedOffset>, Decimal)> = VecDeque::new();
+
+    for row in rows {
+        if is_withdrawal(row) {
+            if row.amount.abs() >= config.large_withdrawal {
+                let detail = AlertDetail::AboveThreshold(config.large_withdrawal);
+                alerts.push(Alert::new(AlertKind::LargeWithdrawal, row, detail));
+            }
+
+            let linked = row
+                .counterparty_id
+                .is_some_and(|id| linked_ids.contains(&id));
+            if linked == false {
+                let detail = AlertDetail::UnlinkedRecipient;
+                alerts.push(Alert::new(AlertKind::UnknownCounterparty, row, detail));
+            }
+
+            let hour = row.date_time.hour();
+            if config.is_quiet_hour(hour) {
+                let detail = AlertDetail::LocalTime(hour, *row.date_time.offset());
+                alerts.push(Alert::new(AlertKind::UnusualHour, row, detail));
+            }
+        }
+
+        while let Some((t, _)) = window.front() {
+            if row.date_time - *t > Duration::hours(24) {
+                window.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        // 流水发生前的余额也计入窗口
+        let before = row.balance - row.amount;
+        let peak = window.iter().map(|(_, b)| *b).fold(before, Decimal::max);
+        if row.amount.is_sign_negative() && peak > Decimal::ZERO {
+            let ratio = (peak - row.balance) / peak;
+            if ratio >= config.balance_drop {
+                let detail = AlertDetail::BalanceWithin24Hours(peak, row.balance);
+                alerts.push(Alert::new(AlertKind::BalanceDrop, row, detail));
+                // 已告警, 以当前余额重新开始计算
+                window.clear();
+            }
+        }
+        window.push_back((row.date_time, row.balance));
+    }
+
+    alerts
+}
+
+#[derive(EnumIter, EnumCount, Clone, Copy)]
+pub enum ColumnAlerts {
+    DateTime = 1,
+    Kind = 2,
+    RefType = 3,
+    Amount = 4,
+    Balance = 5,
+    Counterparty = 6,
+    Detail = 7,
+    JournalId = 8,
+}
+
+impl ColumnAlerts {
+    pub fn text(&self) -> Text {
+        match self {
+            ColumnAlerts::DateTime => Text::DateTime,
+            ColumnAlerts::Kind => Text::AlertKind,
+            ColumnAlerts::RefType => Text::RefType,
+            ColumnAlerts::Amount => Text::Amount,
+            ColumnAlerts::Balance => Text::Balance,
+            ColumnAlerts::Counterparty => Text::Counterparty,
+            ColumnAlerts::Detail => Text::Detail,
+            ColumnAlerts::JournalId => Text::JournalId,
+        }
+    }
+
+    fn get_style(&self) -> Style {
+        let mut style = Style::default();
+        let format_str = match self {
+            ColumnAlerts::DateTime => r#"yyyy-mm-dd hh:mm:ss"#,
+            ColumnAlerts::Kind => r#"@"#,
+            ColumnAlerts::RefType => r#"@"#,
+            ColumnAlerts::Amount => {
+                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
+            }
+            ColumnAlerts::Balance => {
+                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
+            }
+            ColumnAlerts::Counterparty => r#"@"#,
+            ColumnAlerts::Detail => r#"@"#,
+            ColumnAlerts::JournalId => r#"0"#,
+        };
+        let numbering_format = NumberingFormat::default()
+            .set_format_code(format_str)
+            .to_owned();
+        style.set_numbering_format(numbering_format);
+
+        style
+    }
+}
+
+pub struct SheetAlerts {
+    data: Vec<(Alert, String)>, // (告警, 相关方名称)
+}
+
+impl SheetAlerts {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn insert_worksheet(&self, w: &mut Worksheet, lang: Lang) {
+        // 插入标题
+        for column in ColumnAlerts::iter() {
+            let cell = w.get_cell_mut((column as u32, 1));
+            cell.set_value_string(lang.text(column.text()));
+            let mut alignment = Alignment::default();
+            alignment.set_horizontal(HorizontalAlignmentValues::Center);
+            cell.get_style_mut().set_alignment(alignment);
+        }
+
+        // 插入数据
+        for (i, (alert, counterparty)) in self.data.iter().enumerate() {
+            let row = (i + 2) as u32;
+            for column in ColumnAlerts::iter() {
+                let cell = w.get_cell_mut((column as u32, row));
+                let mut style = column.get_style();
+                style.set_background_color(HIGHLIGHT_RED);
+                cell.set_style(style);
+
+                match column {
+                    ColumnAlerts::DateTime => {
+                        cell.set_value_number(excel_datetime(&alert.date_time));
+                    }
+                    ColumnAlerts::Kind => {
+                        cell.set_value_string(lang.text(alert.kind.text()));
+                    }
+                    ColumnAlerts::RefType => {
+                        cell.set_value_string(lang.ref_type(alert.ref_type));
+                    }
+                    ColumnAlerts::Amount => {
+                        cell.set_value_number(alert.amount.to_f64().unwrap());
+                    }
+                    ColumnAlerts::Balance => {
+                        cell.set_value_number(alert.balance.to_f64().unwrap());
+                    }
+                    ColumnAlerts::Counterparty => {
+                        cell.set_value_string(counterparty.as_str());
+                    }
+                    ColumnAlerts::Detail => {
+                        cell.set_value_string(alert.detail.to_text(lang));
+                    }
+                    ColumnAlerts::JournalId => {
+                        cell.set_value_number(alert.journal_id as f64);
+                    }
+                }
+            }
+        }
+    }
+
+    // 流水时间转换到 offset 时区, 异常时段与告警时间均使用该时区
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        offset: FixedOffset,
+        config: &AnomalyConfig,
+    ) -> Result<SheetAlerts, String> {
+        #[derive(FromQueryResult)]
+        struct Journal {
+            id: i64,
+            date: i64,
+            ref_type: i32,
+            amount: Option<i64>,
+            balance: Option<i64>,
+            second_party_id: Option<i64>,
+        }
+
+        assert!(start_time <= end_time);
+        let start_time = start_time.timestamp();
+        let end_time = end_time.timestamp();
+
+        let journals = ECorporationWalletJournal::find()
+            .select_only()
+            .column(CCorporationWalletJournal::Id)
+            .column(CCorporationWalletJournal::Date)
+            .column(CCorporationWalletJournal::RefType)
+            .column(CCorporationWalletJournal::Amount)
+            .column(CCorporationWalletJournal::Balance)
+            .column(CCorporationWalletJournal::SecondPartyId)
+            .filter(
+                Condition::all()
+                    .add(CCorporationWalletJournal::Date.gte(start_time))
+                    .add(CCorporationWalletJournal::Date.lt(end_time)),
+            )
+            .order_by_asc(CCorporationWalletJournal::Date)
+            .order_by_asc(CCorporationWalletJournal::Id)
+            .into_model::<Journal>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let rows: Vec<JournalRow> = journals
+            .into_iter()
+            .map(|j| JournalRow {
+                id: j.id,
+                date_time: DateTime::from_timestamp_secs(j.date)
+                    .unwrap()
+                    .with_timezone(&offset),
+                ref_type: JournalRefType::from_repr(j.ref_type).unwrap(),
+                amount: decimal_from_i64(j.amount.unwrap_or(0)),
+                balance: decimal_from_i64(j.balance.unwrap_or(0)),
+                counterparty_id: j.second_party_id,
+            })
+            .collect();
+
+        let linked_ids = get_linked_character_ids(db).await?;
+        let alerts = detect(&rows, config, &linked_ids);
+
+        let mut data = Vec::with_capacity(alerts.len());
+        for alert in alerts {
+            let mut counterparty = String::new();
+            if let Some(id) = alert.counterparty_id {
+                if let Some(n) = get_character_name(db, id).await? {
+                    counterparty = n;
+                } else if let Some(n) = get_corporation_name(db, id).await? {
+                    counterparty = n;
+                } else {
+                    counterparty = id.to_string();
+                }
+            }
+            data.push((alert, counterparty));
+        }
+
+        Ok(SheetAlerts { data })
+    }
+}
+
+#[cfg(test)]
+fn test_row(id: i64, hour: u32, ref_type: JournalRefType, amount: i64, balance: i64) -> JournalRow {
+    let date_time = DateTime::from_timestamp_secs(1759276800)
+        .unwrap()
+        .fixed_offset()
+        + Duration::hours(hour as i64);
+    JournalRow {
+        id,
+        date_time,
+        ref_type,
+        amount: Decimal::from(amount),
+        balance: Decimal::from(balance),
+        counterparty_id: Some(100),
+    }
+}
+
+#[test]
+fn test_detect() {
+    let config = AnomalyConfig {
+        large_withdrawal: Decimal::from(1000),
+        quiet_hours: (22, 6),
+        balance_drop: Decimal::new(5, 1),
+    };
+    let linked_ids = BTreeSet::from([100]);
+
+    let rows = vec![
+        test_row(1, 1, JournalRefType::PlayerDonation, 5000, 10000),
+        test_row(
+            2,
+            10,
+            JournalRefType::CorporationAccountWithdrawal,
+            -100,
+            9900,
+        ),
+        test_row(
+            3,
+            12,
+            JournalRefType::CorporationAccountWithdrawal,
+            -2000,
+            7900,
+        ),
+        test_row(
+            4,
+            23,
+            JournalRefType::CorporationDividendPayment,
+            -100,
+            7800,
+        ),
+        test_row(5, 30, JournalRefType::OfficeRentalFee, -3000, 4800),
+    ];
+    let kinds: Vec<(i64, AlertKind)> = detect(&rows, &config, &linked_ids)
+        .iter()
+        .map(|a| (a.journal_id, a.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (3, AlertKind::LargeWithdrawal),
+            (4, AlertKind::UnusualHour),
+            (5, AlertKind::BalanceDrop),
+        ]
+    );
+
+    let kinds: Vec<AlertKind> = detect(&rows[1..2], &config, &BTreeSet::new())
+        .iter()
+        .map(|a| a.kind)
+        .collect();
+    assert_eq!(kinds, vec![AlertKind::UnknownCounterparty]);
+
+    // 异常时段按流水所在时区的小时判断, UTC 17:00 为 UTC+8 的 01:00
+    let config = AnomalyConfig {
+        quiet_hours: (0, 6),
+        ..config
+    };
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let mut row = test_row(
+        6,
+        17,
+        JournalRefType::CorporationAccountWithdrawal,
+        -100,
+        10000,
+    );
+    row.date_time = row.date_time.with_timezone(&utc8);
+    let alerts = detect(&[row], &config, &linked_ids);
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].kind, AlertKind::UnusualHour);
+    assert_eq!(alerts[0].detail, AlertDetail::LocalTime(1, utc8),);
+    assert_eq!(
+        alerts[0].detail.to_text(Lang::Zh),
+        "发生时刻 01:00 (UTC+08:00)"
+    );
+    assert_eq!(
+        alerts[0].detail.to_text(Lang::En),
+        "Local time 01:00 (UTC+08:00)"
+    );
+}
+
+#[test]
+fn test_parse_quiet_hours() {
+    assert_eq!(parse_quiet_hours("16-22"), Ok((16, 22)));
+    assert_eq!(parse_quiet_hours("22-6"), Ok((22, 6)));
+    assert!(parse_quiet_hours("25-3").is_err());
+    assert!(parse_quiet_hours("16").is_err());
+}
@@ -0,0 +1,368 @@
+use umya_spreadsheet::{
+    Alignment, HorizontalAlignmentValues, Worksheet,
+    helper::coordinate::string_from_column_index,
+    structs::{
+        Chart, ChartType,
+        drawing::{
+            charts::{CategoryAxisData, GroupingValues, StringReference},
+            spreadsheet::MarkerType,
+        },
+    },
+};
+
+use crate::{
+    db_op::{RangeYearMonth, YearMonth},
+    locale::{Lang, Text},
+    report::{ColumnWalletJournal, SheetTaxList, SheetWalletJournal},
+    summary::SheetSummary,
+    template::ReportTemplate,
+};
+
+// 辅助数据表的起始列, 位于图表右侧
+const TABLE_COLUMN: u32 = 14;
+// 每个图表占用的行数
+const CHART_ROWS: u32 = 20;
+// 每个图表占用的列数
+const CHART_COLUMNS: u32 = 12;
+
+// 余额折线图的数据范围
+struct BalanceSeries {
+    date_times: String,
+    balances: String,
+}
+
+// 月度收入堆积柱形图的一个分类, 数据为各月收入单元格
+struct IncomeSeries {
+    category: String,
+    cells: Vec<String>,
+}
+
+// 每月应缴与实缴税额的计算公式
+struct TaxSeries {
+    assessed: Vec<String>,
+    collected: Vec<String>,
+}
+
+// 图表工作表, 数据均引用其他工作表, 以便在 Excel 中直接修改样式
+pub struct SheetCharts {
+    lang: Lang,
+    months: Vec<YearMonth>,
+    balance: Option<BalanceSeries>,
+    income: Vec<IncomeSeries>,
+    tax: Option<TaxSeries>,
+}
+
+impl SheetCharts {
+    pub fn new(start: YearMonth, end: YearMonth, lang: Lang) -> Self {
+        SheetCharts {
+            lang,
+            months: RangeYearMonth::new(start, end).collect(),
+            balance: None,
+            income: Vec::new(),
+            tax: None,
+        }
+    }
+
+    pub fn with_wallet_journal(mut self, sheet_name: &str, data: &SheetWalletJournal) -> Self {
+        // 模板中未输出日期时间或余额列时不生成余额折线图
+        let date_time = data.column_index(ColumnWalletJournal::DateTime);
+        let balance = data.column_index(ColumnWalletJournal::Balance);
+        let (date_time, balance) = match (date_time, balance) {
+            (Some(date_time), Some(balance)) => (date_time, balance),
+            _ => return self,
+        };
+        self.balance = data.data_rows().map(|(first, last)| BalanceSeries {
+            date_times: range_ref(sheet_name, (date_time, first), (date_time, last)),
+            balances: range_ref(sheet_name, (balance, first), (balance, last)),
+        });
+        self
+    }
+
+    pub fn with_summary(mut self, sheet_name: &str, data: &SheetSummary) -> Self {
+        self.income = data
+            .income_cells(self.lang)
+            .into_iter()
+            .map(|(category, cells)| IncomeSeries {
+                category,
+                cells: cells
+                    .into_iter()
+                    .map(|cell| cell_ref(sheet_name, cell))
+                    .collect(),
+            })
+            .collect();
+        self
+    }
+
+    pub fn with_tax_list(mut self, sheet_name: &str, data: &SheetTaxList) -> Self {
+        self.tax = data.data_rows().map(|(first, last)| {
+            let sum =
+                |col: u32| format!("SUM({})", range_ref(sheet_name, (col, first), (col, last)));
+            let columns = data.month_columns();
+            TaxSeries {
+                assessed: columns
+                    .iter()
+                    .map(|(pap, poll, _)| format!("{}+{}", sum(*pap), sum(*poll)))
+                    .collect(),
+                collected: columns.iter().map(|(_, _, paid)| sum(*paid)).collect(),
+            }
+        });
+        self
+    }
+
+    pub fn insert_worksheet(&self, w: &mut Worksheet, template: &ReportTemplate) {
+        let sheet_name = w.get_name().to_string();
+        let lang = self.lang;
+        let mut chart_row = 1;
+
+        if let Some(balance) = &self.balance {
+            let mut chart = Chart::default();
+            chart.new_chart(
+                ChartType::LineChart,
+                chart_marker(1, chart_row),
+                chart_marker(CHART_COLUMNS + 1, chart_row + CHART_ROWS),
+                vec![balance.balances.as_str()],
+            );
+            chart.set_series_title(vec![lang.text(Text::Balance)]);
+            set_categories(&mut chart, balance.date_times.as_str());
+            chart.set_title(lang.text(Text::Balance));
+            w.add_chart(chart);
+            chart_row += CHART_ROWS + 1;
+        }
+
+        // 辅助数据表: 首列为月份, 之后依次为各分类收入, 应缴税额, 实缴税额
+        let mut alignment = Alignment::default();
+        alignment.set_horizontal(HorizontalAlignmentValues::Center);
+        let c = w.get_cell_mut((TABLE_COLUMN, 1));
+        c.set_value_string(lang.text(Text::Month));
+        c.get_style_mut().set_alignment(alignment.clone());
+        for (i, ym) in self.months.iter().enumerate() {
+            w.get_cell_mut((TABLE_COLUMN, i as u32 + 2))
+                .set_value_string(lang.year_month(*ym));
+        }
+        let months = range_ref(
+            sheet_name.as_str(),
+            (TABLE_COLUMN, 2),
+            (TABLE_COLUMN, self.months.len() as u32 + 1),
+        );
+
+        let mut col = TABLE_COLUMN + 1;
+        let mut income_series = Vec::new();
+        for income in &self.income {
+            income_series.push(write_table_column(
+                w,
+                sheet_name.as_str(),
+                col,
+                income.category.as_str(),
+                income.cells.clone(),
+                template,
+            ));
+            col += 1;
+        }
+
+        if income_series.is_empty() == false {
+            let mut chart = Chart::default();
+            chart.new_chart(
+                ChartType::BarChart,
+                chart_marker(1, chart_row),
+                chart_marker(CHART_COLUMNS + 1, chart_row + CHART_ROWS),
+                income_series.iter().map(|s| s.as_str()).collect(),
+            );
+            let titles: Vec<&str> = self.income.iter().map(|s| s.category.as_str()).collect();
+            chart.set_series_title(titles);
+            set_categories(&mut chart, months.as_str());
+            chart.set_grouping(GroupingValues::Stacked);
+            chart.set_title(lang.text(Text::MonthlyIncome));
+            w.add_chart(chart);
+            chart_row += CHART_ROWS + 1;
+        }
+
+        if let Some(tax) = &self.tax {
+            let assessed = write_table_column(
+                w,
+                sheet_name.as_str(),
+                col,
+                lang.text(Text::AssessedTax),
+                tax.assessed.clone(),
+                template,
+            );
+            let collected = write_table_column(
+                w,
+                sheet_name.as_str(),
+                col + 1,
+                lang.text(Text::PaidUpTax),
+                tax.collected.clone(),
+                template,
+            );
+
+            let mut chart = Chart::default();
+            chart.new_chart(
+                ChartType::BarChart,
+                chart_marker(1, chart_row),
+                chart_marker(CHART_COLUMNS + 1, chart_row + CHART_ROWS),
+                vec![assessed.as_str(), collected.as_str()],
+            );
+            chart.set_series_title(vec![
+                lang.text(Text::AssessedTax),
+                lang.text(Text::PaidUpTax),
+            ]);
+            set_categories(&mut chart, months.as_str());
+            // 柱形图默认为堆积, 应缴与实缴需并列比较
+            chart.set_grouping(GroupingValues::Standard);
+            chart.set_title(lang.text(Text::AssessedAndPaidTax));
+            w.add_chart(chart);
+        }
+    }
+}
+
+// 写入辅助数据表的一列, 各行为引用数据工作表的公式, 返回该列数据范围
+fn write_table_column(
+    w: &mut Worksheet,
+    sheet_name: &str,
+    col: u32,
+    title: &str,
+    formulas: Vec<String>,
+    template: &ReportTemplate,
+) -> String {
+    let mut alignment = Alignment::default();
+    alignment.set_horizontal(HorizontalAlignmentValues::Center);
+    let c = w.get_cell_mut((col, 1));
+    c.set_value_string(title);
+    c.get_style_mut().set_alignment(alignment);
+
+    let len = formulas.len() as u32;
+    for (i, formula) in formulas.into_iter().enumerate() {
+        let c = w.get_cell_mut((col, i as u32 + 2));
+        c.set_formula(formula);
+        c.get_style_mut()
+            .set_numbering_format(template.isk_format());
+    }
+    range_ref(sheet_name, (col, 2), (col, len + 1))
+}
+
+// 各系列的分类标签引用同一数据范围, set_series_point_title 只能写入固定文字
+fn set_categories(chart: &mut Chart, range: &str) {
+    for series in chart
+        .get_area_chart_series_list_mut()
+        .get_area_chart_series_mut()
+    {
+        let mut reference = StringReference::default();
+        reference.get_formula_mut().set_address_str(range);
+        let mut data = CategoryAxisData::default();
+        data.set_string_reference(reference);
+        series.set_category_axis_data(data);
+    }
+}
+
+fn chart_marker(col: u32, row: u32) -> MarkerType {
+    let mut marker = MarkerType::default();
+    marker.set_coordinate(format!("{}{}", string_from_column_index(&col), row));
+    marker
+}
+
+// 带工作表名的绝对引用, 例如 '主账户流水'!$D$2
+fn cell_ref(sheet_name: &str, (col, row): (u32, u32)) -> String {
+    format!(
+        "'{}'!${}${}",
+        sheet_name,
+        string_from_column_index(&col),
+        row
+    )
+}
+
+fn range_ref(sheet_name: &str, from: (u32, u32), to: (u32, u32)) -> String {
+    format!(
+        "{}:${}${}",
+        cell_ref(sheet_name, from),
+        string_from_column_index(&to.0),
+        to.1
+    )
+}
+
+#[test]
+fn test_range_ref() {
+    assert_eq!(cell_ref("主账户流水", (4, 2)), "'主账户流水'!$D$2");
+    assert_eq!(
+        range_ref("税收清单", (27, 3), (27, 10)),
+        "'税收清单'!$AA$3:$AA$10"
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_chart_series() {
+    use crate::{
+        db_op::test_tax_database, period::ReportPeriod, report::Report, summary::SummaryCategories,
+    };
+    use umya_spreadsheet::new_file_empty_worksheet;
+
+    let db = test_tax_database().await;
+    let utc = chrono::FixedOffset::east_opt(0).unwrap();
+    let period = ReportPeriod::from_range("2025-09", "2025-10", utc).unwrap();
+    let report = Report::select_from_db(&db, &period, &SummaryCategories::default())
+        .await
+        .unwrap();
+    let template = ReportTemplate::default();
+    let data_charts = SheetCharts::new(report.start, report.end, Lang::Zh)
+        .with_wallet_journal("流水", &report.wallet_journal)
+        .with_tax_list("清单", &report.tax_list)
+        .with_summary("汇总", &report.summary);
+
+    let mut book = new_file_empty_worksheet();
+    let w = book.new_sheet("图表").unwrap();
+    data_charts.insert_worksheet(w, &template);
+
+    // 每个图表各系列的数据与分类标签引用, 工作表名无需引号时 umya 会去掉引号
+    let series = |chart: &Chart| -> Vec<(String, String)> {
+        let plot_area = chart.get_chart_space().get_chart().get_plot_area();
+        let list = match (plot_area.get_line_chart(), plot_area.get_bar_chart()) {
+            (Some(line), _) => line.get_area_chart_series_list(),
+            (None, Some(bar)) => bar.get_area_chart_series_list(),
+            _ => panic!("unexpected chart type"),
+        };
+        list.get_area_chart_series()
+            .iter()
+            .map(|s| {
+                let values = s.get_values().unwrap().get_number_reference();
+                let categories = s.get_category_axis_data().unwrap();
+                (
+                    values.get_formula().get_address_str(),
+                    categories
+                        .get_string_reference()
+                        .unwrap()
+                        .get_formula()
+                        .get_address_str(),
+                )
+            })
+            .collect()
+    };
+    let charts = w.get_chart_collection();
+    assert_eq!(charts.len(), 3);
+
+    // 3条流水位于第2至4行, 日期时间为第1列, 余额为第4列
+    assert_eq!(
+        series(&charts[0]),
+        vec![("流水!$D$2:$D$4".to_string(), "流水!$A$2:$A$4".to_string())]
+    );
+
+    // 收入与税额均引用辅助数据表, 分类为首列的月份
+    let months = "图表!$N$2:$N$3".to_string();
+    let income = series(&charts[1]);
+    assert_eq!(income.len(), report.summary.income_cells(Lang::Zh).len());
+    assert_eq!(income[0], ("图表!$O$2:$O$3".to_string(), months.clone()));
+    let col = string_from_column_index(&(TABLE_COLUMN + income.len() as u32 + 1));
+    assert_eq!(
+        series(&charts[2])[0],
+        (format!("图表!${}$2:${}$3", col, col), months.clone())
+    );
+    // 9月实缴税额为税收清单的第5列, 2名成员位于第3至4行
+    assert_eq!(
+        w.get_cell((TABLE_COLUMN + income.len() as u32 + 2, 2))
+            .unwrap()
+            .get_formula(),
+        "SUM('清单'!$E$3:$E$4)"
+    );
+    assert_eq!(
+        w.get_value((TABLE_COLUMN, 3)),
+        Lang::Zh.year_month(report.end)
+    );
+}
@@ -1,115 +1,303 @@
-use crate::esi::{
-    Portraits, ResCharacterPublicInformation, ResCorporationInformation,
-    ResCorporationWalletJournal,
+use crate::{
+    esi::{
+        ResCharacterPublicInformation, ResCorporationInformation, ResCorporationWalletJournal,
+        ResCorporationWalletJournalItem,
+    },
+    notify::EventQueue,
 };
-use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use db_wallet::{
-    JournalRefType,
+    JournalRefType, JournalSource,
     entities::{
         characters::{ActiveModel as AmCharacters, Column as CCharacters, Entity as ECharacters},
         corporation_wallet_journal::{
             ActiveModel as AmCorporationWalletJournal, Column as CCorporationWalletJournal,
             Entity as ECorporationWalletJournal,
         },
-        corporations::{ActiveModel as AmCorporations, Entity as ECorporations},
+        corporations::{
+            ActiveModel as AmCorporations, Column as CCorporations, Entity as ECorporations,
+        },
+        images::{ActiveModel as AmImages, Column as CImages, Entity as EImages},
         pap_journal::{Column as CPapJournal, Entity as EPapJournal},
+        party_history::{ActiveModel as AmPartyHistory, Entity as EPartyHistory},
+        party_images::{
+            ActiveModel as AmPartyImages, Column as CPartyImages, Entity as EPartyImages,
+        },
         tax_parameters::{Column as CTaxParameters, Entity as ETaxParameters},
         taxable_list::{Column as CTaxableList, Entity as ETaxableList},
         users::{Column as CUsers, Entity as EUsers},
     },
+    image_hash,
 };
 use rust_decimal::Decimal;
 use sea_orm::{
     ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, NotSet, QueryFilter,
-    QuerySelect, Set,
+    QueryOrder, QuerySelect, Set, sea_query::OnConflict,
 };
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+
+// 单条语句的最大行数或 IN 参数个数, 避免超过数据库的参数数量上限 (sqlite 为 32766)
+const BATCH_SIZE: usize = 1000;
 
+// 一页流水的入库结果
+pub struct UpgradeCount {
+    pub inserted: u64,
+    pub skipped: u64,  // 已存在的流水
+    pub replaced: u64, // 替换的导入流水, 已计入 inserted
+}
+
+// 一次查询出本页已存在的ID, 其余流水批量插入, 主键冲突的行直接跳过
+// 此前从游戏客户端导入的同一笔流水使用合成ID, 以 ESI 的流水替换, 避免重复计税
+// 由调用方负责开启事务
 pub async fn db_upgrade_wall_journal<DB: ConnectionTrait>(
     db: &DB,
     journal: ResCorporationWalletJournal,
-) -> Result<usize, String> {
-    let mut wait_write = Vec::new();
+    events: &mut EventQueue,
+) -> Result<UpgradeCount, String> {
+    let total = journal.0.len() as u64;
+    let ids: Vec<i64> = journal.0.iter().map(|item| item.id).collect();
+
+    let mut exist_ids = BTreeSet::new();
+    for chunk in ids.chunks(BATCH_SIZE) {
+        let rows = ECorporationWalletJournal::find()
+            .select_only()
+            .column_as(CCorporationWalletJournal::Id, "id")
+            .filter(CCorporationWalletJournal::Id.is_in(chunk.iter().copied()))
+            .into_model::<PartyId>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        exist_ids.extend(rows.into_iter().map(|r| r.id));
+    }
 
-    for item in journal.0 {
-        let row = ECorporationWalletJournal::find_by_id(item.id)
-            .one(db)
+    let wait_write: Vec<ResCorporationWalletJournalItem> = journal
+        .0
+        .into_iter()
+        .filter(|item| exist_ids.contains(&item.id) == false)
+        .collect();
+
+    let imported = find_imported_counterparts(db, &wait_write).await?;
+    let imported_ids: Vec<i64> = imported.values().copied().collect();
+    for chunk in imported_ids.chunks(BATCH_SIZE) {
+        ECorporationWalletJournal::delete_many()
+            .filter(CCorporationWalletJournal::Id.is_in(chunk.iter().copied()))
+            .exec(db)
             .await
             .map_err(|e| e.to_string())?;
+    }
 
-        if row.is_none() {
-            wait_write.push(item);
+    // 插入成功后再记录事件, 插入失败时不会产生多余的通知, 替换导入流水的不再通知
+    let inserted = insert_journal_items(db, &wait_write, JournalSource::Esi).await?;
+    for item in wait_write.iter() {
+        if imported.contains_key(&item.id) == false {
+            events.on_journal_inserted(item);
         }
     }
 
-    let count = wait_write.len();
-
-    for item in wait_write {
-        let data = AmCorporationWalletJournal {
-            id: Set(item.id),
-            date: Set(item.date.timestamp()),
-            description: Set(item.description),
-            ref_type: Set(item.ref_type as i32),
-            amount: Set(item.amount.map(|i| decimal_to_i64(i))),
-            balance: Set(item.balance.map(|i| decimal_to_i64(i))),
-            context_id: Set(item.context_id),
-            context_id_type: Set(item.context_id_type.map(|t| t as i32)),
-            reason: Set(item.reason),
-            first_party_id: Set(item.first_party_id),
-            second_party_id: Set(item.second_party_id),
-            tax: Set(item.tax.map(|i| decimal_to_i64(i))),
-            tax_receiver_id: Set(item.tax_receiver_id),
-        };
+    Ok(UpgradeCount {
+        inserted,
+        skipped: total - inserted,
+        replaced: imported.len() as u64,
+    })
+}
 
-        ECorporationWalletJournal::insert(data)
-            .exec(db)
+// 与 ESI 流水对应的导入流水, ESI ID -> 导入流水ID
+async fn find_imported_counterparts<DB: ConnectionTrait>(
+    db: &DB,
+    items: &[ResCorporationWalletJournalItem],
+) -> Result<BTreeMap<i64, i64>, String> {
+    find_counterparts(db, items, JournalSource::GameExport).await
+}
+
+// 已有的 source 来源流水中与 items 为同一笔的流水, 流水ID -> 已有流水ID
+// 按 类型, 金额, 余额 与 时间(误差1分钟) 对应, 导入时可能未能解析交易方, 因此不比较交易方
+async fn find_counterparts<DB: ConnectionTrait>(
+    db: &DB,
+    items: &[ResCorporationWalletJournalItem],
+    source: JournalSource,
+) -> Result<BTreeMap<i64, i64>, String> {
+    let dates = items.iter().map(|item| item.date.timestamp());
+    let (min, max) = match (dates.clone().min(), dates.max()) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return Ok(BTreeMap::new()),
+    };
+
+    let rows = ECorporationWalletJournal::find()
+        .filter(
+            Condition::all()
+                .add(CCorporationWalletJournal::Source.eq(source as i32))
+                .add(CCorporationWalletJournal::Date.gte(min - 60))
+                .add(CCorporationWalletJournal::Date.lte(max + 60)),
+        )
+        .order_by_asc(CCorporationWalletJournal::Date)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut counterparts = BTreeMap::new();
+    let mut used = BTreeSet::new();
+    for item in items {
+        let found = rows.iter().find(|r| {
+            used.contains(&r.id) == false
+                && r.ref_type == item.ref_type as i32
+                && r.amount == item.amount.map(decimal_to_i64)
+                && r.balance == item.balance.map(decimal_to_i64)
+                && (r.date - item.date.timestamp()).abs() <= 60
+        });
+        if let Some(r) = found {
+            used.insert(r.id);
+            counterparts.insert(item.id, r.id);
+        }
+    }
+    Ok(counterparts)
+}
+
+// 导入游戏客户端导出的流水, 跳过ID相同的已有流水, 以及已同步的同一笔 ESI 流水
+// 由调用方负责开启事务
+// (inserted, duplicated)
+pub async fn db_import_journal<DB: ConnectionTrait>(
+    db: &DB,
+    items: Vec<ResCorporationWalletJournalItem>,
+) -> Result<(usize, usize), String> {
+    let total = items.len();
+    let ids: Vec<i64> = items.iter().map(|item| item.id).collect();
+
+    let mut exist_ids = BTreeSet::new();
+    for chunk in ids.chunks(BATCH_SIZE) {
+        let rows = ECorporationWalletJournal::find()
+            .select_only()
+            .column_as(CCorporationWalletJournal::Id, "id")
+            .filter(CCorporationWalletJournal::Id.is_in(chunk.iter().copied()))
+            .into_model::<PartyId>()
+            .all(db)
             .await
             .map_err(|e| e.to_string())?;
+        exist_ids.extend(rows.into_iter().map(|r| r.id));
     }
 
-    Ok(count)
+    let items: Vec<ResCorporationWalletJournalItem> = items
+        .into_iter()
+        .filter(|item| exist_ids.contains(&item.id) == false)
+        .collect();
+    let synced = find_counterparts(db, &items, JournalSource::Esi).await?;
+    let wait_write: Vec<ResCorporationWalletJournalItem> = items
+        .into_iter()
+        .filter(|item| synced.contains_key(&item.id) == false)
+        .collect();
+
+    let inserted = insert_journal_items(db, &wait_write, JournalSource::GameExport).await? as usize;
+
+    Ok((inserted, total - inserted))
 }
 
-#[derive(FromQueryResult)]
-struct FirstPartyId {
-    first_party_id: Option<i64>,
+fn journal_active_model(
+    item: &ResCorporationWalletJournalItem,
+    source: JournalSource,
+) -> AmCorporationWalletJournal {
+    AmCorporationWalletJournal {
+        id: Set(item.id),
+        date: Set(item.date.timestamp()),
+        description: Set(item.description.clone()),
+        ref_type: Set(item.ref_type as i32),
+        amount: Set(item.amount.map(|i| decimal_to_i64(i))),
+        balance: Set(item.balance.map(|i| decimal_to_i64(i))),
+        context_id: Set(item.context_id),
+        context_id_type: Set(item.context_id_type.map(|t| t as i32)),
+        reason: Set(item.reason.clone()),
+        first_party_id: Set(item.first_party_id),
+        second_party_id: Set(item.second_party_id),
+        tax: Set(item.tax.map(|i| decimal_to_i64(i))),
+        tax_receiver_id: Set(item.tax_receiver_id),
+        source: Set(source as i32),
+    }
 }
 
-#[derive(FromQueryResult)]
-struct SecondPartyId {
-    second_party_id: Option<i64>,
+// 分批插入流水, ID 已存在的行不做处理, 返回实际插入的行数
+async fn insert_journal_items<DB: ConnectionTrait>(
+    db: &DB,
+    items: &[ResCorporationWalletJournalItem],
+    source: JournalSource,
+) -> Result<u64, String> {
+    let mut inserted = 0;
+
+    for chunk in items.chunks(BATCH_SIZE) {
+        let data: Vec<AmCorporationWalletJournal> = chunk
+            .iter()
+            .map(|item| journal_active_model(item, source))
+            .collect();
+
+        inserted += ECorporationWalletJournal::insert_many(data)
+            .on_conflict(
+                OnConflict::column(CCorporationWalletJournal::Id)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(inserted)
 }
 
-// 获取 corporation_wallet_journal first_party_id second_party_id 中的所有ID
-pub async fn get_all_ids<DB: ConnectionTrait>(db: &DB) -> Result<Vec<i64>, String> {
-    let first_party_ids = ECorporationWalletJournal::find()
-        .select_only()
-        .column(CCorporationWalletJournal::FirstPartyId)
-        .group_by(CCorporationWalletJournal::FirstPartyId)
-        .into_model::<FirstPartyId>()
-        .all(db)
+// 最早登记计税的月份, 未登记时为 None
+pub async fn get_first_taxable_month<DB: ConnectionTrait>(
+    db: &DB,
+) -> Result<Option<YearMonth>, String> {
+    let data = ETaxableList::find()
+        .order_by_asc(CTaxableList::Year)
+        .order_by_asc(CTaxableList::Month)
+        .one(db)
         .await
         .map_err(|e| e.to_string())?;
 
-    let second_party_ids = ECorporationWalletJournal::find()
+    Ok(data.map(|t| YearMonth::new(t.year as i16, t.month as u8)))
+}
+
+// 获取已入库流水中最新的日期时间
+pub async fn get_latest_journal_date<DB: ConnectionTrait>(
+    db: &DB,
+) -> Result<Option<DateTime<Utc>>, String> {
+    #[derive(FromQueryResult)]
+    struct RowData {
+        date: i64,
+    }
+
+    let data = ECorporationWalletJournal::find()
         .select_only()
-        .column(CCorporationWalletJournal::SecondPartyId)
-        .group_by(CCorporationWalletJournal::SecondPartyId)
-        .into_model::<SecondPartyId>()
-        .all(db)
+        .column(CCorporationWalletJournal::Date)
+        .order_by_desc(CCorporationWalletJournal::Date)
+        .into_model::<RowData>()
+        .one(db)
         .await
         .map_err(|e| e.to_string())?;
 
+    Ok(data.map(|d| DateTime::from_timestamp_secs(d.date).unwrap()))
+}
+
+#[derive(FromQueryResult)]
+struct PartyId {
+    id: i64,
+}
+
+// 获取 corporation_wallet_journal first_party_id second_party_id 中的所有ID
+pub async fn get_all_ids<DB: ConnectionTrait>(db: &DB) -> Result<Vec<i64>, String> {
     let mut ids = BTreeSet::<i64>::new();
-    for m in first_party_ids {
-        if let Some(id) = m.first_party_id {
-            ids.insert(id);
-        }
-    }
-    for m in second_party_ids {
-        if let Some(id) = m.second_party_id {
-            ids.insert(id);
-        }
+
+    for column in [
+        CCorporationWalletJournal::FirstPartyId,
+        CCorporationWalletJournal::SecondPartyId,
+    ] {
+        let party_ids = ECorporationWalletJournal::find()
+            .select_only()
+            .column_as(column, "id")
+            .distinct()
+            .filter(column.is_not_null())
+            .into_model::<PartyId>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        ids.extend(party_ids.into_iter().map(|m| m.id));
     }
 
     // 需要排除的ID号
@@ -188,41 +376,34 @@ pub async fn get_corporation_name<DB: ConnectionTrait>(
     Ok(d.map(|c| c.name.clone()))
 }
 
-// 插入角色数据
-pub async fn insert_character_info<DB: ConnectionTrait>(
+// 按名称查找角色或公司ID, 优先匹配角色
+pub async fn find_party_id_by_name<DB: ConnectionTrait>(
     db: &DB,
-    character_id: i64,
-    info: ResCharacterPublicInformation,
-    portraits: Portraits,
-) -> Result<(), String> {
-    let m = AmCharacters {
-        character_id: Set(character_id),
-        alliance_id: Set(info.alliance_id),
-        corporation_id: Set(info.corporation_id),
-        birthday: Set(info.birthday.timestamp()),
-        name: Set(info.name),
-        user_id: NotSet,
-        main: Set(false),
-        portrait64: Set(Some(portraits.portrait64)),
-        portrait128: Set(Some(portraits.portrait128)),
-        portrait256: Set(Some(portraits.portrait256)),
-        portrait512: Set(Some(portraits.portrait512)),
-    };
-
-    ECharacters::insert(m)
-        .exec(db)
+    name: &str,
+) -> Result<Option<i64>, String> {
+    let character = ECharacters::find()
+        .filter(CCharacters::Name.eq(name))
+        .one(db)
         .await
         .map_err(|e| e.to_string())?;
+    if let Some(c) = character {
+        return Ok(Some(c.character_id));
+    }
 
-    Ok(())
+    let corporation = ECorporations::find()
+        .filter(CCorporations::Name.eq(name))
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(corporation.map(|c| c.corporation_id))
 }
 
-// 更新角色数据
-pub async fn update_character_info<DB: ConnectionTrait>(
+// 插入角色数据
+pub async fn insert_character_info<DB: ConnectionTrait>(
     db: &DB,
     character_id: i64,
     info: ResCharacterPublicInformation,
-    portraits: Portraits,
+    portrait: Vec<u8>,
 ) -> Result<(), String> {
     let m = AmCharacters {
         character_id: Set(character_id),
@@ -232,16 +413,14 @@ pub async fn update_character_info<DB: ConnectionTrait>(
         name: Set(info.name),
         user_id: NotSet,
         main: Set(false),
-        portrait64: Set(Some(portraits.portrait64)),
-        portrait128: Set(Some(portraits.portrait128)),
-        portrait256: Set(Some(portraits.portrait256)),
-        portrait512: Set(Some(portraits.portrait512)),
+        last_refreshed: Set(Some(Utc::now().timestamp())),
     };
 
-    ECharacters::update(m)
+    ECharacters::insert(m)
         .exec(db)
         .await
         .map_err(|e| e.to_string())?;
+    save_party_image(db, character_id, portrait).await?;
 
     Ok(())
 }
@@ -258,6 +437,8 @@ pub async fn insert_corporation_info<DB: ConnectionTrait>(
         ticker: Set(info.ticker),
         date_founded: Set(info.date_founded.map(|t| t.timestamp())),
         description: Set(info.description),
+        alliance_id: Set(info.alliance_id),
+        last_refreshed: Set(Some(Utc::now().timestamp())),
     };
 
     ECorporations::insert(m)
@@ -280,6 +461,8 @@ pub async fn update_corporation_info<DB: ConnectionTrait>(
         ticker: Set(info.ticker),
         date_founded: Set(info.date_founded.map(|t| t.timestamp())),
         description: Set(info.description),
+        alliance_id: Set(info.alliance_id),
+        last_refreshed: Set(Some(Utc::now().timestamp())),
     };
 
     ECorporations::update(m)
@@ -290,22 +473,349 @@ pub async fn update_corporation_info<DB: ConnectionTrait>(
     Ok(())
 }
 
-// 获取所有用户ID
-pub async fn get_users_ids<DB: ConnectionTrait>(db: &DB) -> Result<Vec<i32>, String> {
+// 保存角色头像或公司, 联盟图标, 相同内容的图片只保存一份
+pub async fn save_party_image<DB: ConnectionTrait>(
+    db: &DB,
+    party_id: i64,
+    data: Vec<u8>,
+) -> Result<(), String> {
+    let hash = image_hash(&data);
+
+    let m = AmImages {
+        hash: Set(hash.clone()),
+        data: Set(data),
+    };
+    EImages::insert(m)
+        .on_conflict(OnConflict::column(CImages::Hash).do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let m = AmPartyImages {
+        party_id: Set(party_id),
+        image_hash: Set(hash),
+    };
+    EPartyImages::insert(m)
+        .on_conflict(
+            OnConflict::column(CPartyImages::PartyId)
+                .update_column(CPartyImages::ImageHash)
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// 批量获取原图, 没有图片的ID不在结果中
+pub async fn get_party_images<DB: ConnectionTrait>(
+    db: &DB,
+    ids: &BTreeSet<i64>,
+) -> Result<BTreeMap<i64, Vec<u8>>, String> {
+    let ids: Vec<i64> = ids.iter().copied().collect();
+    let mut hashes = BTreeMap::new();
+    for chunk in ids.chunks(BATCH_SIZE) {
+        let rows = EPartyImages::find()
+            .filter(CPartyImages::PartyId.is_in(chunk.iter().copied()))
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        for r in rows {
+            hashes.insert(r.party_id, r.image_hash);
+        }
+    }
+
+    let unique: BTreeSet<String> = hashes.values().cloned().collect();
+    let unique: Vec<String> = unique.into_iter().collect();
+    let mut data = BTreeMap::new();
+    for chunk in unique.chunks(BATCH_SIZE) {
+        let rows = EImages::find()
+            .filter(CImages::Hash.is_in(chunk.iter().cloned()))
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        for r in rows {
+            data.insert(r.hash, r.data);
+        }
+    }
+
+    Ok(hashes
+        .into_iter()
+        .filter_map(|(id, hash)| data.get(&hash).map(|d| (id, d.clone())))
+        .collect())
+}
+
+// 用户的主角色ID, 没有标注主角色的用户不在结果中
+pub async fn get_main_character_ids<DB: ConnectionTrait>(
+    db: &DB,
+    users_ids: &[i32],
+) -> Result<BTreeMap<i32, i64>, String> {
+    let mut ids = BTreeMap::new();
+    for chunk in users_ids.chunks(BATCH_SIZE) {
+        let characters = ECharacters::find()
+            .filter(
+                Condition::all()
+                    .add(CCharacters::UserId.is_in(chunk.iter().copied()))
+                    .add(CCharacters::Main.eq(true)),
+            )
+            .order_by_asc(CCharacters::CharacterId)
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        for c in characters {
+            if let Some(user_id) = c.user_id {
+                ids.entry(user_id).or_insert(c.character_id);
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+// 还没有图标的公司与联盟
+pub async fn get_ids_without_logo<DB: ConnectionTrait>(
+    db: &DB,
+) -> Result<(Vec<i64>, Vec<i64>), String> {
+    #[derive(FromQueryResult)]
+    struct RowData {
+        id: Option<i64>,
+    }
+
+    let with_image: BTreeSet<i64> = EPartyImages::find()
+        .select_only()
+        .column_as(CPartyImages::PartyId, "id")
+        .into_model::<RowData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .filter_map(|r| r.id)
+        .collect();
+
+    let corporation_ids = ECorporations::find()
+        .select_only()
+        .column_as(CCorporations::CorporationId, "id")
+        .into_model::<RowData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut alliance_ids = BTreeSet::new();
+    for rows in [
+        ECharacters::find()
+            .select_only()
+            .column_as(CCharacters::AllianceId, "id")
+            .distinct()
+            .into_model::<RowData>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?,
+        ECorporations::find()
+            .select_only()
+            .column_as(CCorporations::AllianceId, "id")
+            .distinct()
+            .into_model::<RowData>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?,
+    ] {
+        alliance_ids.extend(rows.iter().filter_map(|r| r.id));
+    }
+
+    let corporation_ids = corporation_ids
+        .iter()
+        .filter_map(|r| r.id)
+        .filter(|id| with_image.contains(id) == false)
+        .collect();
+    let alliance_ids = alliance_ids
+        .into_iter()
+        .filter(|id| with_image.contains(id) == false)
+        .collect();
+
+    Ok((corporation_ids, alliance_ids))
+}
+
+// 从未刷新或刷新时间早于 before 的角色
+pub async fn get_stale_character_ids<DB: ConnectionTrait>(
+    db: &DB,
+    before: i64,
+) -> Result<Vec<i64>, String> {
     #[derive(FromQueryResult)]
     struct RowData {
-        id: i32,
+        character_id: i64,
     }
 
-    let data = EUsers::find()
+    let ids = ECharacters::find()
         .select_only()
-        .column(CUsers::Id)
+        .column(CCharacters::CharacterId)
+        .filter(
+            Condition::any()
+                .add(CCharacters::LastRefreshed.is_null())
+                .add(CCharacters::LastRefreshed.lt(before)),
+        )
+        .order_by_asc(CCharacters::CharacterId)
         .into_model::<RowData>()
         .all(db)
         .await
         .map_err(|e| e.to_string())?;
 
-    Ok(data.iter().map(|c| c.id).collect())
+    Ok(ids.iter().map(|r| r.character_id).collect())
+}
+
+// 从未刷新或刷新时间早于 before 的公司
+pub async fn get_stale_corporation_ids<DB: ConnectionTrait>(
+    db: &DB,
+    before: i64,
+) -> Result<Vec<i64>, String> {
+    #[derive(FromQueryResult)]
+    struct RowData {
+        corporation_id: i64,
+    }
+
+    let ids = ECorporations::find()
+        .select_only()
+        .column(CCorporations::CorporationId)
+        .filter(
+            Condition::any()
+                .add(CCorporations::LastRefreshed.is_null())
+                .add(CCorporations::LastRefreshed.lt(before)),
+        )
+        .order_by_asc(CCorporations::CorporationId)
+        .into_model::<RowData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(ids.iter().map(|r| r.corporation_id).collect())
+}
+
+// 角色或公司的一次变更, 仅包含变化的字段, 值为 (旧, 新)
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PartyChange {
+    pub corporation_id: Option<(i64, i64)>,
+    pub alliance_id: Option<(Option<i64>, Option<i64>)>,
+    pub name: Option<(String, String)>,
+}
+
+// 比较新旧数据, 有变化时写入 party_history
+// 公司没有所属公司, corporation_id 为 None
+async fn record_party_change<DB: ConnectionTrait>(
+    db: &DB,
+    party_id: i64,
+    corporation_id: Option<(i64, i64)>,
+    alliance_id: (Option<i64>, Option<i64>),
+    name: (String, String),
+    now: i64,
+) -> Result<Option<PartyChange>, String> {
+    let change = PartyChange {
+        corporation_id: corporation_id.filter(|(old, new)| old != new),
+        alliance_id: (alliance_id.0 != alliance_id.1).then_some(alliance_id),
+        name: (name.0 != name.1).then(|| name.clone()),
+    };
+    if change.corporation_id.is_none() && change.alliance_id.is_none() && change.name.is_none() {
+        return Ok(None);
+    }
+
+    let m = AmPartyHistory {
+        id: NotSet,
+        party_id: Set(party_id),
+        changed_at: Set(now),
+        old_corporation_id: Set(corporation_id.map(|c| c.0)),
+        new_corporation_id: Set(corporation_id.map(|c| c.1)),
+        old_alliance_id: Set(alliance_id.0),
+        new_alliance_id: Set(alliance_id.1),
+        old_name: Set(Some(name.0)),
+        new_name: Set(Some(name.1)),
+    };
+    EPartyHistory::insert(m)
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(change))
+}
+
+// 以 ESI 的最新数据刷新角色的公司, 联盟与名称, 不修改用户关联与头像
+// 有变化时写入 party_history
+pub async fn refresh_character_info<DB: ConnectionTrait>(
+    db: &DB,
+    character_id: i64,
+    info: ResCharacterPublicInformation,
+    now: i64,
+) -> Result<Option<PartyChange>, String> {
+    let old = ECharacters::find_by_id(character_id)
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("unknown character_id: {}", character_id))?;
+
+    let change = record_party_change(
+        db,
+        character_id,
+        Some((old.corporation_id, info.corporation_id)),
+        (old.alliance_id, info.alliance_id),
+        (old.name, info.name.clone()),
+        now,
+    )
+    .await?;
+
+    let m = AmCharacters {
+        character_id: Set(character_id),
+        alliance_id: Set(info.alliance_id),
+        corporation_id: Set(info.corporation_id),
+        birthday: NotSet,
+        name: Set(info.name),
+        user_id: NotSet,
+        main: NotSet,
+        last_refreshed: Set(Some(now)),
+    };
+    ECharacters::update(m)
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(change)
+}
+
+// 以 ESI 的最新数据刷新公司, 有变化时写入 party_history
+pub async fn refresh_corporation_info<DB: ConnectionTrait>(
+    db: &DB,
+    corporation_id: i64,
+    info: ResCorporationInformation,
+    now: i64,
+) -> Result<Option<PartyChange>, String> {
+    let old = ECorporations::find_by_id(corporation_id)
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or(format!("unknown corporation_id: {}", corporation_id))?;
+
+    let change = record_party_change(
+        db,
+        corporation_id,
+        None,
+        (old.alliance_id, info.alliance_id),
+        (old.name, info.name.clone()),
+        now,
+    )
+    .await?;
+
+    let m = AmCorporations {
+        corporation_id: Set(corporation_id),
+        name: Set(info.name),
+        ticker: Set(info.ticker),
+        date_founded: Set(info.date_founded.map(|t| t.timestamp())),
+        description: Set(info.description),
+        alliance_id: Set(info.alliance_id),
+        last_refreshed: Set(Some(now)),
+    };
+    ECorporations::update(m)
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(change)
 }
 
 // 获取指定用户的所有角色
@@ -330,6 +840,52 @@ pub async fn get_user_characters_ids<DB: ConnectionTrait>(
     Ok(ids.iter().map(|c| c.character_id).collect())
 }
 
+// 获取指定用户的所有角色 (角色ID, 角色名)
+pub async fn get_user_characters<DB: ConnectionTrait>(
+    db: &DB,
+    user_id: i32,
+) -> Result<Vec<(i64, String)>, String> {
+    #[derive(FromQueryResult)]
+    struct RowData {
+        character_id: i64,
+        name: String,
+    }
+
+    let data = ECharacters::find()
+        .select_only()
+        .column(CCharacters::CharacterId)
+        .column(CCharacters::Name)
+        .filter(CCharacters::UserId.eq(user_id))
+        .order_by_asc(CCharacters::CharacterId)
+        .into_model::<RowData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(data.into_iter().map(|c| (c.character_id, c.name)).collect())
+}
+
+// 获取所有已关联用户的角色ID
+pub async fn get_linked_character_ids<DB: ConnectionTrait>(
+    db: &DB,
+) -> Result<BTreeSet<i64>, String> {
+    #[derive(FromQueryResult)]
+    struct RowData {
+        character_id: i64,
+    }
+
+    let ids = ECharacters::find()
+        .select_only()
+        .column(CCharacters::CharacterId)
+        .filter(CCharacters::UserId.is_not_null())
+        .into_model::<RowData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(ids.iter().map(|c| c.character_id).collect())
+}
+
 // 获取指定用户的主角色名, 若没有标注主角色,  则返回微信群昵称
 pub async fn get_user_main_character_name<DB: ConnectionTrait>(
     db: &DB,
@@ -371,217 +927,469 @@ pub async fn get_user_main_character_name<DB: ConnectionTrait>(
     Err("User not found".to_string())
 }
 
-// 查询指定角色在指定时间范围内上缴的税收总数
-pub async fn find_character_tax_amount<DB: ConnectionTrait>(
+// 获取指定用户在微信群中的称呼, 依次取群昵称与微信昵称
+pub async fn get_user_we_chat_nickname<DB: ConnectionTrait>(
     db: &DB,
-    character_id: i64,
-    start_time: DateTime<Utc>,
-    end_time: DateTime<Utc>,
-) -> Result<Decimal, String> {
+    user_id: i32,
+) -> Result<Option<String>, String> {
+    let user = EUsers::find_by_id(user_id)
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(user.and_then(|u| u.we_chat_group_nickname.or(u.we_chat_nick_name)))
+}
+
+// 缴税流水
+pub struct PayTaxJournal {
+    pub date_time: DateTime<Utc>,
+    pub character_id: i64,
+    pub amount: Decimal,
+}
+
+// 查询指定用户所有角色的全部缴税流水, 按时间升序
+pub async fn find_user_pay_tax_journal<DB: ConnectionTrait>(
+    db: &DB,
+    user_id: i32,
+) -> Result<Vec<PayTaxJournal>, String> {
     #[derive(FromQueryResult)]
     struct RowData {
+        date: i64,
+        first_party_id: i64,
         amount: i64,
     }
 
-    assert!(start_time <= end_time);
-
-    let start_time = start_time.timestamp();
-    let end_time = end_time.timestamp();
+    let ids = get_user_characters_ids(db, user_id).await?;
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
 
     let data = ECorporationWalletJournal::find()
         .select_only()
+        .column(CCorporationWalletJournal::Date)
+        .column(CCorporationWalletJournal::FirstPartyId)
         .column(CCorporationWalletJournal::Amount)
         .filter(
             Condition::all()
-                .add(CCorporationWalletJournal::Date.gte(start_time))
-                .add(CCorporationWalletJournal::Date.lt(end_time))
-                .add(CCorporationWalletJournal::FirstPartyId.eq(character_id))
+                .add(CCorporationWalletJournal::FirstPartyId.is_in(ids))
                 .add(CCorporationWalletJournal::RefType.eq(JournalRefType::PlayerDonation as i32))
                 .add(CCorporationWalletJournal::Amount.gt(0)),
         )
+        .order_by_asc(CCorporationWalletJournal::Date)
         .into_model::<RowData>()
         .all(db)
         .await
         .map_err(|e| e.to_string())?;
 
-    let mut sum = Decimal::ZERO;
-    for d in data {
-        sum += decimal_from_i64(d.amount);
-    }
+    let journal = data
+        .into_iter()
+        .map(|d| PayTaxJournal {
+            date_time: DateTime::from_timestamp_secs(d.date).unwrap(),
+            character_id: d.first_party_id,
+            amount: decimal_from_i64(d.amount),
+        })
+        .collect();
 
-    Ok(sum)
+    Ok(journal)
 }
 
-// 查询指定用户在指定时间范围内上缴的税收总额
-pub async fn find_user_pay_tax_amount<DB: ConnectionTrait>(
+// 获取指定用户在 taxable_list 中登记过的所有月份, 升序
+pub async fn get_user_taxable_year_months<DB: ConnectionTrait>(
     db: &DB,
     user_id: i32,
-    start_time: DateTime<Utc>,
-    end_time: DateTime<Utc>,
-) -> Result<Decimal, String> {
-    assert!(start_time <= end_time);
-    let ids = get_user_characters_ids(db, user_id).await?;
-    let mut sum = Decimal::ZERO;
-    for character_id in ids {
-        let a = find_character_tax_amount(db, character_id, start_time, end_time).await?;
-        sum += a;
+) -> Result<Vec<YearMonth>, String> {
+    #[derive(FromQueryResult)]
+    struct RowData {
+        year: i32,
+        month: i32,
     }
-    Ok(sum)
-}
 
-// 查询指定用户在指定月份上缴的税收总额
-pub async fn find_user_year_month_pay_tax<DB: ConnectionTrait>(
-    db: &DB,
-    user_id: i32,
-    year_month: YearMonth,
-) -> Result<Decimal, String> {
-    let start = year_month.lower();
-    let end = year_month.upper();
-    find_user_pay_tax_amount(db, user_id, start, end).await
+    let data = ETaxableList::find()
+        .select_only()
+        .column(CTaxableList::Year)
+        .column(CTaxableList::Month)
+        .filter(CTaxableList::UserId.eq(user_id))
+        .into_model::<RowData>()
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut year_months: Vec<YearMonth> = data
+        .iter()
+        .map(|d| YearMonth::new(d.year as i16, d.month as u8))
+        .collect();
+    year_months.sort();
+    year_months.dedup();
+
+    Ok(year_months)
 }
 
-// 获取指定用户在指定月份需上缴的税收
-// (poll_tax, pap_tax)
-pub async fn get_user_tax<DB: ConnectionTrait>(
-    db: &DB,
-    user_id: i32,
-    year_month: YearMonth,
-) -> Result<(Decimal, Decimal), String> {
-    let mut poll_tax_amount = Decimal::ZERO;
-    let mut pap_tax_amount = Decimal::ZERO;
+// 一次查询出的角色与公司名称, 用于报表中按ID显示名称
+pub struct PartyNames {
+    characters: BTreeMap<i64, String>,
+    corporations: BTreeMap<i64, String>,
+}
 
-    let (flag_poll_tax, flag_pap_tax) = get_user_taxable(db, user_id, year_month).await?;
-    if (flag_poll_tax == false) & (flag_pap_tax == false) {
-        return Ok((poll_tax_amount, pap_tax_amount));
+impl PartyNames {
+    // 与 check_id 相同, Some(true) 表示 character, Some(false) 表示 corporation
+    pub fn kind(&self, id: i64) -> Option<bool> {
+        if self.characters.contains_key(&id) {
+            Some(true)
+        } else if self.corporations.contains_key(&id) {
+            Some(false)
+        } else {
+            None
+        }
     }
 
-    let (par_poll_tax, par_pap_tax, par_pap_standard) = get_tax_parameters(db, year_month).await?;
+    pub fn character_name(&self, id: i64) -> Option<&str> {
+        self.characters.get(&id).map(|n| n.as_str())
+    }
 
-    if flag_poll_tax {
-        poll_tax_amount = par_poll_tax;
+    pub fn corporation_name(&self, id: i64) -> Option<&str> {
+        self.corporations.get(&id).map(|n| n.as_str())
     }
 
-    if flag_pap_tax {
-        let user_pap = find_user_pap(db, user_id, year_month).await?;
-        let delta_pap = par_pap_standard - user_pap;
-        if delta_pap.is_sign_positive() {
-            pap_tax_amount = delta_pap * par_pap_tax;
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        ids: &BTreeSet<i64>,
+    ) -> Result<PartyNames, String> {
+        #[derive(FromQueryResult)]
+        struct RowData {
+            id: i64,
+            name: String,
+        }
+
+        let ids: Vec<i64> = ids.iter().copied().collect();
+        let mut characters = BTreeMap::new();
+        let mut corporations = BTreeMap::new();
+
+        for chunk in ids.chunks(BATCH_SIZE) {
+            let data = ECharacters::find()
+                .select_only()
+                .column_as(CCharacters::CharacterId, "id")
+                .column(CCharacters::Name)
+                .filter(CCharacters::CharacterId.is_in(chunk.iter().copied()))
+                .into_model::<RowData>()
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            characters.extend(data.into_iter().map(|d| (d.id, d.name)));
+
+            let data = ECorporations::find()
+                .select_only()
+                .column_as(CCorporations::CorporationId, "id")
+                .column(CCorporations::Name)
+                .filter(CCorporations::CorporationId.is_in(chunk.iter().copied()))
+                .into_model::<RowData>()
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            corporations.extend(data.into_iter().map(|d| (d.id, d.name)));
         }
+
+        Ok(PartyNames {
+            characters,
+            corporations,
+        })
     }
+}
 
-    Ok((poll_tax_amount, pap_tax_amount))
+// 指定月份范围内计算税收所需的全部数据, 以少量查询一次取出后在内存中关联
+pub struct TaxLedger {
+    users_ids: Vec<i32>,
+    main_character_names: BTreeMap<i32, String>,
+    main_character_ids: BTreeMap<i32, i64>,
+    group_nicknames: BTreeMap<i32, String>,
+    notes: BTreeMap<i32, String>,
+    taxable: BTreeMap<(i32, YearMonth), (bool, bool)>, // (poll_tax, pap_tax)
+    parameters: BTreeMap<YearMonth, (Decimal, Decimal, Decimal)>, // (poll_tax, pap_tax, pap_standard)
+    paps: BTreeMap<(i32, YearMonth), Decimal>,
+    character_paps: BTreeMap<(i64, YearMonth), Decimal>,
+    payments: BTreeMap<(i32, YearMonth), Decimal>,
 }
 
-// 获取指定用户在指定月份是否需要交税
-// (poll_tax, pap_tax)
-pub async fn get_user_taxable<DB: ConnectionTrait>(
-    db: &DB,
-    user_id: i32,
-    year_month: YearMonth,
-) -> Result<(bool, bool), String> {
-    #[derive(FromQueryResult)]
-    struct RowData {
-        poll_tax: bool,
-        pap_tax: bool,
+impl TaxLedger {
+    pub fn users_ids(&self) -> &[i32] {
+        &self.users_ids
     }
 
-    let data = ETaxableList::find()
-        .filter(
-            Condition::all()
-                .add(CTaxableList::UserId.eq(user_id))
-                .add(CTaxableList::Year.eq(year_month.year as i32))
-                .add(CTaxableList::Month.eq(year_month.month as i32)),
-        )
-        .into_model::<RowData>()
-        .one(db)
-        .await
-        .map_err(|e| e.to_string())?;
+    // 主角色名, 若没有标注主角色, 则返回微信群昵称
+    pub fn main_character_name(&self, user_id: i32) -> Result<String, String> {
+        self.main_character_names
+            .get(&user_id)
+            .or(self.group_nicknames.get(&user_id))
+            .cloned()
+            .ok_or("User not found".to_string())
+    }
 
-    match data {
-        None => Ok((false, false)),
-        Some(o) => Ok((o.poll_tax, o.pap_tax)),
+    pub fn main_character_id(&self, user_id: i32) -> Option<i64> {
+        self.main_character_ids.get(&user_id).copied()
     }
-}
 
-// 查询指定角色在指定月份的PAP分
-pub async fn find_character_pap<DB: ConnectionTrait>(
-    db: &DB,
-    character_id: i64,
-    year_month: YearMonth,
-) -> Result<Decimal, String> {
-    #[derive(FromQueryResult)]
-    struct RowData {
-        pap: i32,
+    // 税收清单中的备注
+    pub fn note(&self, user_id: i32) -> Option<&str> {
+        self.notes.get(&user_id).map(|n| n.as_str())
     }
 
-    let data = EPapJournal::find()
-        .filter(
-            Condition::all()
-                .add(CPapJournal::CharacterId.eq(character_id))
-                .add(CPapJournal::Year.eq(year_month.year as i32))
-                .add(CPapJournal::Month.eq(year_month.month as i32)),
-        )
-        .into_model::<RowData>()
-        .one(db)
-        .await
-        .map_err(|e| e.to_string())?;
+    // 指定用户在指定月份是否需缴纳 (poll_tax, pap_tax)
+    pub fn taxable(&self, user_id: i32, year_month: YearMonth) -> (bool, bool) {
+        self.taxable
+            .get(&(user_id, year_month))
+            .copied()
+            .unwrap_or((false, false))
+    }
 
-    match data {
-        None => Ok(Decimal::ZERO),
-        Some(o) => Ok(decimal_from_i64(o.pap as i64)),
+    // 指定月份的税收参数 (poll_tax, pap_tax, pap_standard)
+    pub fn parameters(&self, year_month: YearMonth) -> Option<(Decimal, Decimal, Decimal)> {
+        self.parameters.get(&year_month).copied()
     }
-}
 
-// 查询指定用户在指定月份的PAP分
-pub async fn find_user_pap<DB: ConnectionTrait>(
-    db: &DB,
-    user_id: i32,
-    year_month: YearMonth,
-) -> Result<Decimal, String> {
-    let ids = get_user_characters_ids(db, user_id).await?;
-    let mut sum = Decimal::ZERO;
-    for id in ids {
-        let pap = find_character_pap(db, id, year_month).await?;
-        sum += pap;
+    // 指定用户所有角色在指定月份的 PAP 之和
+    pub fn user_pap(&self, user_id: i32, year_month: YearMonth) -> Decimal {
+        self.paps
+            .get(&(user_id, year_month))
+            .copied()
+            .unwrap_or_default()
     }
 
-    Ok(sum)
-}
+    // 指定角色在指定月份的PAP分
+    pub fn character_pap(&self, character_id: i64, year_month: YearMonth) -> Decimal {
+        self.character_paps
+            .get(&(character_id, year_month))
+            .copied()
+            .unwrap_or_default()
+    }
 
-// 获取指定月份的税收计算参数
-// (poll_tax, pap_tax, pap_standard)
-pub async fn get_tax_parameters<DB: ConnectionTrait>(
-    db: &DB,
-    year_month: YearMonth,
-) -> Result<(Decimal, Decimal, Decimal), String> {
-    #[derive(FromQueryResult)]
-    struct RowData {
-        poll_tax: i64,
-        pap_tax: i64,
-        pap_standard: i32,
+    // 指定用户在指定月份需上缴的税收
+    // (poll_tax, pap_tax)
+    pub fn user_tax(
+        &self,
+        user_id: i32,
+        year_month: YearMonth,
+    ) -> Result<(Decimal, Decimal), String> {
+        let mut poll_tax_amount = Decimal::ZERO;
+        let mut pap_tax_amount = Decimal::ZERO;
+
+        let (flag_poll_tax, flag_pap_tax) = self.taxable(user_id, year_month);
+        if (flag_poll_tax == false) & (flag_pap_tax == false) {
+            return Ok((poll_tax_amount, pap_tax_amount));
+        }
+
+        let (par_poll_tax, par_pap_tax, par_pap_standard) =
+            self.parameters.get(&year_month).copied().ok_or(format!(
+                "no tax parameters found, year:{}, month:{}",
+                year_month.year, year_month.month
+            ))?;
+
+        if flag_poll_tax {
+            poll_tax_amount = par_poll_tax;
+        }
+
+        if flag_pap_tax {
+            let user_pap = self.user_pap(user_id, year_month);
+            let delta_pap = par_pap_standard - user_pap;
+            if delta_pap.is_sign_positive() {
+                pap_tax_amount = delta_pap * par_pap_tax;
+            }
+        }
+
+        Ok((poll_tax_amount, pap_tax_amount))
     }
 
-    let data = ETaxParameters::find()
-        .filter(
-            Condition::all()
-                .add(CTaxParameters::Year.eq(year_month.year as i32))
-                .add(CTaxParameters::Month.eq(year_month.month as i32)),
-        )
-        .into_model::<RowData>()
-        .one(db)
-        .await
-        .map_err(|e| e.to_string())?;
+    // 指定用户在指定月份上缴的税收总额
+    pub fn paid_up_tax(&self, user_id: i32, year_month: YearMonth) -> Decimal {
+        self.payments
+            .get(&(user_id, year_month))
+            .copied()
+            .unwrap_or_default()
+    }
 
-    let row_data = data.ok_or(format!(
-        "no tax parameters found, year:{}, month:{}",
-        year_month.year, year_month.month
-    ))?;
+    // 月份按 offset 时区划分
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        start: YearMonth,
+        end: YearMonth,
+        offset: FixedOffset,
+    ) -> Result<TaxLedger, String> {
+        #[derive(FromQueryResult)]
+        struct Character {
+            character_id: i64,
+            user_id: i32,
+            name: String,
+            main: bool,
+        }
 
-    let poll_tax = decimal_from_i64(row_data.poll_tax);
-    let pap_tax = decimal_from_i64(row_data.pap_tax);
-    let pap_standard = decimal_from_i64(row_data.pap_standard as i64);
+        #[derive(FromQueryResult)]
+        struct Pap {
+            character_id: i64,
+            year: i32,
+            month: i32,
+            pap: i32,
+        }
+
+        #[derive(FromQueryResult)]
+        struct Payment {
+            date: i64,
+            first_party_id: i64,
+            amount: i64,
+        }
+
+        let in_range = |year: i32, month: i32| {
+            let ym = YearMonth::new(year as i16, month as u8);
+            (start <= ym && ym <= end).then_some(ym)
+        };
+
+        let users = EUsers::find()
+            .order_by_asc(CUsers::Id)
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        let users_ids = users.iter().map(|u| u.id).collect();
+        let group_nicknames = users
+            .iter()
+            .filter_map(|u| u.we_chat_group_nickname.clone().map(|n| (u.id, n)))
+            .collect();
+        let notes = users
+            .into_iter()
+            .filter_map(|u| u.tax_note.map(|n| (u.id, n)))
+            .collect();
+
+        let characters = ECharacters::find()
+            .select_only()
+            .column(CCharacters::CharacterId)
+            .column(CCharacters::UserId)
+            .column(CCharacters::Name)
+            .column(CCharacters::Main)
+            .filter(CCharacters::UserId.is_not_null())
+            .order_by_asc(CCharacters::CharacterId)
+            .into_model::<Character>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut main_character_names = BTreeMap::new();
+        let mut main_character_ids = BTreeMap::new();
+        let mut character_users = BTreeMap::new();
+        for c in characters {
+            character_users.insert(c.character_id, c.user_id);
+            if c.main {
+                main_character_names.entry(c.user_id).or_insert(c.name);
+                main_character_ids
+                    .entry(c.user_id)
+                    .or_insert(c.character_id);
+            }
+        }
 
-    Ok((poll_tax, pap_tax, pap_standard))
+        let taxable = ETaxableList::find()
+            .filter(
+                Condition::all()
+                    .add(CTaxableList::Year.gte(start.year as i32))
+                    .add(CTaxableList::Year.lte(end.year as i32)),
+            )
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter_map(|t| {
+                let ym = in_range(t.year, t.month)?;
+                Some(((t.user_id, ym), (t.poll_tax, t.pap_tax)))
+            })
+            .collect();
+
+        let parameters = ETaxParameters::find()
+            .filter(
+                Condition::all()
+                    .add(CTaxParameters::Year.gte(start.year as i32))
+                    .add(CTaxParameters::Year.lte(end.year as i32)),
+            )
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter_map(|p| {
+                let ym = in_range(p.year, p.month)?;
+                let value = (
+                    decimal_from_i64(p.poll_tax),
+                    decimal_from_i64(p.pap_tax),
+                    decimal_from_i64(p.pap_standard as i64),
+                );
+                Some((ym, value))
+            })
+            .collect();
+
+        let mut paps = BTreeMap::new();
+        let mut character_paps = BTreeMap::new();
+        let data = EPapJournal::find()
+            .select_only()
+            .column(CPapJournal::CharacterId)
+            .column(CPapJournal::Year)
+            .column(CPapJournal::Month)
+            .column(CPapJournal::Pap)
+            .filter(
+                Condition::all()
+                    .add(CPapJournal::Year.gte(start.year as i32))
+                    .add(CPapJournal::Year.lte(end.year as i32)),
+            )
+            .into_model::<Pap>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        for d in data {
+            if let (Some(user_id), Some(ym)) = (
+                character_users.get(&d.character_id),
+                in_range(d.year, d.month),
+            ) {
+                let pap = decimal_from_i64(d.pap as i64);
+                *paps.entry((*user_id, ym)).or_insert(Decimal::ZERO) += pap;
+                *character_paps
+                    .entry((d.character_id, ym))
+                    .or_insert(Decimal::ZERO) += pap;
+            }
+        }
+
+        let mut payments = BTreeMap::new();
+        let data = ECorporationWalletJournal::find()
+            .select_only()
+            .column(CCorporationWalletJournal::Date)
+            .column(CCorporationWalletJournal::FirstPartyId)
+            .column(CCorporationWalletJournal::Amount)
+            .filter(
+                Condition::all()
+                    .add(CCorporationWalletJournal::Date.gte(start.lower(offset).timestamp()))
+                    .add(CCorporationWalletJournal::Date.lt(end.upper(offset).timestamp()))
+                    .add(CCorporationWalletJournal::FirstPartyId.is_not_null())
+                    .add(
+                        CCorporationWalletJournal::RefType
+                            .eq(JournalRefType::PlayerDonation as i32),
+                    )
+                    .add(CCorporationWalletJournal::Amount.gt(0)),
+            )
+            .into_model::<Payment>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        for d in data {
+            if let Some(user_id) = character_users.get(&d.first_party_id) {
+                let date_time = DateTime::from_timestamp_secs(d.date).unwrap();
+                let ym = YearMonth::from_datetime(&date_time, offset);
+                *payments.entry((*user_id, ym)).or_insert(Decimal::ZERO) +=
+                    decimal_from_i64(d.amount);
+            }
+        }
+
+        Ok(TaxLedger {
+            users_ids,
+            main_character_names,
+            main_character_ids,
+            group_nicknames,
+            notes,
+            taxable,
+            parameters,
+            paps,
+            character_paps,
+            payments,
+        })
+    }
 }
 
 fn decimal_to_i64(mut d: Decimal) -> i64 {
@@ -604,26 +1412,31 @@ impl YearMonth {
         Self { year, month }
     }
 
-    pub fn lower(&self) -> DateTime<Utc> {
-        let date = NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, 1).unwrap();
-        let time = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
-        let datetime = NaiveDateTime::new(date, time);
-        DateTime::from_naive_utc_and_offset(datetime, Utc)
+    // 时刻在 offset 时区中所在的月份
+    pub fn from_datetime(t: &DateTime<Utc>, offset: FixedOffset) -> Self {
+        Self::from_date(&t.with_timezone(&offset).date_naive())
     }
 
-    pub fn upper(&self) -> DateTime<Utc> {
-        let mut month = self.month as u32;
-        let mut year = self.year as i32;
-        if self.month >= 12 {
-            month = 1;
-            year += 1;
-        } else {
-            month += 1;
-        }
-        let date = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
-        let time = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
-        let datetime = NaiveDateTime::new(date, time);
-        DateTime::from_naive_utc_and_offset(datetime, Utc)
+    pub fn from_date(date: &NaiveDate) -> Self {
+        Self::new(date.year() as i16, date.month() as u8)
+    }
+
+    pub fn first_day(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, 1).unwrap()
+    }
+
+    pub fn last_day(&self) -> NaiveDate {
+        self.add_month(1).first_day().pred_opt().unwrap()
+    }
+
+    // 该月在 offset 时区中的起点, 包含
+    pub fn lower(&self, offset: FixedOffset) -> DateTime<Utc> {
+        local_midnight(self.first_day(), offset)
+    }
+
+    // 该月在 offset 时区中的终点, 即下月的起点, 不包含
+    pub fn upper(&self, offset: FixedOffset) -> DateTime<Utc> {
+        self.add_month(1).lower(offset)
     }
 
     pub fn add_month(&self, month: i32) -> YearMonth {
@@ -652,6 +1465,11 @@ impl YearMonth {
         format!("{}年{}月", self.year, self.month)
     }
 
+    // 与 from_str 的格式相同, 用作 csv, json 等输出的稳定键名, 例如 "2025-08"
+    pub fn to_key(&self) -> String {
+        format!("{}-{:02}", self.year, self.month)
+    }
+
     pub fn from_str(s: &str) -> Result<Self, String> {
         // 2025-11
         if let Some((y_str, m_str)) = s.split_once("-") {
@@ -668,6 +1486,12 @@ impl YearMonth {
     }
 }
 
+// 日期在 offset 时区中的零点
+pub fn local_midnight(date: NaiveDate, offset: FixedOffset) -> DateTime<Utc> {
+    let datetime = NaiveDateTime::new(date, NaiveTime::MIN);
+    (datetime - offset).and_utc()
+}
+
 #[test]
 fn year_month_add() {
     let a = YearMonth::new(2025, 9);
@@ -726,3 +1550,509 @@ fn test_range_year_month_iter() {
         println!("{:?}", i);
     }
 }
+
+// 测试用的合成流水
+#[cfg(test)]
+fn test_journal_item(
+    id: i64,
+    first: Option<i64>,
+    second: Option<i64>,
+) -> ResCorporationWalletJournalItem {
+    ResCorporationWalletJournalItem {
+        id,
+        date: DateTime::from_timestamp_secs(1759276800 + id).unwrap(),
+        ref_type: JournalRefType::PlayerDonation,
+        description: String::new(),
+        amount: Some(Decimal::new(100, 0)),
+        balance: Some(Decimal::new(100 * id, 0)),
+        context_id: None,
+        context_id_type: None,
+        reason: None,
+        first_party_id: first,
+        second_party_id: second,
+        tax: None,
+        tax_receiver_id: None,
+    }
+}
+
+// 在给定数据库上重建表并检查常用查询, sqlite 与 PostgreSQL 共用
+#[cfg(test)]
+async fn check_backend(db: &sea_orm::DatabaseConnection) {
+    use db_wallet::{Migrator, MigratorTrait};
+
+    Migrator::fresh(db).await.unwrap();
+
+    let item = test_journal_item;
+    for i in [
+        item(1, Some(1001), Some(98000001)),
+        item(2, Some(1001), Some(98000001)),
+        item(3, None, Some(500016)),
+        item(4, Some(1002), None),
+    ] {
+        insert_journal_items(db, &[i], JournalSource::Esi)
+            .await
+            .unwrap();
+    }
+
+    let ids = get_all_ids(db).await.unwrap();
+    assert_eq!(ids, vec![1001, 1002, 98000001]);
+
+    let latest = get_latest_journal_date(db).await.unwrap().unwrap();
+    assert_eq!(latest.timestamp(), 1759276804);
+
+    let corporation = AmCorporations {
+        corporation_id: Set(98000001),
+        name: Set("Corp".to_string()),
+        ticker: Set("C".to_string()),
+        date_founded: Set(None),
+        description: Set(None),
+        alliance_id: Set(None),
+        last_refreshed: Set(None),
+    };
+    ECorporations::insert(corporation).exec(db).await.unwrap();
+    assert_eq!(
+        find_party_id_by_name(db, "Corp").await.unwrap(),
+        Some(98000001)
+    );
+    assert_eq!(
+        check_out_unknown_ids(db, ids).await.unwrap(),
+        vec![1001, 1002]
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_backend_sqlite() {
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    check_backend(&db).await;
+}
+
+// 需要本地 PostgreSQL, 通过 TEST_DATABASE_URL 指定一个可清空的测试库, 未设置时跳过
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn test_backend_postgres() {
+    let url = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) if url.starts_with("postgres") => url,
+        _ => return,
+    };
+    let db = sea_orm::Database::connect(url).await.unwrap();
+    check_backend(&db).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_upgrade_wall_journal() {
+    use db_wallet::{Migrator, MigratorTrait};
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+    let mut events = EventQueue::new(Decimal::ZERO);
+
+    let page = |ids: &[i64]| {
+        ResCorporationWalletJournal(
+            ids.iter()
+                .map(|id| test_journal_item(*id, Some(1001), None))
+                .collect(),
+        )
+    };
+    let count = db_upgrade_wall_journal(&db, page(&[1, 2, 3]), &mut events)
+        .await
+        .unwrap();
+    assert_eq!((count.inserted, count.skipped), (3, 0));
+
+    let count = db_upgrade_wall_journal(&db, page(&[2, 3, 4]), &mut events)
+        .await
+        .unwrap();
+    assert_eq!((count.inserted, count.skipped), (1, 2));
+    // 已存在的流水不重复产生事件
+    assert_eq!(events.events().len(), 4);
+}
+
+// 以合成的 10 万行流水测试入库速度, 每页 2500 行, 每次同步一个事务
+// just bench_upgrade_wall_journal
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+#[ignore]
+async fn bench_upgrade_wall_journal() {
+    use db_wallet::{Migrator, MigratorTrait};
+    use sea_orm::TransactionTrait;
+    use std::time::Instant;
+
+    let path = std::env::temp_dir().join(format!("bench_journal_{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let url = format!("sqlite://{}?mode=rwc", path.to_string_lossy());
+    let db = sea_orm::Database::connect(url).await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    let pages: Vec<Vec<i64>> = (0..40)
+        .map(|p| (1..=2500).map(|i| p * 2500 + i).collect())
+        .collect();
+
+    for round in ["insert", "skip"] {
+        let mut events = EventQueue::new(Decimal::ZERO);
+        let start = Instant::now();
+        let txn = db.begin().await.unwrap();
+        let mut inserted = 0;
+        let mut skipped = 0;
+        for ids in pages.iter() {
+            let journal = ResCorporationWalletJournal(
+                ids.iter()
+                    .map(|id| test_journal_item(*id, Some(1001), Some(98000001)))
+                    .collect(),
+            );
+            let count = db_upgrade_wall_journal(&txn, journal, &mut events)
+                .await
+                .unwrap();
+            inserted += count.inserted;
+            skipped += count.skipped;
+        }
+        txn.commit().await.unwrap();
+        println!(
+            "{}: inserted {} rows, skipped {} rows, {:?}",
+            round,
+            inserted,
+            skipped,
+            start.elapsed()
+        );
+        assert_eq!(inserted + skipped, 100000);
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+// 测试用的 sqlite 内存数据库: 用户 1 (主角色 c1001, 小号 c1002), 用户 2 (无主角色 c2001)
+// 2025年9月, 10月的税收参数, PAP 与缴税流水
+#[cfg(all(test, feature = "sqlite"))]
+pub async fn test_tax_database() -> sea_orm::DatabaseConnection {
+    use db_wallet::{
+        Migrator, MigratorTrait,
+        entities::{
+            pap_journal::ActiveModel as AmPapJournal,
+            tax_parameters::ActiveModel as AmTaxParameters,
+            taxable_list::ActiveModel as AmTaxableList, users::ActiveModel as AmUsers,
+        },
+    };
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    for (id, nickname) in [(1, "u1"), (2, "u2")] {
+        let m = AmUsers {
+            id: Set(id),
+            we_chat_id: Set(None),
+            we_chat_nick_name: Set(None),
+            we_chat_group_nickname: Set(Some(nickname.to_string())),
+            tax_note: Set((id == 1).then(|| "note".to_string())),
+        };
+        EUsers::insert(m).exec(&db).await.unwrap();
+    }
+    for (character_id, user_id, main) in [(1001, 1, true), (1002, 1, false), (2001, 2, false)] {
+        let m = AmCharacters {
+            character_id: Set(character_id),
+            alliance_id: Set(None),
+            corporation_id: Set(98000001),
+            birthday: Set(0),
+            name: Set(format!("c{}", character_id)),
+            user_id: Set(Some(user_id)),
+            main: Set(main),
+            last_refreshed: Set(None),
+        };
+        ECharacters::insert(m).exec(&db).await.unwrap();
+    }
+    for (user_id, month, poll_tax, pap_tax) in [
+        (1, 9, true, true),
+        (1, 10, false, true),
+        (2, 10, true, false),
+    ] {
+        let m = AmTaxableList {
+            id: NotSet,
+            user_id: Set(user_id),
+            year: Set(2025),
+            month: Set(month),
+            poll_tax: Set(poll_tax),
+            pap_tax: Set(pap_tax),
+        };
+        ETaxableList::insert(m).exec(&db).await.unwrap();
+    }
+    for month in [9, 10] {
+        let m = AmTaxParameters {
+            id: NotSet,
+            year: Set(2025),
+            month: Set(month),
+            poll_tax: Set(5000000000),
+            pap_tax: Set(100000000),
+            pap_standard: Set(1000),
+        };
+        ETaxParameters::insert(m).exec(&db).await.unwrap();
+    }
+    for (character_id, month, pap) in [(1001, 9, 400), (1002, 9, 300), (1001, 10, 1500)] {
+        let m = AmPapJournal {
+            id: NotSet,
+            character_id: Set(character_id),
+            year: Set(2025),
+            month: Set(month),
+            pap: Set(pap),
+        };
+        EPapJournal::insert(m).exec(&db).await.unwrap();
+    }
+    let mut items = Vec::new();
+    for (id, character_id, date) in [
+        (1, 1001, 1757000000),
+        (2, 1002, 1757100000),
+        (3, 2001, 1760000000),
+    ] {
+        let mut item = test_journal_item(id, Some(character_id), Some(98000001));
+        item.date = DateTime::from_timestamp_secs(date).unwrap();
+        items.push(item);
+    }
+    insert_journal_items(&db, &items, JournalSource::Esi)
+        .await
+        .unwrap();
+
+    db
+}
+
+// 批量查询后在内存中计算税收
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_tax_ledger() {
+    let db = test_tax_database().await;
+
+    let start = YearMonth::new(2025, 9);
+    let end = YearMonth::new(2025, 11);
+    let ledger = TaxLedger::select_from_db(&db, start, end, FixedOffset::east_opt(0).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(ledger.users_ids(), &[1, 2]);
+    assert_eq!(ledger.main_character_name(1).unwrap(), "c1001");
+    assert_eq!(ledger.main_character_name(2).unwrap(), "u2");
+    assert_eq!(
+        ledger.main_character_name(3),
+        Err("User not found".to_string())
+    );
+    assert_eq!(ledger.main_character_id(1), Some(1001));
+    assert_eq!(ledger.main_character_id(2), None);
+    assert_eq!(ledger.note(1), Some("note"));
+    assert_eq!(ledger.note(2), None);
+    assert_eq!(ledger.user_pap(1, start), Decimal::from(7));
+    assert_eq!(ledger.character_pap(1001, start), Decimal::from(4));
+    assert_eq!(ledger.character_pap(1002, start), Decimal::from(3));
+    assert_eq!(ledger.character_pap(2001, start), Decimal::ZERO);
+
+    // 9月: 人头税 5000万, PAP 7分 不足标准 10分, PAP税 3 * 100万
+    // 10月: PAP 15分 已达标; 11月: 未登记
+    assert_eq!(
+        ledger.user_tax(1, start).unwrap(),
+        (Decimal::new(50000000, 0), Decimal::new(3000000, 0))
+    );
+    assert_eq!(
+        ledger.user_tax(1, YearMonth::new(2025, 10)).unwrap(),
+        (Decimal::ZERO, Decimal::ZERO)
+    );
+    assert_eq!(
+        ledger.user_tax(2, YearMonth::new(2025, 10)).unwrap(),
+        (Decimal::new(50000000, 0), Decimal::ZERO)
+    );
+    assert_eq!(
+        ledger.user_tax(1, end).unwrap(),
+        (Decimal::ZERO, Decimal::ZERO)
+    );
+    assert_eq!(ledger.paid_up_tax(1, start), Decimal::new(200, 0));
+    assert_eq!(ledger.paid_up_tax(2, start), Decimal::ZERO);
+    assert_eq!(
+        ledger.paid_up_tax(2, YearMonth::new(2025, 10)),
+        Decimal::new(100, 0)
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_refresh_character_info() {
+    use db_wallet::{Migrator, MigratorTrait};
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    let m = AmCharacters {
+        character_id: Set(1001),
+        alliance_id: Set(None),
+        corporation_id: Set(98000001),
+        birthday: Set(0),
+        name: Set("Alice".to_string()),
+        user_id: Set(Some(1)),
+        main: Set(true),
+        last_refreshed: Set(Some(100)),
+    };
+    ECharacters::insert(m).exec(&db).await.unwrap();
+    save_party_image(&db, 1001, vec![1]).await.unwrap();
+    assert!(get_stale_character_ids(&db, 100).await.unwrap().is_empty());
+    assert_eq!(get_stale_character_ids(&db, 101).await.unwrap(), vec![1001]);
+
+    let info = |corporation_id, alliance_id, name: &str| ResCharacterPublicInformation {
+        name: name.to_string(),
+        birthday: DateTime::from_timestamp_secs(0).unwrap(),
+        corporation_id,
+        bloodline_id: 1,
+        race_id: 1,
+        gender: "female".to_string(),
+        alliance_id,
+        description: None,
+        security_status: None,
+        title: None,
+    };
+
+    // 无变化时只更新刷新时间
+    let change = refresh_character_info(&db, 1001, info(98000001, None, "Alice"), 200)
+        .await
+        .unwrap();
+    assert!(change.is_none());
+
+    let change = refresh_character_info(&db, 1001, info(98000002, Some(99000001), "Alice"), 300)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.corporation_id, Some((98000001, 98000002)));
+    assert_eq!(change.alliance_id, Some((None, Some(99000001))));
+    assert!(change.name.is_none());
+
+    // 用户关联与头像不受刷新影响
+    let c = ECharacters::find_by_id(1001)
+        .one(&db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(c.corporation_id, 98000002);
+    assert_eq!(c.user_id, Some(1));
+    assert!(c.main);
+    let images = get_party_images(&db, &BTreeSet::from([1001]))
+        .await
+        .unwrap();
+    assert_eq!(images.get(&1001), Some(&vec![1]));
+    assert_eq!(c.last_refreshed, Some(300));
+
+    let history = EPartyHistory::find().all(&db).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].party_id, 1001);
+    assert_eq!(history[0].changed_at, 300);
+    assert_eq!(history[0].old_corporation_id, Some(98000001));
+    assert_eq!(history[0].new_corporation_id, Some(98000002));
+
+    assert!(
+        refresh_character_info(&db, 1002, info(1, None, "Bob"), 300)
+            .await
+            .is_err()
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_update_character_info() {
+    use db_wallet::{Migrator, MigratorTrait};
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    let info = |corporation_id, name: &str| ResCharacterPublicInformation {
+        name: name.to_string(),
+        birthday: DateTime::from_timestamp_secs(1600000000).unwrap(),
+        corporation_id,
+        bloodline_id: 1,
+        race_id: 1,
+        gender: "male".to_string(),
+        alliance_id: None,
+        description: None,
+        security_status: None,
+        title: None,
+    };
+    let portrait = |b: u8| vec![b];
+
+    insert_character_info(&db, 1001, info(98000001, "Bob"), portrait(1))
+        .await
+        .unwrap();
+    let c = ECharacters::find_by_id(1001)
+        .one(&db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(c.user_id, None);
+    assert!(c.main == false);
+    assert!(c.last_refreshed.is_some());
+
+    // 由用户关联为主角色
+    let m = AmCharacters {
+        character_id: Set(1001),
+        user_id: Set(Some(3)),
+        main: Set(true),
+        ..Default::default()
+    };
+    ECharacters::update(m).exec(&db).await.unwrap();
+
+    // 与 --refresh 相同, 由 refresh_character_info 更新并记录变化
+    let change = refresh_character_info(&db, 1001, info(98000002, "Bobby"), 200)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.name, Some(("Bob".to_string(), "Bobby".to_string())));
+    save_party_image(&db, 1001, portrait(2)).await.unwrap();
+    let c = ECharacters::find_by_id(1001)
+        .one(&db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(c.name, "Bobby");
+    assert_eq!(c.corporation_id, 98000002);
+    assert_eq!(c.birthday, 1600000000);
+    let images = get_party_images(&db, &BTreeSet::from([1001]))
+        .await
+        .unwrap();
+    assert_eq!(images.get(&1001), Some(&vec![2]));
+    assert_eq!(c.user_id, Some(3));
+    assert!(c.main);
+
+    let history = EPartyHistory::find().all(&db).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].new_corporation_id, Some(98000002));
+    assert_eq!(history[0].new_name.as_deref(), Some("Bobby"));
+}
+
+// 旧版本按四种尺寸保存在 characters 中的头像迁入 images, 相同内容只保存一份
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_migrate_portraits() {
+    use db_wallet::{Migrator, MigratorTrait};
+    use sea_orm::PaginatorTrait;
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    // 迁移到 add_images 之前的版本
+    let steps = Migrator::migrations()
+        .iter()
+        .position(|m| m.name() == "m20251108_000001_add_images")
+        .unwrap() as u32;
+    Migrator::up(&db, Some(steps)).await.unwrap();
+    db.execute_unprepared(
+        "INSERT INTO characters (character_id, corporation_id, birthday, name, main, \
+         portrait64, portrait512) VALUES \
+         (1001, 1, 0, 'a', 0, x'01', x'0512'), \
+         (1002, 1, 0, 'b', 0, x'01', x'0512'), \
+         (1003, 1, 0, 'c', 0, x'64', NULL), \
+         (1004, 1, 0, 'd', 0, NULL, NULL)",
+    )
+    .await
+    .unwrap();
+
+    Migrator::up(&db, None).await.unwrap();
+    assert_eq!(EImages::find().count(&db).await.unwrap(), 2);
+    // 迁移与运行时写入图片使用相同的哈希
+    let image = EImages::find_by_id(image_hash(&[0x05, 0x12]))
+        .one(&db)
+        .await
+        .unwrap();
+    assert!(image.is_some());
+    let images = get_party_images(&db, &BTreeSet::from([1001, 1002, 1003, 1004]))
+        .await
+        .unwrap();
+    assert_eq!(images.len(), 3);
+    assert_eq!(images[&1001], vec![0x05, 0x12]);
+    assert_eq!(images[&1002], vec![0x05, 0x12]);
+    assert_eq!(images[&1003], vec![0x64]);
+}
@@ -0,0 +1,421 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use sea_orm::{
+    ActiveModelTrait, ConnectionTrait, DbBackend, EntityName, EntityTrait, IdenStatic,
+    IntoActiveModel, Iterable, PrimaryKeyToColumn, Statement, TransactionTrait,
+    sea_query::OnConflict,
+};
+use serde::{Deserialize, Serialize};
+
+use db_wallet::{
+    Migrator, MigratorTrait,
+    entities::{
+        characters::{Entity as ECharacters, Model as MCharacters},
+        corporation_wallet_journal::{
+            Entity as ECorporationWalletJournal, Model as MCorporationWalletJournal,
+        },
+        corporations::{Entity as ECorporations, Model as MCorporations},
+        images::{Entity as EImages, Model as MImages},
+        pap_journal::{Entity as EPapJournal, Model as MPapJournal},
+        party_history::{Entity as EPartyHistory, Model as MPartyHistory},
+        party_images::{Entity as EPartyImages, Model as MPartyImages},
+        tax_parameters::{Entity as ETaxParameters, Model as MTaxParameters},
+        taxable_list::{Entity as ETaxableList, Model as MTaxableList},
+        users::{Entity as EUsers, Model as MUsers},
+    },
+};
+
+// 导出文件格式标识, 位于首行
+const DUMP_FORMAT: &str = "db_wallet_jsonl";
+
+// 导出文件首行
+#[derive(Serialize, Deserialize)]
+struct DumpHeader {
+    format: String,
+    schema_version: String, // 最后一个迁移的名称
+    exported_at: DateTime<Utc>,
+    portraits: bool, // 是否包含 images 与 party_images
+}
+
+// 导出文件中除首行外的每一行
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "table", content = "row", rename_all = "snake_case")]
+enum DumpRecord {
+    Users(MUsers),
+    Characters(MCharacters),
+    Corporations(MCorporations),
+    TaxParameters(MTaxParameters),
+    TaxableList(MTaxableList),
+    PapJournal(MPapJournal),
+    PartyHistory(MPartyHistory),
+    Images(MImages),
+    PartyImages(MPartyImages),
+    CorporationWalletJournal(MCorporationWalletJournal),
+}
+
+// 导入时主键已存在的处理方式
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    Fail,
+}
+
+#[derive(Default)]
+pub struct DumpImportSummary {
+    pub written: u64, // 新增或覆盖的行数
+    pub skipped: u64,
+}
+
+// 当前程序对应的数据库版本
+fn schema_version() -> String {
+    Migrator::migrations()
+        .last()
+        .map(|m| m.name().to_string())
+        .unwrap_or_default()
+}
+
+async fn check_schema<DB: ConnectionTrait>(db: &DB) -> Result<(), String> {
+    let pending = Migrator::get_pending_migrations(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    if pending.is_empty() == false {
+        return Err(format!(
+            "database has {} pending migrations, migrate it first",
+            pending.len()
+        ));
+    }
+    Ok(())
+}
+
+// 将所有表导出为 JSON Lines, 首行为 DumpHeader
+pub async fn export_lines<DB: ConnectionTrait>(db: &DB, portraits: bool) -> Result<String, String> {
+    check_schema(db).await?;
+
+    let header = DumpHeader {
+        format: DUMP_FORMAT.to_string(),
+        schema_version: schema_version(),
+        exported_at: Utc::now(),
+        portraits,
+    };
+
+    let mut records = Vec::new();
+    records.extend(
+        EUsers::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::Users),
+    );
+    records.extend(
+        ECharacters::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::Characters),
+    );
+    records.extend(
+        ECorporations::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::Corporations),
+    );
+    records.extend(
+        ETaxParameters::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::TaxParameters),
+    );
+    records.extend(
+        ETaxableList::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::TaxableList),
+    );
+    records.extend(
+        EPapJournal::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::PapJournal),
+    );
+    records.extend(
+        EPartyHistory::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::PartyHistory),
+    );
+    if portraits {
+        records.extend(
+            EImages::find()
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(DumpRecord::Images),
+        );
+        records.extend(
+            EPartyImages::find()
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(DumpRecord::PartyImages),
+        );
+    }
+    records.extend(
+        ECorporationWalletJournal::find()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(DumpRecord::CorporationWalletJournal),
+    );
+
+    let mut text = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+    text.push('\n');
+    for r in records.iter() {
+        text.push_str(
+            serde_json::to_string(r)
+                .map_err(|e| e.to_string())?
+                .as_str(),
+        );
+        text.push('\n');
+    }
+
+    Ok(text)
+}
+
+// 导入 export_lines 生成的内容, 保留原有主键, 全部成功或全部回滚
+pub async fn import_lines<DB: ConnectionTrait + TransactionTrait>(
+    db: &DB,
+    text: &str,
+    policy: ConflictPolicy,
+) -> Result<DumpImportSummary, String> {
+    check_schema(db).await?;
+
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| l.trim().is_empty() == false);
+    let header = match lines.next() {
+        None => return Err("empty file".to_string()),
+        Some((_, l)) => serde_json::from_str::<DumpHeader>(l)
+            .map_err(|e| format!("line 1: illegal header, {}", e))?,
+    };
+    if header.format != DUMP_FORMAT {
+        return Err(format!("unknown format: {}", header.format));
+    }
+    if header.schema_version != schema_version() {
+        return Err(format!(
+            "schema version mismatch: file {}, database {}",
+            header.schema_version,
+            schema_version()
+        ));
+    }
+
+    let mut records = Vec::new();
+    for (i, l) in lines {
+        let record =
+            serde_json::from_str::<DumpRecord>(l).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        records.push(record);
+    }
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    let mut summary = DumpImportSummary::default();
+    for record in records {
+        let written = match record {
+            DumpRecord::Users(m) => import_model::<EUsers, _>(&txn, m, policy).await?,
+            DumpRecord::Characters(m) => import_model::<ECharacters, _>(&txn, m, policy).await?,
+            DumpRecord::Corporations(m) => {
+                import_model::<ECorporations, _>(&txn, m, policy).await?
+            }
+            DumpRecord::TaxParameters(m) => {
+                import_model::<ETaxParameters, _>(&txn, m, policy).await?
+            }
+            DumpRecord::TaxableList(m) => import_model::<ETaxableList, _>(&txn, m, policy).await?,
+            DumpRecord::PapJournal(m) => import_model::<EPapJournal, _>(&txn, m, policy).await?,
+            DumpRecord::PartyHistory(m) => {
+                import_model::<EPartyHistory, _>(&txn, m, policy).await?
+            }
+            DumpRecord::Images(m) => import_model::<EImages, _>(&txn, m, policy).await?,
+            DumpRecord::PartyImages(m) => import_model::<EPartyImages, _>(&txn, m, policy).await?,
+            DumpRecord::CorporationWalletJournal(m) => {
+                import_model::<ECorporationWalletJournal, _>(&txn, m, policy).await?
+            }
+        };
+        if written {
+            summary.written += 1;
+        } else {
+            summary.skipped += 1;
+        }
+    }
+    reset_sequences(&txn).await?;
+    txn.commit().await.map_err(|e| e.to_string())?;
+
+    Ok(summary)
+}
+
+// PostgreSQL 的自增序列不随显式主键的插入而前进, 导入后需要重置
+async fn reset_sequences<DB: ConnectionTrait>(db: &DB) -> Result<(), String> {
+    if db.get_database_backend() != DbBackend::Postgres {
+        return Ok(());
+    }
+
+    let tables = [
+        EUsers.table_name(),
+        ETaxParameters.table_name(),
+        ETaxableList.table_name(),
+        EPapJournal.table_name(),
+        EPartyHistory.table_name(),
+    ];
+    for table in tables {
+        let sql = format!(
+            "SELECT setval(pg_get_serial_sequence('{0}', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM {0}",
+            table
+        );
+        db.execute(Statement::from_string(DbBackend::Postgres, sql))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+// 按原主键写入一行, 返回是否写入
+async fn import_model<E, DB>(
+    db: &DB,
+    model: E::Model,
+    policy: ConflictPolicy,
+) -> Result<bool, String>
+where
+    E: EntityTrait,
+    E::Model: IntoActiveModel<E::ActiveModel>,
+    E::ActiveModel: ActiveModelTrait<Entity = E>,
+    DB: ConnectionTrait,
+{
+    let keys: Vec<E::Column> = E::PrimaryKey::iter().map(|k| k.into_column()).collect();
+    let mut on_conflict = OnConflict::columns(keys.clone());
+    match policy {
+        ConflictPolicy::Skip => {
+            on_conflict.do_nothing();
+        }
+        ConflictPolicy::Overwrite => {
+            let is_key = |c: &E::Column| keys.iter().any(|k| k.as_str() == c.as_str());
+            on_conflict.update_columns(E::Column::iter().filter(|c| !is_key(c)));
+        }
+        ConflictPolicy::Fail => {}
+    }
+
+    let mut insert = E::insert(model.into_active_model());
+    if policy != ConflictPolicy::Fail {
+        insert = insert.on_conflict(on_conflict);
+    }
+    let rows = insert
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows > 0)
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_export_import() {
+    use crate::db_op::{get_party_images, save_party_image};
+    use db_wallet::entities::users::ActiveModel as AmUsers;
+    use sea_orm::{Database, PaginatorTrait, Set};
+    use std::collections::BTreeSet;
+
+    let source = Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&source, None).await.unwrap();
+    let user = AmUsers {
+        id: Set(7),
+        we_chat_id: Set(Some("wx".to_string())),
+        we_chat_nick_name: Set(None),
+        we_chat_group_nickname: Set(Some("nick".to_string())),
+        tax_note: Set(None),
+    };
+    EUsers::insert(user).exec(&source).await.unwrap();
+    let character = MCharacters {
+        character_id: 1001,
+        alliance_id: None,
+        corporation_id: 2001,
+        birthday: 1600000000,
+        name: "Alice".to_string(),
+        user_id: Some(7),
+        main: true,
+        last_refreshed: None,
+    };
+    ECharacters::insert(character.clone().into_active_model())
+        .exec(&source)
+        .await
+        .unwrap();
+    save_party_image(&source, 1001, vec![1, 2, 3])
+        .await
+        .unwrap();
+
+    let text = export_lines(&source, false).await.unwrap();
+    assert_eq!(text.lines().count(), 3);
+
+    let target = Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&target, None).await.unwrap();
+    let summary = import_lines(&target, &text, ConflictPolicy::Skip)
+        .await
+        .unwrap();
+    assert_eq!((summary.written, summary.skipped), (2, 0));
+    let user = EUsers::find_by_id(7).one(&target).await.unwrap().unwrap();
+    assert_eq!(user.we_chat_group_nickname.as_deref(), Some("nick"));
+    assert_eq!(EImages::find().count(&target).await.unwrap(), 0);
+
+    let summary = import_lines(&target, &text, ConflictPolicy::Skip)
+        .await
+        .unwrap();
+    assert_eq!((summary.written, summary.skipped), (0, 2));
+    assert!(
+        import_lines(&target, &text, ConflictPolicy::Fail)
+            .await
+            .is_err()
+    );
+
+    // 覆盖时保留本地头像
+    import_lines(&source, &text, ConflictPolicy::Overwrite)
+        .await
+        .unwrap();
+    let c = ECharacters::find_by_id(1001)
+        .one(&source)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(c, character);
+    assert_eq!(EPartyImages::find().count(&source).await.unwrap(), 1);
+
+    let text = export_lines(&source, true).await.unwrap();
+    assert_eq!(text.lines().count(), 5);
+    let summary = import_lines(&target, &text, ConflictPolicy::Skip)
+        .await
+        .unwrap();
+    assert_eq!((summary.written, summary.skipped), (2, 2));
+    let images = get_party_images(&target, &BTreeSet::from([1001]))
+        .await
+        .unwrap();
+    assert_eq!(images.get(&1001), Some(&vec![1, 2, 3]));
+
+    let text = text.replacen(schema_version().as_str(), "m00000000_000000_old", 1);
+    assert!(
+        import_lines(&target, &text, ConflictPolicy::Skip)
+            .await
+            .is_err()
+    );
+}
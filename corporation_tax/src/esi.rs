@@ -1,30 +1,123 @@
 use chrono::{DateTime, Utc};
-use image::codecs::jpeg::JpegDecoder;
+use image::ImageReader;
 use reqwest::{
-    Client, Proxy,
-    header::{ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, HeaderMap},
+    Client, Proxy, RequestBuilder, Response,
+    header::{ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, HeaderMap, RETRY_AFTER},
 };
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::io::Cursor;
+use std::{io::Cursor, sync::Mutex, time::Duration};
+use tokio::time::{Instant, sleep_until};
 
 use db_wallet::{ContextIdType, JournalRefType};
 
 pub const CORPORATION_ID: i64 = 98762057;
 
+// 默认的 ESI 兼容日期, 决定接口返回数据的格式
+pub const COMPATIBILITY_DATE: &str = "2025-09-30";
+
+// ESI 错误限额剩余低于此值时暂停所有请求, 直到限额重置
+const ERROR_LIMIT_THRESHOLD: i64 = 10;
+
+// 被限流 (429/420) 时的最大重试次数
+const RATE_LIMIT_RETRIES: u32 = 3;
+
+// ESI 请求的公共设置
+#[derive(Clone, PartialEq, Debug)]
+pub struct EsiConfig {
+    pub https_proxy: Option<String>,
+    pub compatibility_date: String,
+    pub user_agent: Option<String>, // 为 None 时使用 reqwest 的默认值
+}
+
+impl Default for EsiConfig {
+    fn default() -> Self {
+        EsiConfig {
+            https_proxy: None,
+            compatibility_date: COMPATIBILITY_DATE.to_string(),
+            user_agent: None,
+        }
+    }
+}
+
+// 多个并发任务共享同一个 QueryDevice, 限流状态也随之共享
 pub struct QueryDevice {
     client: Client,
     token_str: Option<String>,
+    compatibility_date: String,
+    paused_until: Mutex<Option<Instant>>, // 在此时间之前不发送请求
 }
 
 impl QueryDevice {
-    pub fn new(https_proxy: Option<String>, token_str: Option<String>) -> QueryDevice {
+    pub fn new(config: &EsiConfig, token_str: Option<String>) -> QueryDevice {
         let mut client_builder = Client::builder();
-        if let Some(proxy_str) = https_proxy {
+        if let Some(proxy_str) = &config.https_proxy {
             client_builder = client_builder.proxy(Proxy::https(proxy_str).unwrap());
         }
+        if let Some(user_agent) = &config.user_agent {
+            client_builder = client_builder.user_agent(user_agent);
+        }
         let client = client_builder.build().unwrap();
-        QueryDevice { client, token_str }
+        QueryDevice {
+            client,
+            token_str,
+            compatibility_date: config.compatibility_date.clone(),
+            paused_until: Mutex::new(None),
+        }
+    }
+
+    fn pause(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut paused_until = self.paused_until.lock().unwrap();
+        if paused_until.is_none_or(|u| u < until) {
+            *paused_until = Some(until);
+        }
+    }
+
+    async fn wait_pause(&self) {
+        let until = *self.paused_until.lock().unwrap();
+        if let Some(until) = until {
+            sleep_until(until).await;
+        }
+    }
+
+    // 所有请求经由此处发送
+    // 遵守 ESI 的错误限额 (X-ESI-Error-Limit-Remain/Reset), 被限流时按 Retry-After 等待后重试
+    async fn send(&self, request: RequestBuilder) -> Result<Response, String> {
+        let mut attempt = 0;
+        loop {
+            self.wait_pause().await;
+
+            let r = request
+                .try_clone()
+                .ok_or("request can not be cloned".to_string())?;
+            let res = r.send().await.map_err(|e| e.to_string())?;
+
+            let headers = res.headers();
+            let remain = header_i64(headers, "X-ESI-Error-Limit-Remain");
+            let reset = header_i64(headers, "X-ESI-Error-Limit-Reset");
+            let status = res.status().as_u16();
+
+            if status == 429 || status == 420 {
+                let secs = header_i64(headers, RETRY_AFTER.as_str())
+                    .or(reset)
+                    .unwrap_or(60);
+                self.pause(Duration::from_secs(secs.max(0) as u64));
+                if attempt < RATE_LIMIT_RETRIES {
+                    attempt += 1;
+                    continue;
+                }
+                return Ok(res);
+            }
+
+            if let (Some(remain), Some(reset)) = (remain, reset) {
+                if remain < ERROR_LIMIT_THRESHOLD {
+                    self.pause(Duration::from_secs(reset.max(0) as u64));
+                }
+            }
+
+            return Ok(res);
+        }
     }
 
     pub async fn get_corporation_wallet_journal(
@@ -43,15 +136,12 @@ impl QueryDevice {
             AUTHORIZATION,
             self.token_str.clone().unwrap().parse().unwrap(),
         );
-        headers.insert("X-Compatibility-Date", "2025-09-30".parse().unwrap());
+        headers.insert(
+            "X-Compatibility-Date",
+            self.compatibility_date.parse().unwrap(),
+        );
 
-        let res = self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+        let res = self.send(self.client.get(url).headers(headers)).await?;
 
         if res.status().is_success() {
             let r = res
@@ -75,15 +165,15 @@ impl QueryDevice {
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, "application/json".parse().unwrap());
         headers.insert(ACCEPT_LANGUAGE, "en".parse().unwrap());
-        headers.insert("X-Compatibility-Date", "2025-09-30".parse().unwrap());
+        headers.insert(
+            "X-Compatibility-Date",
+            self.compatibility_date.parse().unwrap(),
+        );
 
         let res = self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
+            .send(self.client.get(url).headers(headers))
             .await
-            .map_err(|e| format!("get_character_public_information: {}", e.to_string()))?;
+            .map_err(|e| format!("get_character_public_information: {}", e))?;
 
         if res.status().is_success() {
             let r = res
@@ -111,15 +201,15 @@ impl QueryDevice {
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, "application/json".parse().unwrap());
         headers.insert(ACCEPT_LANGUAGE, "en".parse().unwrap());
-        headers.insert("X-Compatibility-Date", "2025-09-30".parse().unwrap());
+        headers.insert(
+            "X-Compatibility-Date",
+            self.compatibility_date.parse().unwrap(),
+        );
 
         let res = self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
+            .send(self.client.get(url).headers(headers))
             .await
-            .map_err(|e| format!("get_corporation_information: {}", e.to_string()))?;
+            .map_err(|e| format!("get_corporation_information: {}", e))?;
 
         if res.status().is_success() {
             let r = res
@@ -146,15 +236,15 @@ impl QueryDevice {
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, "application/json".parse().unwrap());
         headers.insert(ACCEPT_LANGUAGE, "en".parse().unwrap());
-        headers.insert("X-Compatibility-Date", "2025-09-30".parse().unwrap());
+        headers.insert(
+            "X-Compatibility-Date",
+            self.compatibility_date.parse().unwrap(),
+        );
 
         let res = self
-            .client
-            .get(url)
-            .headers(headers)
-            .send()
+            .send(self.client.get(url).headers(headers))
             .await
-            .map_err(|e| format!("get_character_portraits: {}", e.to_string()))?;
+            .map_err(|e| format!("get_character_portraits: {}", e))?;
 
         if res.status().is_success() {
             let r = res
@@ -168,43 +258,44 @@ impl QueryDevice {
         }
     }
 
+    // 校验为可识别的图片 (角色头像为 JPEG, 图标为 PNG)
     pub async fn get_image(&self, url: &str) -> Result<Vec<u8>, String> {
         let res = self
-            .client
-            .get(url)
-            .send()
+            .send(self.client.get(url))
             .await
-            .map_err(|e| format!("get_image: {}", e.to_string()))?;
+            .map_err(|e| format!("get_image: {}", e))?;
         if res.status().is_success() == false {
             let s = res.text().await.unwrap_or_else(|e| e.to_string());
             return Err(s);
         }
         let data = res.bytes().await.map_err(|e| e.to_string())?;
-        match JpegDecoder::new(Cursor::new(data.as_ref())) {
-            Ok(_) => Ok(data.to_vec()),
-            Err(e) => Err(e.to_string()),
-        }
+        ImageReader::new(Cursor::new(data.as_ref()))
+            .with_guessed_format()
+            .map_err(|e| e.to_string())?
+            .into_dimensions()
+            .map_err(|e| format!("get_image: {}, {}", url, e))?;
+        Ok(data.to_vec())
     }
 
-    pub async fn get_portraits(&self, urls: &ResCharacterPortraits) -> Result<Portraits, String> {
-        let portrait64 = self.get_image(urls.px64x64.as_str()).await?;
-        let portrait128 = self.get_image(urls.px128x128.as_str()).await?;
-        let portrait256 = self.get_image(urls.px256x256.as_str()).await?;
-        let portrait512 = self.get_image(urls.px512x512.as_str()).await?;
-        Ok(Portraits {
-            portrait64,
-            portrait128,
-            portrait256,
-            portrait512,
-        })
+    // 只下载 512px 的头像, 较小的尺寸在使用时由 images::thumbnail 生成
+    pub async fn get_portrait(&self, character_id: i64) -> Result<Vec<u8>, String> {
+        let urls = self.get_character_portraits(character_id).await?;
+        self.get_image(urls.px512x512.as_str()).await
+    }
+
+    pub async fn get_corporation_logo(&self, corporation_id: i64) -> Result<Vec<u8>, String> {
+        let url = format!("https://images.evetech.net/corporations/{corporation_id}/logo?size=256");
+        self.get_image(url.as_str()).await
+    }
+
+    pub async fn get_alliance_logo(&self, alliance_id: i64) -> Result<Vec<u8>, String> {
+        let url = format!("https://images.evetech.net/alliances/{alliance_id}/logo?size=128");
+        self.get_image(url.as_str()).await
     }
 }
 
-pub struct Portraits {
-    pub portrait64: Vec<u8>,
-    pub portrait128: Vec<u8>,
-    pub portrait256: Vec<u8>,
-    pub portrait512: Vec<u8>,
+fn header_i64(headers: &HeaderMap, name: &str) -> Option<i64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -265,3 +356,36 @@ pub struct ResCorporationInformation {
     pub url: Option<String>,
     pub war_eligible: Option<bool>,
 }
+
+#[tokio::test]
+async fn test_send_rate_limit() {
+    use crate::notify::http_stand_in_with_headers;
+
+    // 被限流后按 Retry-After 等待并重试
+    let (url, handle) = http_stand_in_with_headers(vec![
+        (429, "retry-after: 0\r\n".to_string()),
+        (200, String::new()),
+    ])
+    .await;
+    let query_device = QueryDevice::new(&EsiConfig::default(), None);
+    let res = query_device
+        .send(query_device.client.get(url.as_str()))
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(handle.await.unwrap().len(), 2);
+
+    // 错误限额将尽时暂停后续请求, 直到限额重置
+    let (url, _handle) = http_stand_in_with_headers(vec![(
+        404,
+        "x-esi-error-limit-remain: 5\r\nx-esi-error-limit-reset: 30\r\n".to_string(),
+    )])
+    .await;
+    let res = query_device
+        .send(query_device.client.get(url.as_str()))
+        .await
+        .unwrap();
+    assert_eq!(res.status().as_u16(), 404);
+    let paused_until = query_device.paused_until.lock().unwrap().unwrap();
+    assert!(paused_until > Instant::now() + Duration::from_secs(20));
+}
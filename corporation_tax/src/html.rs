@@ -0,0 +1,285 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::path::Path;
+
+use crate::{
+    locale::{Lang, Text},
+    render::{Renderer, Table, Value},
+    report::{Report, SheetTaxList},
+    template::ReportTemplate,
+};
+
+// 单个静态文件, 样式与脚本均内嵌, 不依赖网络, 便于在手机上直接打开
+pub struct HtmlRenderer {
+    pub(crate) lang: Lang,
+}
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, report: &Report, output_path: &Path) -> Result<(), String> {
+        std::fs::write(output_path, to_html(report, self.lang)).map_err(|e| e.to_string())
+    }
+}
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 1em; }
+section { margin-bottom: 2em; overflow-x: auto; }
+table { border-collapse: collapse; font-size: 0.9em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; white-space: nowrap; }
+th { background: #f2f2f2; cursor: pointer; user-select: none; position: sticky; top: 0; }
+th[data-order="asc"]::after { content: " ▲"; }
+th[data-order="desc"]::after { content: " ▼"; }
+td.number { text-align: right; }
+td img { display: block; width: 48px; height: 48px; }
+input.filter { margin: 0.5em 0; padding: 0.3em; width: 20em; max-width: 100%; }
+"#;
+
+// 点击表头排序, 数值列按 data-sort 排序, 其余按文本排序; 输入框按行文本筛选
+const SCRIPT: &str = r#"
+document.querySelectorAll("table.report").forEach(function (table) {
+  var headers = table.tHead.rows[0].cells;
+  Array.prototype.forEach.call(headers, function (th, i) {
+    th.addEventListener("click", function () {
+      var asc = th.dataset.order !== "asc";
+      Array.prototype.forEach.call(headers, function (h) { delete h.dataset.order; });
+      th.dataset.order = asc ? "asc" : "desc";
+      var body = table.tBodies[0];
+      var rows = Array.prototype.slice.call(body.rows);
+      rows.sort(function (a, b) {
+        var x = a.cells[i], y = b.cells[i];
+        var r;
+        if (x.dataset.sort !== undefined && y.dataset.sort !== undefined) {
+          r = Number(x.dataset.sort) - Number(y.dataset.sort);
+        } else {
+          r = x.textContent.localeCompare(y.textContent, document.documentElement.lang);
+        }
+        return asc ? r : -r;
+      });
+      rows.forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});
+document.querySelectorAll("input.filter").forEach(function (input) {
+  var table = document.getElementById(input.dataset.table);
+  input.addEventListener("input", function () {
+    var q = input.value.trim().toLowerCase();
+    Array.prototype.forEach.call(table.tBodies[0].rows, function (row) {
+      row.hidden = q !== "" && row.textContent.toLowerCase().indexOf(q) < 0;
+    });
+  });
+});
+"#;
+
+fn to_html(report: &Report, lang: Lang) -> String {
+    let title = format!(
+        "{} {} ~ {}",
+        lang.text(Text::ReportTitle),
+        lang.year_month(report.start),
+        lang.year_month(report.end)
+    );
+    let mut html = String::new();
+    html += &format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n",
+        lang.code()
+    );
+    html += "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n";
+    html += &format!("<title>{}</title>\n", escape(title.as_str()));
+    html += &format!("<style>{}</style>\n</head>\n<body>\n", STYLE);
+    html += &format!("<h1>{}</h1>\n", escape(title.as_str()));
+
+    for table in report.tables(lang) {
+        // 与 xlsx 相同, 流水连续时不输出警告
+        if table.key == "journal_gaps" && table.rows.is_empty() {
+            continue;
+        }
+        let portraits = if table.key == "tax_list" && report.tax_list.has_portraits() {
+            Some(&report.tax_list)
+        } else {
+            None
+        };
+        html += &table_to_html(&table, portraits, &report.template, lang);
+    }
+
+    html += &format!("<script>{}</script>\n</body>\n</html>\n", SCRIPT);
+    html
+}
+
+fn table_to_html(
+    table: &Table,
+    portraits: Option<&SheetTaxList>,
+    template: &ReportTemplate,
+    lang: Lang,
+) -> String {
+    let id = format!("table-{}", table.key);
+    let mut html = format!("<section>\n<h2>{}</h2>\n", escape(table.title.as_str()));
+    html += &format!(
+        "<input class=\"filter\" type=\"search\" placeholder=\"{}\" data-table=\"{}\">\n",
+        lang.text(Text::Filter),
+        id
+    );
+    html += &format!("<table class=\"report\" id=\"{}\">\n<thead><tr>", id);
+    for column in &table.columns {
+        html += &format!(
+            "<th data-key=\"{}\">{}</th>",
+            escape(column.key.as_str()),
+            escape(column.header.as_str())
+        );
+        if portraits.is_some() && column.key == "character_name" {
+            html += &format!(
+                "<th data-key=\"portrait\">{}</th>",
+                lang.text(Text::Portrait)
+            );
+        }
+    }
+    html += "</tr></thead>\n<tbody>\n";
+
+    for row in &table.rows {
+        html += "<tr>";
+        for (i, (column, value)) in table.columns.iter().zip(row.iter()).enumerate() {
+            html += &cell_to_html(value, highlight(table, row, i, template), lang);
+            if let Some(tax_list) = portraits {
+                if column.key == "character_name" {
+                    html += &portrait_to_html(tax_list, row_value(table, row, "user_id"));
+                }
+            }
+        }
+        html += "</tr>\n";
+    }
+    html += "</tbody>\n</table>\n</section>\n";
+    html
+}
+
+fn cell_to_html(value: &Value, color: Option<&str>, lang: Lang) -> String {
+    let mut attributes = String::new();
+    let sort = match value {
+        Value::Integer(_) | Value::Isk(_) => Some(value.to_plain()),
+        Value::DateTime(t) => Some(t.timestamp().to_string()),
+        _ => None,
+    };
+    if let Some(sort) = sort {
+        attributes += &format!(" class=\"number\" data-sort=\"{}\"", sort);
+    }
+    if let Some(color) = color {
+        // ARGB 转为 css 的 RGB
+        attributes += &format!(" style=\"background:#{}\"", &color[2..]);
+    }
+    format!(
+        "<td{}>{}</td>",
+        attributes,
+        escape(value.to_display(lang).as_str())
+    )
+}
+
+fn portrait_to_html(tax_list: &SheetTaxList, user_id: Option<&Value>) -> String {
+    let portrait = match user_id {
+        Some(Value::Integer(user_id)) => tax_list.portrait(*user_id as i32),
+        _ => None,
+    };
+    let mime = portrait.and_then(|p| image::guess_format(p).ok());
+    match (portrait, mime) {
+        (Some(portrait), Some(mime)) => format!(
+            "<td><img alt=\"\" src=\"data:{};base64,{}\"></td>",
+            mime.to_mime_type(),
+            STANDARD.encode(portrait)
+        ),
+        _ => "<td></td>".to_string(),
+    }
+}
+
+fn row_value<'a>(table: &Table, row: &'a [Value], key: &str) -> Option<&'a Value> {
+    let index = table.columns.iter().position(|c| c.key == key)?;
+    row.get(index)
+}
+
+// 与 xlsx 相同的高亮规则
+fn highlight<'a>(
+    table: &Table,
+    row: &[Value],
+    index: usize,
+    template: &'a ReportTemplate,
+) -> Option<&'a str> {
+    let value = &row[index];
+    let isk = |value: Option<&Value>| match value {
+        Some(Value::Isk(v)) => Some(*v),
+        _ => None,
+    };
+    match table.key {
+        "wallet_journal" => {
+            let ref_type = match row_value(table, row, "ref_type") {
+                Some(Value::RefType(ref_type)) => Some(*ref_type),
+                _ => None,
+            };
+            template.wallet_journal.highlight(
+                table.columns[index].key.as_str(),
+                ref_type,
+                isk(row_value(table, row, "amount")),
+                isk(Some(value)),
+            )
+        }
+        "tax_list" => {
+            // 各月的列键名为 2025-08.pap_tax, 模板中为 pap_tax
+            let key = table.columns[index].key.as_str();
+            let key = key.rsplit('.').next().unwrap_or(key);
+            template.tax_list.highlight(
+                key,
+                None,
+                isk(row_value(table, row, "unpaid_tax")),
+                isk(Some(value)),
+            )
+        }
+        "summary" => template
+            .summary
+            .highlight("", None, isk(Some(value)), isk(Some(value))),
+        "journal_gaps" => template.journal_gaps.highlight(
+            table.columns[index].key.as_str(),
+            None,
+            isk(row_value(table, row, "missing_amount")),
+            isk(Some(value)),
+        ),
+        _ => None,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test_table_to_html() {
+    use crate::render::TableColumn;
+    use db_wallet::JournalRefType;
+    use rust_decimal::Decimal;
+
+    let table = Table {
+        key: "wallet_journal",
+        title: "主账户流水".to_string(),
+        columns: vec![
+            TableColumn::new("ref_type", "类型"),
+            TableColumn::new("amount", "收支金额"),
+            TableColumn::new("description", "备注"),
+        ],
+        rows: vec![
+            vec![
+                Value::RefType(JournalRefType::PlayerDonation),
+                Value::Isk(Decimal::from(100)),
+                Value::Text("<b>".to_string()),
+            ],
+            vec![
+                Value::RefType(JournalRefType::BountyPrizes),
+                Value::Isk(Decimal::from(-5)),
+                Value::Empty,
+            ],
+            vec![
+                Value::RefType(JournalRefType::BountyPrizes),
+                Value::Isk(Decimal::from(5)),
+                Value::Empty,
+            ],
+        ],
+    };
+    let html = table_to_html(&table, None, &ReportTemplate::default(), Lang::Zh);
+    assert!(html.contains("<td style=\"background:#C6EFCE\">玩家捐助</td>"));
+    assert!(html.contains("<td class=\"number\" data-sort=\"-5\" style=\"background:#FFC7CE\">"));
+    assert!(html.contains("<td style=\"background:#C6EFCE\">&lt;b&gt;</td>"));
+    assert!(html.contains("<tr><td>追击赏金</td><td class=\"number\" data-sort=\"5\">"));
+}
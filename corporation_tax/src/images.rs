@@ -0,0 +1,153 @@
+use image::{ImageFormat, ImageReader, imageops::FilterType};
+use sea_orm::ConnectionTrait;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Cursor,
+};
+use umya_spreadsheet::{
+    Worksheet,
+    helper::coordinate::string_from_column_index,
+    structs::{Image, drawing::spreadsheet::MarkerType},
+};
+
+use crate::db_op::{get_main_character_ids, get_party_images};
+use db_wallet::image_hash;
+
+// 嵌入工作表的头像边长, 单位 px
+pub const EMBEDDED_PORTRAIT_SIZE: u32 = 64;
+
+// 64px 约为 48 磅的行高, 9.14 个字符的列宽
+pub const EMBEDDED_PORTRAIT_ROW_HEIGHT: f64 = 48.0;
+pub const EMBEDDED_PORTRAIT_COLUMN_WIDTH: f64 = 9.14;
+
+// 由原图生成 size x size 的缩略图, 保持原图的格式
+pub fn thumbnail(data: &[u8], size: u32) -> Result<Vec<u8>, String> {
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    let format = reader.format().ok_or("unknown image format".to_string())?;
+    let image = reader.decode().map_err(|e| e.to_string())?;
+    if image.width() == size && image.height() == size {
+        return Ok(data.to_vec());
+    }
+
+    let image = image.resize_exact(size, size, FilterType::Lanczos3);
+    let mut buf = Cursor::new(Vec::new());
+    match format {
+        // JPEG 不支持透明通道
+        ImageFormat::Jpeg => image.to_rgb8().write_to(&mut buf, format),
+        _ => image.write_to(&mut buf, ImageFormat::Png),
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(buf.into_inner())
+}
+
+// 用户主角色的 64px 头像, 没有主角色或头像的用户不在结果中
+pub async fn main_character_portraits<DB: ConnectionTrait>(
+    db: &DB,
+    users_ids: &[i32],
+) -> Result<BTreeMap<i32, Vec<u8>>, String> {
+    let main_ids = get_main_character_ids(db, users_ids).await?;
+    let ids: BTreeSet<i64> = main_ids.values().copied().collect();
+    let images = get_party_images(db, &ids).await?;
+
+    let mut portraits = BTreeMap::new();
+    for (user_id, character_id) in main_ids {
+        if let Some(data) = images.get(&character_id) {
+            portraits.insert(user_id, thumbnail(data, EMBEDDED_PORTRAIT_SIZE)?);
+        }
+    }
+    Ok(portraits)
+}
+
+// 将图片嵌入到单元格 (col, row) 处, 尺寸取自图片本身
+pub fn embed_image(w: &mut Worksheet, col: u32, row: u32, data: &[u8]) -> Result<(), String> {
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+    let extension = match reader.format().ok_or("unknown image format".to_string())? {
+        ImageFormat::Jpeg => "jpg",
+        _ => "png",
+    };
+    let (width, height) = reader.into_dimensions().map_err(|e| e.to_string())?;
+
+    let mut marker = MarkerType::default();
+    marker.set_coordinate(format!("{}{}", string_from_column_index(&col), row));
+    let mut image = Image::default();
+    image.new_image_with_dimensions(
+        height,
+        width,
+        &format!("{}.{}", image_hash(data), extension),
+        data,
+        marker,
+    );
+    w.add_image(image);
+    Ok(())
+}
+
+#[cfg(test)]
+pub fn test_image(size: u32, format: ImageFormat) -> Vec<u8> {
+    let image = image::RgbImage::from_fn(size, size, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+    });
+    let mut buf = Cursor::new(Vec::new());
+    image.write_to(&mut buf, format).unwrap();
+    buf.into_inner()
+}
+
+#[test]
+fn test_thumbnail() {
+    let source = test_image(512, ImageFormat::Jpeg);
+    for size in [64, 128, 256, 512] {
+        let data = thumbnail(&source, size).unwrap();
+        let image = image::load_from_memory(&data).unwrap();
+        assert_eq!((image.width(), image.height()), (size, size));
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Jpeg);
+    }
+
+    let logo = test_image(256, ImageFormat::Png);
+    let data = thumbnail(&logo, 32).unwrap();
+    assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Png);
+
+    assert_eq!(image_hash(&source), image_hash(&source.clone()));
+    assert_eq!(image_hash(&source).len(), 64);
+    assert!(thumbnail(b"not an image", 64).is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_main_character_portraits() {
+    use crate::db_op::save_party_image;
+    use db_wallet::{
+        Migrator, MigratorTrait,
+        entities::characters::{ActiveModel as AmCharacters, Entity as ECharacters},
+    };
+    use sea_orm::{EntityTrait, Set};
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    for (character_id, user_id, main) in [(1001, 1, true), (1002, 1, false), (2001, 2, true)] {
+        let m = AmCharacters {
+            character_id: Set(character_id),
+            alliance_id: Set(None),
+            corporation_id: Set(98000001),
+            birthday: Set(0),
+            name: Set(format!("c{}", character_id)),
+            user_id: Set(Some(user_id)),
+            main: Set(main),
+            last_refreshed: Set(None),
+        };
+        ECharacters::insert(m).exec(&db).await.unwrap();
+    }
+    let source = test_image(512, ImageFormat::Jpeg);
+    save_party_image(&db, 1001, source.clone()).await.unwrap();
+    save_party_image(&db, 1002, source).await.unwrap();
+
+    // 用户 2 的主角色没有头像, 用户 3 不存在
+    let portraits = main_character_portraits(&db, &[1, 2, 3]).await.unwrap();
+    assert_eq!(portraits.len(), 1);
+    let image = image::load_from_memory(&portraits[&1]).unwrap();
+    assert_eq!(image.width(), EMBEDDED_PORTRAIT_SIZE);
+}
@@ -0,0 +1,367 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rust_decimal::Decimal;
+use sea_orm::{ConnectionTrait, TransactionTrait};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, str::FromStr};
+use strum::IntoEnumIterator;
+
+use crate::{
+    db_op::{db_import_journal, find_party_id_by_name},
+    esi::ResCorporationWalletJournalItem,
+};
+use db_wallet::JournalRefType;
+
+// 游戏客户端导出的钱包流水中的一行
+pub struct ExportRow {
+    pub date_time: DateTime<Utc>,
+    pub ref_type: JournalRefType,
+    pub amount: Decimal,
+    pub balance: Decimal,
+    pub description: String,
+    pub first_party: Option<String>,
+    pub second_party: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum ExportColumn {
+    Date,
+    RefType,
+    Amount,
+    Balance,
+    Description,
+    FirstParty,
+    SecondParty,
+    Reason,
+}
+
+impl ExportColumn {
+    // 根据表头识别列, 兼容英文与中文客户端
+    fn from_header(s: &str) -> Option<ExportColumn> {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "date" | "日期" | "时间" | "日期时间" => Some(ExportColumn::Date),
+            "type" | "ref type" | "类型" => Some(ExportColumn::RefType),
+            "amount" | "金额" | "收支金额" => Some(ExportColumn::Amount),
+            "balance" | "余额" | "账户余额" => Some(ExportColumn::Balance),
+            "description" | "描述" | "说明" | "备注" => Some(ExportColumn::Description),
+            "first party" | "from" | "第一方" | "付款方" => Some(ExportColumn::FirstParty),
+            "second party" | "to" | "第二方" | "收款方" => Some(ExportColumn::SecondParty),
+            "reason" | "原因" => Some(ExportColumn::Reason),
+            _ => None,
+        }
+    }
+}
+
+// 解析导出文件, 返回成功解析的行与各行的错误信息
+// 文件首行为表头, 以制表符或逗号分隔
+pub fn parse_export(text: &str) -> Result<(Vec<ExportRow>, Vec<String>), String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let first_line = text.lines().next().ok_or("empty file".to_string())?;
+    let delimiter = if first_line.contains('\t') {
+        b'\t'
+    } else {
+        b','
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = reader.headers().map_err(|e| e.to_string())?;
+    let columns: Vec<Option<ExportColumn>> =
+        headers.iter().map(ExportColumn::from_header).collect();
+    for required in [
+        ExportColumn::Date,
+        ExportColumn::RefType,
+        ExportColumn::Amount,
+        ExportColumn::Balance,
+    ] {
+        if columns.contains(&Some(required)) == false {
+            return Err(format!("missing column: {:?}", required));
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let line = i + 2;
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                errors.push(format!("line {}: {}", line, e));
+                continue;
+            }
+        };
+
+        let mut fields = BTreeMap::new();
+        for (column, value) in columns.iter().zip(record.iter()) {
+            if let Some(column) = column {
+                let value = value.trim();
+                if value.is_empty() == false {
+                    fields.insert(*column, value.to_string());
+                }
+            }
+        }
+
+        let row = parse_record(&fields);
+        match row {
+            Ok(r) => rows.push(r),
+            Err(e) => errors.push(format!("line {}: {}", line, e)),
+        }
+    }
+
+    Ok((rows, errors))
+}
+
+fn parse_record(fields: &BTreeMap<ExportColumn, String>) -> Result<ExportRow, String> {
+    let field = |c: ExportColumn| fields.get(&c).cloned();
+
+    let date_time = parse_date_time(&field(ExportColumn::Date).unwrap_or_default())?;
+    let type_name = field(ExportColumn::RefType).unwrap_or_default();
+    let ref_type = ref_type_from_name(&type_name).ok_or(format!("unknown type: {}", type_name))?;
+    let amount = parse_isk(&field(ExportColumn::Amount).unwrap_or_default())?;
+    let balance = parse_isk(&field(ExportColumn::Balance).unwrap_or_default())?;
+
+    Ok(ExportRow {
+        date_time,
+        ref_type,
+        amount,
+        balance,
+        description: field(ExportColumn::Description).unwrap_or_default(),
+        first_party: field(ExportColumn::FirstParty),
+        second_party: field(ExportColumn::SecondParty),
+        reason: field(ExportColumn::Reason),
+    })
+}
+
+// 将本地化的类型名映射回 JournalRefType
+// 忽略大小写, 空格与下划线, 同时匹配中文名
+pub fn ref_type_from_name(name: &str) -> Option<JournalRefType> {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(|c| c.to_lowercase())
+            .collect()
+    };
+    let name = normalize(name);
+    if name.is_empty() {
+        return None;
+    }
+
+    JournalRefType::iter().find(|t| normalize(t.as_ref()) == name || normalize(t.zh_str()) == name)
+}
+
+// 游戏内时间即 UTC
+fn parse_date_time(s: &str) -> Result<DateTime<Utc>, String> {
+    let formats = [
+        "%Y.%m.%d %H:%M:%S",
+        "%Y.%m.%d %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y/%m/%d %H:%M:%S",
+        "%Y/%m/%d %H:%M",
+    ];
+    for f in formats {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, f) {
+            return Ok(t.and_utc());
+        }
+    }
+    Err(format!("illegal date: {}", s))
+}
+
+// 解析金额, 忽略千分位与货币单位, 例如 "-1,234,567.89 ISK"
+fn parse_isk(s: &str) -> Result<Decimal, String> {
+    let digits: String = s
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == '-')
+        .collect();
+    Decimal::from_str(digits.as_str()).map_err(|e| format!("illegal amount: {}, {}", s, e))
+}
+
+// 导入的流水没有ID, 以时间, 金额与余额生成稳定的负数ID, 与 ESI 的ID区分
+fn synthetic_id(row: &ExportRow) -> i64 {
+    let key = format!(
+        "{}|{}|{}",
+        row.date_time.timestamp(),
+        row.amount.normalize(),
+        row.balance.normalize()
+    );
+    let hash = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    -((u64::from_be_bytes(bytes) >> 1) as i64) - 1
+}
+
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub duplicated: usize,
+    pub unresolved_names: Vec<String>,
+}
+
+// 解析参与方名称并在一个事务中导入, 与已有流水按 类型, 时间, 金额与余额 去重
+pub async fn import_rows<DB: ConnectionTrait + TransactionTrait>(
+    db: &DB,
+    rows: Vec<ExportRow>,
+) -> Result<ImportSummary, String> {
+    let mut names: BTreeMap<String, Option<i64>> = BTreeMap::new();
+    let mut items = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut party_ids = [None, None];
+        for (i, name) in [&row.first_party, &row.second_party].iter().enumerate() {
+            if let Some(name) = name {
+                if names.contains_key(name) == false {
+                    let id = find_party_id_by_name(db, name).await?;
+                    names.insert(name.clone(), id);
+                }
+                party_ids[i] = names[name];
+            }
+        }
+
+        items.push(ResCorporationWalletJournalItem {
+            id: synthetic_id(&row),
+            date: row.date_time,
+            ref_type: row.ref_type,
+            description: row.description,
+            amount: Some(row.amount),
+            balance: Some(row.balance),
+            context_id: None,
+            context_id_type: None,
+            reason: row.reason,
+            first_party_id: party_ids[0],
+            second_party_id: party_ids[1],
+            tax: None,
+            tax_receiver_id: None,
+        });
+    }
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    let (inserted, duplicated) = db_import_journal(&txn, items).await?;
+    txn.commit().await.map_err(|e| e.to_string())?;
+    let unresolved_names = names
+        .into_iter()
+        .filter(|(_, id)| id.is_none())
+        .map(|(name, _)| name)
+        .collect();
+
+    Ok(ImportSummary {
+        inserted,
+        duplicated,
+        unresolved_names,
+    })
+}
+
+#[test]
+fn test_ref_type_from_name() {
+    assert!(ref_type_from_name("Player Donation") == Some(JournalRefType::PlayerDonation));
+    assert!(ref_type_from_name("player_donation") == Some(JournalRefType::PlayerDonation));
+    assert!(ref_type_from_name("玩家捐助") == Some(JournalRefType::PlayerDonation));
+    assert!(ref_type_from_name("Bounty Prizes") == Some(JournalRefType::BountyPrizes));
+    assert!(ref_type_from_name("no such type").is_none());
+}
+
+#[test]
+fn test_parse_export() {
+    let text = "\u{feff}Date\tType\tAmount\tBalance\tDescription\tFirst Party\n\
+                2025.10.01 12:30\tPlayer Donation\t\"100,000,000.00 ISK\"\t1,234,567,890.50 ISK\tdeposit\tAlice\n\
+                2025.10.01 13:00\tUnknown Thing\t1\t2\t\t\n\
+                2025-10-02 08:00:05\t军团账户支取\t-5,000.00\t1,234,562,890.50\t\t\n";
+    let (rows, errors) = parse_export(text).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(errors.len(), 1);
+
+    assert!(rows[0].ref_type == JournalRefType::PlayerDonation);
+    assert_eq!(rows[0].amount, Decimal::new(100000000, 0));
+    assert_eq!(rows[0].balance, Decimal::new(123456789050, 2));
+    assert_eq!(rows[0].first_party.as_deref(), Some("Alice"));
+    assert_eq!(rows[0].date_time.timestamp(), 1759321800);
+
+    assert!(rows[1].ref_type == JournalRefType::CorporationAccountWithdrawal);
+    assert_eq!(rows[1].amount, Decimal::new(-5000, 0));
+    assert!(synthetic_id(&rows[1]) < 0);
+
+    let text = "date,amount\n2025.10.01 12:30,1\n";
+    assert!(parse_export(text).is_err());
+}
+
+// 先导入客户端流水再同步 ESI, 同一笔流水只保留 ESI 的记录
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_import_then_sync() {
+    use crate::{
+        db_op::{db_upgrade_wall_journal, decimal_from_i64},
+        esi::ResCorporationWalletJournal,
+        notify::EventQueue,
+    };
+    use db_wallet::{
+        JournalSource, Migrator, MigratorTrait,
+        entities::corporation_wallet_journal::{
+            Column as CCorporationWalletJournal, Entity as ECorporationWalletJournal,
+        },
+    };
+    use sea_orm::{EntityTrait, QueryOrder};
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    // 客户端导出的时间只精确到分钟
+    let text = "Date\tType\tAmount\tBalance\tDescription\tFirst Party\n\
+                2025.10.01 12:30\tPlayer Donation\t100,000,000.00\t1,100,000,000.00\tdeposit\tAlice\n\
+                2025.10.01 13:00\tPlayer Donation\t100,000,000.00\t1,200,000,000.00\tdeposit\tAlice\n";
+    let (rows, _) = parse_export(text).unwrap();
+    let summary = import_rows(&db, rows).await.unwrap();
+    assert_eq!(summary.inserted, 2);
+
+    let esi_item = |id: i64, date: i64, balance: i64| ResCorporationWalletJournalItem {
+        id,
+        date: DateTime::from_timestamp_secs(date).unwrap(),
+        ref_type: JournalRefType::PlayerDonation,
+        description: "deposit".to_string(),
+        amount: Some(Decimal::new(100000000, 0)),
+        balance: Some(Decimal::new(balance, 0)),
+        context_id: None,
+        context_id_type: None,
+        reason: None,
+        first_party_id: Some(1001),
+        second_party_id: Some(98000001),
+        tax: None,
+        tax_receiver_id: None,
+    };
+    // 前两笔与导入的流水相同, 第三笔为新流水
+    let page = ResCorporationWalletJournal(vec![
+        esi_item(101, 1759321825, 1100000000),
+        esi_item(102, 1759323612, 1200000000),
+        esi_item(103, 1759330000, 1300000000),
+    ]);
+    let mut events = EventQueue::new(Decimal::ZERO);
+    let count = db_upgrade_wall_journal(&db, page, &mut events)
+        .await
+        .unwrap();
+    assert_eq!((count.inserted, count.replaced, count.skipped), (3, 2, 0));
+    assert_eq!(events.events().len(), 1);
+
+    let rows = ECorporationWalletJournal::find()
+        .order_by_asc(CCorporationWalletJournal::Id)
+        .all(&db)
+        .await
+        .unwrap();
+    let ids: Vec<(i64, i32)> = rows.iter().map(|r| (r.id, r.source)).collect();
+    let esi = JournalSource::Esi as i32;
+    assert_eq!(ids, vec![(101, esi), (102, esi), (103, esi)]);
+    let total: i64 = rows.iter().filter_map(|r| r.amount).sum();
+    assert_eq!(decimal_from_i64(total), Decimal::new(300000000, 0));
+
+    // 再次导入同一文件时, 按类型, 时间, 金额, 余额 与已同步的 ESI 流水视为重复
+    let (rows, _) = parse_export(text).unwrap();
+    let summary = import_rows(&db, rows).await.unwrap();
+    assert_eq!((summary.inserted, summary.duplicated), (0, 2));
+
+    // 类型不同的不是同一笔流水
+    let text = "Date\tType\tAmount\tBalance\tDescription\tFirst Party\n\
+                2025.10.01 12:30\tBounty Prizes\t100,000,000.00\t1,100,000,000.00\tbounty\tAlice\n";
+    let (rows, _) = parse_export(text).unwrap();
+    let summary = import_rows(&db, rows).await.unwrap();
+    assert_eq!((summary.inserted, summary.duplicated), (1, 0));
+}
@@ -0,0 +1,397 @@
+use chrono::{Duration, Utc};
+use futures::{StreamExt, stream};
+use indicatif::{ProgressBar, ProgressStyle};
+use sea_orm::ConnectionTrait;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::Path};
+use tokio::{
+    fs::{OpenOptions, read_to_string},
+    io::AsyncWriteExt,
+};
+
+use crate::{
+    db_op::{
+        PartyChange, check_out_unknown_ids, get_all_ids, get_ids_without_logo,
+        get_stale_character_ids, get_stale_corporation_ids, insert_character_info,
+        insert_corporation_info, refresh_character_info, refresh_corporation_info,
+        save_party_image,
+    },
+    esi::QueryDevice,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Resolved {
+    Character,
+    Corporation,
+    NotFound,
+}
+
+// 状态文件中的一行, 记录已处理的ID
+// 再次运行时跳过这些ID, 查询失败的ID不记录, 下次会重试
+#[derive(Serialize, Deserialize)]
+struct ProcessedId {
+    id: i64,
+    status: Resolved,
+}
+
+#[derive(Default)]
+pub struct UpgradeSummary {
+    pub characters: usize,
+    pub corporations: usize,
+    pub resumed: usize,
+    pub logos: usize,
+    pub not_found: Vec<i64>,
+    pub failures: Vec<(i64, String)>,
+}
+
+impl UpgradeSummary {
+    pub fn print(&self) {
+        println!(
+            "inserted {} characters, {} corporations, {} logos, skipped {} processed ids",
+            self.characters, self.corporations, self.logos, self.resumed
+        );
+        if self.not_found.is_empty() == false {
+            println!("the final unknown ids: {:?}", self.not_found);
+        }
+        if self.failures.is_empty() == false {
+            println!("failed {} ids:", self.failures.len());
+            for (id, e) in &self.failures {
+                println!("  {}: {}", id, e);
+            }
+        }
+    }
+}
+
+// 读取状态文件, 文件不存在时视为空
+// 中断时可能留下不完整的最后一行, 忽略无法解析的行
+async fn read_processed(path: &Path) -> Result<BTreeSet<i64>, String> {
+    if path.exists() == false {
+        return Ok(BTreeSet::new());
+    }
+    let s = read_to_string(path).await.map_err(|e| e.to_string())?;
+    Ok(s.lines()
+        .filter_map(|l| serde_json::from_str::<ProcessedId>(l).ok())
+        .map(|p| p.id)
+        .collect())
+}
+
+async fn resolve_id<DB: ConnectionTrait>(
+    query_device: &QueryDevice,
+    db: &DB,
+    id: i64,
+) -> Result<(Resolved, String), String> {
+    if let Some(info) = query_device.get_character_public_information(id).await? {
+        let name = info.name.clone();
+        let portrait = query_device.get_portrait(id).await?;
+        insert_character_info(db, id, info, portrait).await?;
+        return Ok((Resolved::Character, name));
+    }
+
+    if let Some(info) = query_device.get_corporation_information(id).await? {
+        let name = info.name.clone();
+        let logo = query_device.get_corporation_logo(id).await?;
+        insert_corporation_info(db, id, info).await?;
+        save_party_image(db, id, logo).await?;
+        return Ok((Resolved::Corporation, name));
+    }
+
+    Ok((Resolved::NotFound, String::new()))
+}
+
+// 以 workers 个并发任务查询未知ID, 所有任务共享同一个 QueryDevice 的限流状态
+// 单个ID失败不会中止整个过程, 失败汇总在返回值中
+pub async fn upgrade_information<DB: ConnectionTrait>(
+    query_device: &QueryDevice,
+    db: &DB,
+    workers: usize,
+    state_path: Option<&Path>,
+) -> Result<UpgradeSummary, String> {
+    let mut summary = UpgradeSummary::default();
+
+    let ids = get_all_ids(db).await?;
+    println!("all id count: {}", ids.len());
+    let mut ids = check_out_unknown_ids(db, ids).await?;
+    if let Some(path) = state_path {
+        let processed = read_processed(path).await?;
+        let count = ids.len();
+        ids.retain(|id| processed.contains(id) == false);
+        summary.resumed = count - ids.len();
+    }
+    println!("unknown id count: {}", ids.len());
+    if ids.is_empty() == false {
+        upgrade_ids(query_device, db, workers, ids, state_path, &mut summary).await?;
+    }
+    upgrade_logos(query_device, db, workers, &mut summary).await?;
+
+    summary.not_found.sort();
+    summary.failures.sort_by_key(|(id, _)| *id);
+    Ok(summary)
+}
+
+async fn upgrade_ids<DB: ConnectionTrait>(
+    query_device: &QueryDevice,
+    db: &DB,
+    workers: usize,
+    ids: Vec<i64>,
+    state_path: Option<&Path>,
+    summary: &mut UpgradeSummary,
+) -> Result<(), String> {
+    let mut state_file = match state_path {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await
+                .map_err(|e| e.to_string())?,
+        ),
+        None => None,
+    };
+
+    let bar = ProgressBar::new(ids.len() as u64);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {wide_bar} {pos}/{len} eta {eta}")
+            .map_err(|e| e.to_string())?,
+    );
+
+    let mut results = stream::iter(ids)
+        .map(|id| async move { (id, resolve_id(query_device, db, id).await) })
+        .buffer_unordered(workers.max(1));
+
+    while let Some((id, result)) = results.next().await {
+        bar.inc(1);
+        let status = match result {
+            Ok((status, name)) => {
+                match status {
+                    Resolved::Character => {
+                        summary.characters += 1;
+                        bar.println(format!("inserted character {}: {}", id, name));
+                    }
+                    Resolved::Corporation => {
+                        summary.corporations += 1;
+                        bar.println(format!("inserted corporation {}: {}", id, name));
+                    }
+                    Resolved::NotFound => summary.not_found.push(id),
+                }
+                status
+            }
+            Err(e) => {
+                summary.failures.push((id, e));
+                continue;
+            }
+        };
+
+        if let Some(file) = state_file.as_mut() {
+            let mut line =
+                serde_json::to_string(&ProcessedId { id, status }).map_err(|e| e.to_string())?;
+            line.push('\n');
+            file.write_all(line.as_bytes())
+                .await
+                .map_err(|e| e.to_string())?;
+            // 每处理一个ID即落盘, 中断后再次运行时不会重复或遗漏
+            file.flush().await.map_err(|e| e.to_string())?;
+        }
+    }
+    bar.finish();
+
+    Ok(())
+}
+
+// 补全公司与联盟的图标, 包括此前已入库但没有图标的公司
+async fn upgrade_logos<DB: ConnectionTrait>(
+    query_device: &QueryDevice,
+    db: &DB,
+    workers: usize,
+    summary: &mut UpgradeSummary,
+) -> Result<(), String> {
+    let (corporation_ids, alliance_ids) = get_ids_without_logo(db).await?;
+    let mut parties: Vec<Party> = corporation_ids
+        .into_iter()
+        .map(Party::Corporation)
+        .collect();
+    parties.extend(alliance_ids.into_iter().map(Party::Alliance));
+    println!("missing logo count: {}", parties.len());
+    if parties.is_empty() {
+        return Ok(());
+    }
+
+    let mut results = stream::iter(parties)
+        .map(|party| async move {
+            let logo = match party {
+                Party::Corporation(id) => query_device.get_corporation_logo(id).await,
+                Party::Alliance(id) => query_device.get_alliance_logo(id).await,
+                Party::Character(id) => query_device.get_portrait(id).await,
+            };
+            let result = match logo {
+                Ok(logo) => save_party_image(db, party.id(), logo).await,
+                Err(e) => Err(e),
+            };
+            (party, result)
+        })
+        .buffer_unordered(workers.max(1));
+
+    while let Some((party, result)) = results.next().await {
+        match result {
+            Ok(_) => summary.logos += 1,
+            Err(e) => summary.failures.push((party.id(), e)),
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Party {
+    Character(i64),
+    Corporation(i64),
+    Alliance(i64),
+}
+
+impl Party {
+    fn id(self) -> i64 {
+        match self {
+            Party::Character(id) | Party::Corporation(id) | Party::Alliance(id) => id,
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Party::Character(id) => format!("character {}", id),
+            Party::Corporation(id) => format!("corporation {}", id),
+            Party::Alliance(id) => format!("alliance {}", id),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RefreshSummary {
+    pub refreshed: usize,
+    pub changed: usize,
+    pub not_found: Vec<String>, // ESI 已查询不到, 例如角色已删除
+    pub failures: Vec<(String, String)>,
+}
+
+impl RefreshSummary {
+    pub fn print(&self) {
+        println!(
+            "refreshed {} records, {} changed",
+            self.refreshed, self.changed
+        );
+        if self.not_found.is_empty() == false {
+            println!("not found: {:?}", self.not_found);
+        }
+        if self.failures.is_empty() == false {
+            println!("failed {} records:", self.failures.len());
+            for (party, e) in &self.failures {
+                println!("  {}: {}", party, e);
+            }
+        }
+    }
+}
+
+fn describe_change(change: &PartyChange) -> String {
+    let mut parts = Vec::new();
+    if let Some((old, new)) = change.corporation_id {
+        parts.push(format!("corporation {} -> {}", old, new));
+    }
+    if let Some((old, new)) = change.alliance_id {
+        let show = |a: Option<i64>| a.map_or("none".to_string(), |a| a.to_string());
+        parts.push(format!("alliance {} -> {}", show(old), show(new)));
+    }
+    if let Some((old, new)) = &change.name {
+        parts.push(format!("name {} -> {}", old, new));
+    }
+    parts.join(", ")
+}
+
+// 返回 None 表示 ESI 已查询不到
+async fn refresh_party<DB: ConnectionTrait>(
+    query_device: &QueryDevice,
+    db: &DB,
+    party: Party,
+    now: i64,
+) -> Result<Option<Option<PartyChange>>, String> {
+    match party {
+        Party::Character(id) => match query_device.get_character_public_information(id).await? {
+            Some(info) => Ok(Some(refresh_character_info(db, id, info, now).await?)),
+            None => Ok(None),
+        },
+        Party::Corporation(id) => match query_device.get_corporation_information(id).await? {
+            Some(info) => Ok(Some(refresh_corporation_info(db, id, info, now).await?)),
+            None => Ok(None),
+        },
+        Party::Alliance(_) => Ok(None),
+    }
+}
+
+// 重新查询刷新时间早于 max_age 之前的角色与公司, 公司, 联盟或名称的变化写入 party_history
+pub async fn refresh_information<DB: ConnectionTrait>(
+    query_device: &QueryDevice,
+    db: &DB,
+    workers: usize,
+    max_age: Duration,
+) -> Result<RefreshSummary, String> {
+    let mut summary = RefreshSummary::default();
+    let now = Utc::now().timestamp();
+    let before = now - max_age.num_seconds();
+
+    let mut parties: Vec<Party> = get_stale_character_ids(db, before)
+        .await?
+        .into_iter()
+        .map(Party::Character)
+        .collect();
+    parties.extend(
+        get_stale_corporation_ids(db, before)
+            .await?
+            .into_iter()
+            .map(Party::Corporation),
+    );
+    println!("stale record count: {}", parties.len());
+    if parties.is_empty() {
+        return Ok(summary);
+    }
+
+    let bar = ProgressBar::new(parties.len() as u64);
+    bar.set_style(
+        ProgressStyle::with_template("[{elapsed_precise}] {wide_bar} {pos}/{len} eta {eta}")
+            .map_err(|e| e.to_string())?,
+    );
+
+    let mut results = stream::iter(parties)
+        .map(|party| async move { (party, refresh_party(query_device, db, party, now).await) })
+        .buffer_unordered(workers.max(1));
+
+    while let Some((party, result)) = results.next().await {
+        bar.inc(1);
+        match result {
+            Ok(Some(change)) => {
+                summary.refreshed += 1;
+                if let Some(change) = change {
+                    summary.changed += 1;
+                    bar.println(format!("{}: {}", party.label(), describe_change(&change)));
+                }
+            }
+            Ok(None) => summary.not_found.push(party.label()),
+            Err(e) => summary.failures.push((party.label(), e)),
+        }
+    }
+    bar.finish();
+
+    Ok(summary)
+}
+
+#[tokio::test]
+async fn test_read_processed() {
+    let path = std::env::temp_dir().join(format!("processed_{}.jsonl", std::process::id()));
+    assert!(read_processed(&path).await.unwrap().is_empty());
+
+    let text = "{\"id\":1,\"status\":\"character\"}\n\
+                {\"id\":2,\"status\":\"not_found\"}\n\
+                {\"id\":3,\"sta";
+    tokio::fs::write(&path, text).await.unwrap();
+    let processed = read_processed(&path).await.unwrap();
+    let _ = tokio::fs::remove_file(&path).await;
+
+    assert_eq!(processed, BTreeSet::from([1, 2]));
+}
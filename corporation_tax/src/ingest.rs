@@ -0,0 +1,829 @@
+use clap::ValueEnum;
+use rust_decimal::{
+    Decimal,
+    prelude::{FromPrimitive, ToPrimitive},
+};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, NotSet, QueryFilter, Set,
+    TransactionTrait,
+};
+use std::{collections::BTreeMap, path::Path};
+use umya_spreadsheet::{Worksheet, helper::coordinate::string_from_column_index, reader};
+
+use crate::{
+    db_op::{RangeYearMonth, TaxLedger, YearMonth},
+    locale::{Lang, Text},
+    statement::format_isk_text,
+};
+use db_wallet::entities::{
+    pap_journal::{ActiveModel as AmPapJournal, Column as CPapJournal, Entity as EPapJournal},
+    taxable_list::{ActiveModel as AmTaxableList, Column as CTaxableList, Entity as ETaxableList},
+    users::{ActiveModel as AmUsers, Entity as EUsers},
+};
+
+// 从税收清单读回的一个月的税额, 为 None 表示单元格仍是引用原始数据的公式, 即未修改
+#[derive(Default, Clone)]
+pub struct EditedMonth {
+    pub pap_tax: Option<Decimal>,
+    pub poll_tax: Option<Decimal>,
+    pub paid_up_tax: Option<Decimal>,
+}
+
+pub struct EditedRow {
+    pub user_id: Option<i32>, // 没有用户ID列的旧报表为 None, 按主角色名匹配用户
+    pub character_name: String,
+    pub months: Vec<EditedMonth>,
+    pub note: Option<String>, // 没有备注列的旧报表为 None
+}
+
+// 已生成并经人工修改的税收清单
+pub struct EditedTaxList {
+    pub lang: Lang,
+    pub year_months: Vec<YearMonth>,
+    pub rows: Vec<EditedRow>,
+}
+
+// 写回数据库的修改
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Update {
+    PollTaxable {
+        user_id: i32,
+        year_month: YearMonth,
+        taxable: bool,
+    },
+    PapTaxable {
+        user_id: i32,
+        year_month: YearMonth,
+        taxable: bool,
+    },
+    // 修正主角色的 PAP, 单位与 pap_journal 相同, 为 0.01 分
+    Pap {
+        character_id: i64,
+        year_month: YearMonth,
+        delta: i32,
+    },
+    Note {
+        user_id: i32,
+        note: Option<String>,
+    },
+}
+
+// 一个被修改的单元格, updates 为 Err 时说明无法写回的原因
+pub struct Change {
+    pub character_name: String,
+    pub field: String,
+    pub before: String,
+    pub after: String,
+    pub updates: Result<Vec<Update>, String>,
+}
+
+impl EditedTaxList {
+    // 查找税收清单工作表, 未指定名称时依次尝试各语言的默认名称
+    pub fn read(
+        path: &Path,
+        sheet_name: Option<&str>,
+        start: YearMonth,
+        end: YearMonth,
+    ) -> Result<Self, String> {
+        let book = reader::xlsx::read(path).map_err(|e| e.to_string())?;
+        let names: Vec<String> = match sheet_name {
+            Some(name) => vec![name.to_string()],
+            None => Lang::value_variants()
+                .iter()
+                .map(|lang| lang.text(Text::SheetTaxList).to_string())
+                .collect(),
+        };
+        let w = names
+            .iter()
+            .find_map(|name| book.get_sheet_by_name(name))
+            .ok_or(format!("sheet {} not found", names.join(" / ")))?;
+        EditedTaxList::from_worksheet(w, start, end)
+    }
+
+    // 布局与 SheetTaxList::insert_worksheet 生成的相同, 语言与头像列由表头判断
+    fn from_worksheet(w: &Worksheet, start: YearMonth, end: YearMonth) -> Result<Self, String> {
+        let header = w.get_value((1, 1));
+        let lang = Lang::value_variants()
+            .iter()
+            .copied()
+            .find(|lang| lang.text(Text::MainCharacter) == header)
+            .ok_or(format!("A1: unexpected header {}", header))?;
+
+        let unpaid_column = if w.get_value((2, 1)) == lang.text(Text::Portrait) {
+            3
+        } else {
+            2
+        };
+        expect_header(w, unpaid_column, lang.text(Text::UnpaidTax))?;
+
+        let year_months: Vec<YearMonth> = RangeYearMonth::new(start, end).collect();
+        for (i, ym) in year_months.iter().enumerate() {
+            let col = i as u32 * 3 + unpaid_column + 1;
+            expect_header(w, col, lang.year_month(*ym).as_str())?;
+        }
+        let note_column = unpaid_column + year_months.len() as u32 * 3 + 1;
+        let has_note = w.get_value((note_column, 1)) == lang.text(Text::Note);
+        let user_id_column = note_column + 1;
+        let has_user_id = w.get_value((user_id_column, 1)) == lang.text(Text::UserId);
+
+        let mut rows = Vec::new();
+        for row in 3..=w.get_highest_row() {
+            let character_name = w.get_value((1, row));
+            if character_name.is_empty() || character_name == lang.text(Text::Total) {
+                break;
+            }
+
+            let mut months = Vec::new();
+            for i in 0..year_months.len() {
+                let col = i as u32 * 3 + unpaid_column + 1;
+                months.push(EditedMonth {
+                    pap_tax: edited_value(w, col, row)?,
+                    poll_tax: edited_value(w, col + 1, row)?,
+                    paid_up_tax: edited_value(w, col + 2, row)?,
+                });
+            }
+            let note = has_note.then(|| w.get_value((note_column, row)).trim().to_string());
+            let user_id = if has_user_id {
+                Some(user_id_value(w, user_id_column, row)?)
+            } else {
+                None
+            };
+
+            rows.push(EditedRow {
+                user_id,
+                character_name,
+                months,
+                note,
+            });
+        }
+
+        Ok(EditedTaxList {
+            lang,
+            year_months,
+            rows,
+        })
+    }
+
+    // 与数据库中的当前值比较, 得到被修改的单元格及对应的数据库修改
+    pub fn changes(&self, ledger: &TaxLedger) -> Result<Vec<Change>, String> {
+        let lang = self.lang;
+        // 主角色名可能重复, 仅用于没有用户ID列的旧报表
+        let mut users: BTreeMap<String, Vec<i32>> = BTreeMap::new();
+        for user_id in ledger.users_ids().iter().copied() {
+            users
+                .entry(ledger.main_character_name(user_id)?)
+                .or_default()
+                .push(user_id);
+        }
+
+        let mut changes = Vec::new();
+        for row in &self.rows {
+            let change = |field: String, before: String, after: String, updates| Change {
+                character_name: row.character_name.clone(),
+                field,
+                before,
+                after,
+                updates,
+            };
+
+            let user_id = match row.user_id {
+                Some(user_id) if ledger.users_ids().contains(&user_id) => Ok(user_id),
+                Some(_) => Err("user not found"),
+                None => match users.get(&row.character_name).map(|ids| ids.as_slice()) {
+                    Some([user_id]) => Ok(*user_id),
+                    Some(_) => Err("character name is shared by several users"),
+                    None => Err("user not found"),
+                },
+            };
+            let user_id = match user_id {
+                Ok(user_id) => user_id,
+                Err(e) => {
+                    changes.push(change(
+                        lang.text(Text::MainCharacter).to_string(),
+                        String::new(),
+                        row.character_name.clone(),
+                        Err(e.to_string()),
+                    ));
+                    continue;
+                }
+            };
+
+            for (ym, edited) in self.year_months.iter().copied().zip(row.months.iter()) {
+                let field = |text| format!("{} {}", lang.year_month(ym), lang.text(text));
+                let (poll_tax, pap_tax) = ledger.user_tax(user_id, ym)?;
+                let paid_up_tax = ledger.paid_up_tax(user_id, ym);
+
+                if let Some(v) = edited.pap_tax.filter(|v| *v != pap_tax) {
+                    changes.push(change(
+                        field(Text::PapTax),
+                        format_isk_text(pap_tax),
+                        format_isk_text(v),
+                        pap_tax_updates(ledger, user_id, ym, v),
+                    ));
+                }
+                if let Some(v) = edited.poll_tax.filter(|v| *v != poll_tax) {
+                    changes.push(change(
+                        field(Text::PollTax),
+                        format_isk_text(poll_tax),
+                        format_isk_text(v),
+                        poll_tax_updates(ledger, user_id, ym, v),
+                    ));
+                }
+                if let Some(v) = edited.paid_up_tax.filter(|v| *v != paid_up_tax) {
+                    changes.push(change(
+                        field(Text::PaidUpTax),
+                        format_isk_text(paid_up_tax),
+                        format_isk_text(v),
+                        Err("paid tax is derived from the wallet journal".to_string()),
+                    ));
+                }
+            }
+
+            if let Some(note) = &row.note {
+                let before = ledger.note(user_id).unwrap_or_default();
+                if note != before {
+                    let update = Update::Note {
+                        user_id,
+                        note: (note.is_empty() == false).then(|| note.clone()),
+                    };
+                    changes.push(change(
+                        lang.text(Text::Note).to_string(),
+                        before.to_string(),
+                        note.clone(),
+                        Ok(vec![update]),
+                    ));
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+impl Change {
+    pub fn to_text(&self) -> String {
+        let mut s = format!(
+            "{} {}: \"{}\" -> \"{}\"",
+            self.character_name, self.field, self.before, self.after
+        );
+        match &self.updates {
+            Ok(updates) => {
+                let updates: Vec<String> = updates.iter().map(|u| u.to_text()).collect();
+                s += &format!(" => {}", updates.join(", "));
+            }
+            Err(e) => s += &format!(" (ignored: {})", e),
+        }
+        s
+    }
+}
+
+impl Update {
+    fn to_text(&self) -> String {
+        match self {
+            Update::PollTaxable {
+                year_month,
+                taxable,
+                ..
+            } => format!(
+                "poll tax {} {}",
+                taxable_text(*taxable),
+                year_month.to_key()
+            ),
+            Update::PapTaxable {
+                year_month,
+                taxable,
+                ..
+            } => format!("pap tax {} {}", taxable_text(*taxable), year_month.to_key()),
+            Update::Pap {
+                character_id,
+                year_month,
+                delta,
+            } => format!(
+                "pap of character {} in {} {:+}",
+                character_id,
+                year_month.to_key(),
+                Decimal::new(*delta as i64, 2)
+            ),
+            Update::Note { note, .. } => match note {
+                Some(_) => "set note".to_string(),
+                None => "clear note".to_string(),
+            },
+        }
+    }
+}
+
+fn taxable_text(taxable: bool) -> &'static str {
+    if taxable { "applied in" } else { "exempted in" }
+}
+
+fn expect_header(w: &Worksheet, col: u32, expected: &str) -> Result<(), String> {
+    let header = w.get_value((col, 1));
+    if header != expected {
+        return Err(format!(
+            "{}1: expected header {}, found {}",
+            string_from_column_index(&col),
+            expected,
+            header
+        ));
+    }
+    Ok(())
+}
+
+// 隐藏的用户ID单元格
+fn user_id_value(w: &Worksheet, col: u32, row: u32) -> Result<i32, String> {
+    let value = w.get_value((col, row));
+    value.trim().parse::<i32>().map_err(|_| {
+        format!(
+            "{}{}: invalid user id {}",
+            string_from_column_index(&col),
+            row,
+            value
+        )
+    })
+}
+
+// 税额单元格, 仍为公式时为 None, 清空的单元格视为 0
+fn edited_value(w: &Worksheet, col: u32, row: u32) -> Result<Option<Decimal>, String> {
+    let cell = match w.get_cell((col, row)) {
+        Some(cell) => cell,
+        None => return Ok(Some(Decimal::ZERO)),
+    };
+    if cell.get_formula().is_empty() == false {
+        return Ok(None);
+    }
+    if let Some(v) = cell.get_value_number() {
+        return Decimal::from_f64(v)
+            .map(|v| Some(v.round_dp(2)))
+            .ok_or(format!(
+                "{}{}: invalid number",
+                string_from_column_index(&col),
+                row
+            ));
+    }
+    let text = cell.get_value();
+    if text.trim().is_empty() {
+        Ok(Some(Decimal::ZERO))
+    } else {
+        Err(format!(
+            "{}{}: {} is not a number",
+            string_from_column_index(&col),
+            row,
+            text
+        ))
+    }
+}
+
+// 人头税只能免除或按参数缴纳
+fn poll_tax_updates(
+    ledger: &TaxLedger,
+    user_id: i32,
+    year_month: YearMonth,
+    value: Decimal,
+) -> Result<Vec<Update>, String> {
+    let taxable = if value.is_zero() {
+        false
+    } else {
+        let (poll_tax, _, _) = ledger
+            .parameters(year_month)
+            .ok_or("no tax parameters".to_string())?;
+        if value != poll_tax {
+            return Err(format!(
+                "poll tax can only be 0 or {}",
+                format_isk_text(poll_tax)
+            ));
+        }
+        true
+    };
+
+    Ok(vec![Update::PollTaxable {
+        user_id,
+        year_month,
+        taxable,
+    }])
+}
+
+// PAP税额为 0 时免除, 否则按 PAP税额 = (标准 - PAP) * 每分税额 反推 PAP, 差值记入主角色
+fn pap_tax_updates(
+    ledger: &TaxLedger,
+    user_id: i32,
+    year_month: YearMonth,
+    value: Decimal,
+) -> Result<Vec<Update>, String> {
+    if value.is_zero() {
+        return Ok(vec![Update::PapTaxable {
+            user_id,
+            year_month,
+            taxable: false,
+        }]);
+    }
+    if value.is_sign_negative() {
+        return Err("pap tax can not be negative".to_string());
+    }
+
+    let (_, pap_tax, pap_standard) = ledger
+        .parameters(year_month)
+        .ok_or("no tax parameters".to_string())?;
+    if pap_tax.is_zero() {
+        return Err("pap tax rate is 0".to_string());
+    }
+    let pap = pap_standard - value / pap_tax;
+    if pap.is_sign_negative() {
+        return Err("pap tax exceeds the standard".to_string());
+    }
+    let delta = (pap - ledger.user_pap(user_id, year_month)) * Decimal::from(100);
+    if delta.fract().is_zero() == false {
+        return Err(format!(
+            "pap tax is not a multiple of {}",
+            format_isk_text(pap_tax / Decimal::from(100))
+        ));
+    }
+
+    let mut updates = Vec::new();
+    let (_, taxable) = ledger.taxable(user_id, year_month);
+    if taxable == false {
+        updates.push(Update::PapTaxable {
+            user_id,
+            year_month,
+            taxable: true,
+        });
+    }
+    if delta.is_zero() == false {
+        let character_id = ledger
+            .main_character_id(user_id)
+            .ok_or("user has no main character".to_string())?;
+        updates.push(Update::Pap {
+            character_id,
+            year_month,
+            delta: delta.to_i32().ok_or("pap out of range".to_string())?,
+        });
+    }
+    Ok(updates)
+}
+
+// 在一个事务中写回所有可写回的修改, 返回写入的修改数
+pub async fn apply_changes<DB: ConnectionTrait + TransactionTrait>(
+    db: &DB,
+    changes: &[Change],
+) -> Result<usize, String> {
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+
+    let mut count = 0;
+    for update in changes
+        .iter()
+        .filter_map(|c| c.updates.as_ref().ok())
+        .flatten()
+    {
+        apply_update(&txn, update).await?;
+        count += 1;
+    }
+
+    txn.commit().await.map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+async fn apply_update<DB: ConnectionTrait>(db: &DB, update: &Update) -> Result<(), String> {
+    match update {
+        Update::PollTaxable {
+            user_id,
+            year_month,
+            taxable,
+        } => set_taxable(db, *user_id, *year_month, Some(*taxable), None).await,
+        Update::PapTaxable {
+            user_id,
+            year_month,
+            taxable,
+        } => set_taxable(db, *user_id, *year_month, None, Some(*taxable)).await,
+        Update::Pap {
+            character_id,
+            year_month,
+            delta,
+        } => add_pap(db, *character_id, *year_month, *delta).await,
+        Update::Note { user_id, note } => {
+            let m = AmUsers {
+                id: Set(*user_id),
+                we_chat_id: NotSet,
+                we_chat_nick_name: NotSet,
+                we_chat_group_nickname: NotSet,
+                tax_note: Set(note.clone()),
+            };
+            EUsers::update(m)
+                .exec(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        }
+    }
+}
+
+// 修改 taxable_list 中的标记, 没有该月记录时新增, 未指定的标记为 false
+async fn set_taxable<DB: ConnectionTrait>(
+    db: &DB,
+    user_id: i32,
+    year_month: YearMonth,
+    poll_tax: Option<bool>,
+    pap_tax: Option<bool>,
+) -> Result<(), String> {
+    let row = ETaxableList::find()
+        .filter(
+            Condition::all()
+                .add(CTaxableList::UserId.eq(user_id))
+                .add(CTaxableList::Year.eq(year_month.year as i32))
+                .add(CTaxableList::Month.eq(year_month.month as i32)),
+        )
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match row {
+        Some(row) => {
+            let m = AmTaxableList {
+                id: Set(row.id),
+                user_id: NotSet,
+                year: NotSet,
+                month: NotSet,
+                poll_tax: poll_tax.map_or(NotSet, Set),
+                pap_tax: pap_tax.map_or(NotSet, Set),
+            };
+            ETaxableList::update(m)
+                .exec(db)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        None => {
+            let m = AmTaxableList {
+                id: NotSet,
+                user_id: Set(user_id),
+                year: Set(year_month.year as i32),
+                month: Set(year_month.month as i32),
+                poll_tax: Set(poll_tax.unwrap_or(false)),
+                pap_tax: Set(pap_tax.unwrap_or(false)),
+            };
+            ETaxableList::insert(m)
+                .exec(db)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// 在角色该月的 PAP 上增加 delta, 没有该月记录时新增
+async fn add_pap<DB: ConnectionTrait>(
+    db: &DB,
+    character_id: i64,
+    year_month: YearMonth,
+    delta: i32,
+) -> Result<(), String> {
+    let row = EPapJournal::find()
+        .filter(
+            Condition::all()
+                .add(CPapJournal::CharacterId.eq(character_id))
+                .add(CPapJournal::Year.eq(year_month.year as i32))
+                .add(CPapJournal::Month.eq(year_month.month as i32)),
+        )
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match row {
+        Some(row) => {
+            let m = AmPapJournal {
+                id: Set(row.id),
+                character_id: NotSet,
+                year: NotSet,
+                month: NotSet,
+                pap: Set(row.pap + delta),
+            };
+            EPapJournal::update(m)
+                .exec(db)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        None => {
+            let m = AmPapJournal {
+                id: NotSet,
+                character_id: Set(character_id),
+                year: Set(year_month.year as i32),
+                month: Set(year_month.month as i32),
+                pap: Set(delta),
+            };
+            EPapJournal::insert(m)
+                .exec(db)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_ingest_changes() {
+    use db_wallet::{
+        Migrator, MigratorTrait,
+        entities::{
+            characters::ActiveModel as AmCharacters, characters::Entity as ECharacters,
+            tax_parameters::ActiveModel as AmTaxParameters,
+            tax_parameters::Entity as ETaxParameters,
+        },
+    };
+
+    let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+    Migrator::up(&db, None).await.unwrap();
+
+    // 用户 3 与 4 没有角色, 主角色名同为群昵称 twin
+    for (id, nickname) in [(1, "u1"), (2, "u2"), (3, "twin"), (4, "twin")] {
+        let m = AmUsers {
+            id: Set(id),
+            we_chat_id: Set(None),
+            we_chat_nick_name: Set(None),
+            we_chat_group_nickname: Set(Some(nickname.to_string())),
+            tax_note: Set(None),
+        };
+        EUsers::insert(m).exec(&db).await.unwrap();
+    }
+    let m = AmCharacters {
+        character_id: Set(1001),
+        alliance_id: Set(None),
+        corporation_id: Set(98000001),
+        birthday: Set(0),
+        name: Set("c1001".to_string()),
+        user_id: Set(Some(1)),
+        main: Set(true),
+        last_refreshed: Set(None),
+    };
+    ECharacters::insert(m).exec(&db).await.unwrap();
+    set_taxable(&db, 1, YearMonth::new(2025, 9), Some(true), Some(true))
+        .await
+        .unwrap();
+    for month in [9, 10] {
+        let m = AmTaxParameters {
+            id: NotSet,
+            year: Set(2025),
+            month: Set(month),
+            poll_tax: Set(5000000000),
+            pap_tax: Set(100000000),
+            pap_standard: Set(1000),
+        };
+        ETaxParameters::insert(m).exec(&db).await.unwrap();
+    }
+    add_pap(&db, 1001, YearMonth::new(2025, 9), 700)
+        .await
+        .unwrap();
+
+    // 9月: 人头税 5000万, PAP 7分, PAP税 300万
+    let start = YearMonth::new(2025, 9);
+    let end = YearMonth::new(2025, 10);
+    let utc = chrono::FixedOffset::east_opt(0).unwrap();
+    let isk = |v: i64| Some(Decimal::from(v));
+    let edited = EditedTaxList {
+        lang: Lang::Zh,
+        year_months: RangeYearMonth::new(start, end).collect(),
+        rows: vec![
+            EditedRow {
+                user_id: Some(1),
+                character_name: "c1001".to_string(),
+                months: vec![
+                    EditedMonth {
+                        pap_tax: isk(1000000),
+                        poll_tax: isk(0),
+                        paid_up_tax: isk(100),
+                    },
+                    EditedMonth {
+                        pap_tax: isk(0),
+                        ..Default::default()
+                    },
+                ],
+                note: Some("补登 PAP".to_string()),
+            },
+            // 旧报表没有用户ID列, 按主角色名匹配
+            EditedRow {
+                user_id: None,
+                character_name: "u2".to_string(),
+                months: vec![
+                    EditedMonth::default(),
+                    EditedMonth {
+                        pap_tax: isk(500000),
+                        poll_tax: isk(1),
+                        paid_up_tax: None,
+                    },
+                ],
+                note: Some(String::new()),
+            },
+            EditedRow {
+                user_id: None,
+                character_name: "nobody".to_string(),
+                months: vec![EditedMonth::default(), EditedMonth::default()],
+                note: None,
+            },
+            EditedRow {
+                user_id: None,
+                character_name: "twin".to_string(),
+                months: vec![EditedMonth::default(), EditedMonth::default()],
+                note: Some("ambiguous".to_string()),
+            },
+            EditedRow {
+                user_id: Some(4),
+                character_name: "twin".to_string(),
+                months: vec![EditedMonth::default(), EditedMonth::default()],
+                note: Some("twin 4".to_string()),
+            },
+            EditedRow {
+                user_id: Some(9),
+                character_name: "u1".to_string(),
+                months: vec![EditedMonth::default(), EditedMonth::default()],
+                note: Some("removed".to_string()),
+            },
+        ],
+    };
+
+    let ledger = TaxLedger::select_from_db(&db, start, end, utc)
+        .await
+        .unwrap();
+    let changes = edited.changes(&ledger).unwrap();
+    let updates: Vec<_> = changes.iter().map(|c| c.updates.clone()).collect();
+    assert_eq!(
+        updates,
+        vec![
+            // PAP税 100万, 即欠 1分, PAP 由 7分 修正为 9分
+            Ok(vec![Update::Pap {
+                character_id: 1001,
+                year_month: start,
+                delta: 200
+            }]),
+            Ok(vec![Update::PollTaxable {
+                user_id: 1,
+                year_month: start,
+                taxable: false
+            }]),
+            Err("paid tax is derived from the wallet journal".to_string()),
+            Ok(vec![Update::Note {
+                user_id: 1,
+                note: Some("补登 PAP".to_string())
+            }]),
+            // u2 没有主角色, 无法记入 PAP
+            Err("user has no main character".to_string()),
+            Err("poll tax can only be 0 or 50,000,000 isk".to_string()),
+            Err("user not found".to_string()),
+            Err("character name is shared by several users".to_string()),
+            Ok(vec![Update::Note {
+                user_id: 4,
+                note: Some("twin 4".to_string())
+            }]),
+            Err("user not found".to_string()),
+        ]
+    );
+    assert_eq!(
+        changes[0].to_text(),
+        "c1001 2025年9月 PAP税额: \"3,000,000 isk\" -> \"1,000,000 isk\" => pap of character 1001 in 2025-09 +2.00"
+    );
+
+    assert_eq!(apply_changes(&db, &changes).await.unwrap(), 4);
+    let ledger = TaxLedger::select_from_db(&db, start, end, utc)
+        .await
+        .unwrap();
+    assert_eq!(
+        ledger.user_tax(1, start).unwrap(),
+        (Decimal::ZERO, Decimal::from(1000000))
+    );
+    assert_eq!(ledger.note(1), Some("补登 PAP"));
+    assert_eq!(ledger.note(4), Some("twin 4"));
+    assert_eq!(ledger.note(3), None);
+
+    // 写回后再次比较没有修改
+    let changes = edited.changes(&ledger).unwrap();
+    assert_eq!(changes.len(), 6);
+    assert!(changes.iter().all(|c| c.updates.is_err()));
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_ingest_user_ids() {
+    use crate::{db_op::test_tax_database, report::SheetTaxList, template::ReportTemplate};
+    use umya_spreadsheet::new_file_empty_worksheet;
+
+    let db = test_tax_database().await;
+    let utc = chrono::FixedOffset::east_opt(0).unwrap();
+    let (start, end) = (YearMonth::new(2025, 9), YearMonth::new(2025, 10));
+    let tax_list = SheetTaxList::select_from_db(&db, start, end, utc)
+        .await
+        .unwrap();
+
+    let mut book = new_file_empty_worksheet();
+    let w = book.new_sheet("清单").unwrap();
+    tax_list.insert_worksheet(w, "输入", Lang::Zh, &ReportTemplate::default());
+    // 与用户 2 的主角色同名, 仍按隐藏的用户ID匹配
+    let name = w.get_value("A3");
+    w.get_cell_mut("A4").set_value_string(name);
+
+    let edited = EditedTaxList::from_worksheet(w, start, end).unwrap();
+    let users_ids: Vec<Option<i32>> = edited.rows.iter().map(|r| r.user_id).collect();
+    assert_eq!(users_ids, vec![Some(1), Some(2)]);
+    let ledger = TaxLedger::select_from_db(&db, start, end, utc)
+        .await
+        .unwrap();
+    assert!(edited.changes(&ledger).unwrap().is_empty());
+
+    w.get_cell_mut("J4").set_value_string("x");
+    assert!(EditedTaxList::from_worksheet(w, start, end).is_err());
+}
@@ -0,0 +1,300 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use strum::EnumIter;
+
+use crate::db_op::YearMonth;
+use db_wallet::JournalRefType;
+
+// 报表输出语言
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+// 报表中的固定文本, 包括工作表名, 列标题等
+#[derive(EnumIter, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Text {
+    ReportTitle,
+    Filter,
+    Total,
+    SheetJournalGaps,
+    SheetWalletJournal,
+    SheetTaxList,
+    SheetSummary,
+    SheetCharts,
+    SheetTaxInputs,
+    SheetVerify,
+    DateTime,
+    RefType,
+    Amount,
+    Balance,
+    Party,
+    Description,
+    IssueKind,
+    WindowStart,
+    WindowEnd,
+    PreviousId,
+    JournalId,
+    ExpectedBalance,
+    ActualBalance,
+    MissingAmount,
+    ChainBreak,
+    DuplicateId,
+    UserId,
+    MainCharacter,
+    Portrait,
+    UnpaidTax,
+    PapTax,
+    PollTax,
+    PaidUpTax,
+    Note,
+    AssessedTax,
+    Category,
+    Income,
+    Expense,
+    Net,
+    Change,
+    Month,
+    MonthlyIncome,
+    AssessedAndPaidTax,
+    SheetStatement,
+    TaxStatement,
+    Item,
+    Character,
+    Pap,
+    ArrearsBalance,
+    SheetAlerts,
+    AlertKind,
+    Counterparty,
+    Detail,
+    LargeWithdrawal,
+    UnknownCounterparty,
+    UnusualHour,
+    BalanceDrop,
+    AboveThreshold,
+    UnlinkedRecipient,
+    LocalTime,
+    BalanceWithin24Hours,
+}
+
+impl Lang {
+    // html 等使用的语言代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lang::Zh => "zh",
+            Lang::En => "en",
+        }
+    }
+
+    pub fn ref_type(&self, ref_type: JournalRefType) -> &'static str {
+        match self {
+            Lang::Zh => ref_type.zh_str(),
+            Lang::En => ref_type.en_str(),
+        }
+    }
+
+    pub fn year_month(&self, ym: YearMonth) -> String {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        match self {
+            Lang::Zh => ym.to_string_zh(),
+            Lang::En => format!("{} {}", MONTHS[ym.month as usize - 1], ym.year),
+        }
+    }
+
+    pub fn text(&self, text: Text) -> &'static str {
+        match self {
+            Lang::Zh => text_zh(text),
+            Lang::En => text_en(text),
+        }
+    }
+}
+
+fn text_zh(text: Text) -> &'static str {
+    match text {
+        Text::ReportTitle => "报表",
+        Text::Filter => "筛选",
+        Text::Total => "合计",
+        Text::SheetJournalGaps => "警告-流水不连续",
+        Text::SheetWalletJournal => "主账户流水",
+        Text::SheetTaxList => "税收清单",
+        Text::SheetSummary => "收支汇总",
+        Text::SheetCharts => "图表",
+        Text::SheetTaxInputs => "税收原始数据",
+        Text::SheetVerify => "数据校验",
+        Text::DateTime => "日期时间",
+        Text::RefType => "类型",
+        Text::Amount => "收支金额",
+        Text::Balance => "账户余额",
+        Text::Party => "相关角色",
+        Text::Description => "备注",
+        Text::IssueKind => "问题类型",
+        Text::WindowStart => "疑似缺失起点",
+        Text::WindowEnd => "疑似缺失终点",
+        Text::PreviousId => "前一流水ID",
+        Text::JournalId => "流水ID",
+        Text::ExpectedBalance => "期望余额",
+        Text::ActualBalance => "实际余额",
+        Text::MissingAmount => "缺失金额",
+        Text::ChainBreak => "余额不连续",
+        Text::DuplicateId => "重复ID",
+        Text::UserId => "用户ID",
+        Text::MainCharacter => "主角色名",
+        Text::Portrait => "头像",
+        Text::UnpaidTax => "欠税额",
+        Text::PapTax => "PAP税额",
+        Text::PollTax => "人头税额",
+        Text::PaidUpTax => "实缴税额",
+        Text::Note => "备注",
+        Text::AssessedTax => "应缴税额",
+        Text::Category => "分类",
+        Text::Income => "收入",
+        Text::Expense => "支出",
+        Text::Net => "净额",
+        Text::Change => "环比",
+        Text::Month => "月份",
+        Text::MonthlyIncome => "月度收入",
+        Text::AssessedAndPaidTax => "应缴与实缴税额",
+        Text::SheetStatement => "对账单",
+        Text::TaxStatement => "税收对账单",
+        Text::Item => "项目",
+        Text::Character => "角色",
+        Text::Pap => "PAP分",
+        Text::ArrearsBalance => "欠税余额",
+        Text::SheetAlerts => "异常告警",
+        Text::AlertKind => "告警类型",
+        Text::Counterparty => "相关方",
+        Text::Detail => "说明",
+        Text::LargeWithdrawal => "大额支出",
+        Text::UnknownCounterparty => "陌生收款方",
+        Text::UnusualHour => "异常时段",
+        Text::BalanceDrop => "余额骤降",
+        Text::AboveThreshold => "超过阈值",
+        Text::UnlinkedRecipient => "收款方不是已关联用户的角色",
+        Text::LocalTime => "发生时刻",
+        Text::BalanceWithin24Hours => "24小时内余额",
+    }
+}
+
+fn text_en(text: Text) -> &'static str {
+    match text {
+        Text::ReportTitle => "Report",
+        Text::Filter => "Filter",
+        Text::Total => "Total",
+        Text::SheetJournalGaps => "Warning - Journal Gaps",
+        Text::SheetWalletJournal => "Wallet Journal",
+        Text::SheetTaxList => "Tax List",
+        Text::SheetSummary => "Income and Expense",
+        Text::SheetCharts => "Charts",
+        Text::SheetTaxInputs => "Tax Inputs",
+        Text::SheetVerify => "Verification",
+        Text::DateTime => "Date Time",
+        Text::RefType => "Type",
+        Text::Amount => "Amount",
+        Text::Balance => "Balance",
+        Text::Party => "Party",
+        Text::Description => "Description",
+        Text::IssueKind => "Issue",
+        Text::WindowStart => "Gap Start",
+        Text::WindowEnd => "Gap End",
+        Text::PreviousId => "Previous ID",
+        Text::JournalId => "Journal ID",
+        Text::ExpectedBalance => "Expected Balance",
+        Text::ActualBalance => "Actual Balance",
+        Text::MissingAmount => "Missing Amount",
+        Text::ChainBreak => "Balance Chain Break",
+        Text::DuplicateId => "Duplicate ID",
+        Text::UserId => "User ID",
+        Text::MainCharacter => "Main Character",
+        Text::Portrait => "Portrait",
+        Text::UnpaidTax => "Unpaid Tax",
+        Text::PapTax => "PAP Tax",
+        Text::PollTax => "Poll Tax",
+        Text::PaidUpTax => "Paid Tax",
+        Text::Note => "Note",
+        Text::AssessedTax => "Assessed Tax",
+        Text::Category => "Category",
+        Text::Income => "Income",
+        Text::Expense => "Expense",
+        Text::Net => "Net",
+        Text::Change => "Change",
+        Text::Month => "Month",
+        Text::MonthlyIncome => "Monthly Income",
+        Text::AssessedAndPaidTax => "Assessed and Paid Tax",
+        Text::SheetStatement => "Statement",
+        Text::TaxStatement => "Tax Statement",
+        Text::Item => "Item",
+        Text::Character => "Character",
+        Text::Pap => "PAP",
+        Text::ArrearsBalance => "Arrears Balance",
+        Text::SheetAlerts => "Anomaly Alerts",
+        Text::AlertKind => "Alert",
+        Text::Counterparty => "Counterparty",
+        Text::Detail => "Detail",
+        Text::LargeWithdrawal => "Large Withdrawal",
+        Text::UnknownCounterparty => "Unknown Recipient",
+        Text::UnusualHour => "Unusual Hour",
+        Text::BalanceDrop => "Balance Drop",
+        Text::AboveThreshold => "Above threshold",
+        Text::UnlinkedRecipient => "Recipient is not a character of a linked user",
+        Text::LocalTime => "Local time",
+        Text::BalanceWithin24Hours => "Balance within 24 hours",
+    }
+}
+
+#[test]
+fn test_translations() {
+    use std::collections::BTreeSet;
+    use strum::IntoEnumIterator;
+
+    for lang in Lang::value_variants() {
+        // 每种流水类型都有翻译, 不再回退到 snake_case 名称, 且互不重复
+        let mut names = BTreeSet::new();
+        for ref_type in JournalRefType::iter() {
+            let name = lang.ref_type(ref_type);
+            assert!(name.is_empty() == false);
+            assert_ne!(name, ref_type.as_ref());
+            assert!(names.insert(name), "{:?} duplicated: {}", lang, name);
+        }
+
+        for text in Text::iter() {
+            assert!(lang.text(text).is_empty() == false);
+        }
+    }
+
+    // 中文翻译不能是英文
+    for ref_type in JournalRefType::iter() {
+        assert_ne!(Lang::Zh.ref_type(ref_type), Lang::En.ref_type(ref_type));
+    }
+    for text in Text::iter() {
+        assert_ne!(Lang::Zh.text(text), Lang::En.text(text));
+    }
+
+    // 工作表名互不重复, 且不超过 Excel 的 31 字符限制
+    for lang in Lang::value_variants() {
+        let sheets = [
+            Text::SheetJournalGaps,
+            Text::SheetWalletJournal,
+            Text::SheetTaxList,
+            Text::SheetSummary,
+            Text::SheetCharts,
+            Text::SheetTaxInputs,
+            Text::SheetVerify,
+            Text::SheetStatement,
+            Text::SheetAlerts,
+        ];
+        let names: BTreeSet<&str> = sheets.iter().map(|t| lang.text(*t)).collect();
+        assert_eq!(names.len(), sheets.len());
+        assert!(names.iter().all(|n| n.chars().count() <= 31));
+    }
+    assert_eq!(Lang::En.text(Text::SheetStatement), "Statement");
+    assert_eq!(Lang::En.text(Text::SheetAlerts), "Anomaly Alerts");
+    assert_eq!(Lang::Zh.text(Text::UnusualHour), "异常时段");
+
+    let ym = YearMonth::new(2025, 8);
+    assert_eq!(Lang::Zh.year_month(ym), "2025年8月");
+    assert_eq!(Lang::En.year_month(ym), "Aug 2025");
+}
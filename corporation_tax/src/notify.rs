@@ -0,0 +1,462 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, header::CONTENT_TYPE};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, time::sleep};
+
+use crate::{db_op::YearMonth, esi::ResCorporationWalletJournalItem, report::UserArrears};
+use db_wallet::JournalRefType;
+
+// webhook 请求体签名所在的请求头, 值为 "sha256=<hex>"
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+#[derive(Serialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    // 新入库的大额支出
+    LargeWithdrawal {
+        journal_id: i64,
+        date: DateTime<Utc>,
+        ref_type: JournalRefType,
+        amount: Decimal,
+        party_id: Option<i64>,
+    },
+    // 新入库的缴税记录
+    PaymentReceived {
+        journal_id: i64,
+        date: DateTime<Utc>,
+        character_id: Option<i64>,
+        amount: Decimal,
+    },
+    // 成员在月份结束时开始欠税, 此前已欠税的成员不再重复通知
+    MemberOverdue {
+        user_id: i32,
+        character_name: String,
+        year_month: String, // 例如 2025-10
+        amount: Decimal,
+    },
+}
+
+// 同步过程中收集到的事件, 同步结束后统一分发
+pub struct EventQueue {
+    large_withdrawal: Decimal, // 大额支出阈值, 正数
+    events: Vec<Event>,
+}
+
+impl EventQueue {
+    pub fn new(large_withdrawal: Decimal) -> Self {
+        Self {
+            large_withdrawal: large_withdrawal.abs(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    // 钱包流水入库时调用
+    pub fn on_journal_inserted(&mut self, item: &ResCorporationWalletJournalItem) {
+        let amount = match item.amount {
+            None => return,
+            Some(a) => a,
+        };
+
+        match item.ref_type {
+            JournalRefType::CorporationAccountWithdrawal
+            | JournalRefType::CorporationDividendPayment
+                if amount.is_sign_negative() && amount.abs() >= self.large_withdrawal =>
+            {
+                self.events.push(Event::LargeWithdrawal {
+                    journal_id: item.id,
+                    date: item.date,
+                    ref_type: item.ref_type,
+                    amount,
+                    party_id: item.second_party_id,
+                });
+            }
+            JournalRefType::PlayerDonation if amount > Decimal::ZERO => {
+                self.events.push(Event::PaymentReceived {
+                    journal_id: item.id,
+                    date: item.date,
+                    character_id: item.first_party_id,
+                    amount,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    // 月份结束后计算税收时调用, arrears 与 previous 分别为截至本月与上月的累计欠税
+    // 只通知上月末没有欠税, 本月末开始欠税的成员
+    pub fn on_month_closed(
+        &mut self,
+        year_month: YearMonth,
+        arrears: &[UserArrears],
+        previous: &[UserArrears],
+    ) {
+        for a in arrears {
+            if previous.iter().any(|p| p.user_id == a.user_id) {
+                continue;
+            }
+            self.events.push(Event::MemberOverdue {
+                user_id: a.user_id,
+                character_name: a.character_name.clone(),
+                year_month: year_month.to_key(),
+                amount: a.amount,
+            });
+        }
+    }
+}
+
+// 以 JSON POST 发送事件, 失败时按指数退避重试
+pub struct WebhookSink {
+    client: Client,
+    url: String,
+    secret: Option<String>,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl WebhookSink {
+    pub fn new(url: String, secret: Option<String>, retries: u32) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+            secret,
+            retries,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    async fn send(&self, event: &Event) -> Result<(), String> {
+        let body = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .client
+                .post(self.url.as_str())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            if let Some(secret) = &self.secret {
+                request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+            }
+
+            let error = match request.send().await {
+                Ok(res) if res.status().is_success() => return Ok(()),
+                Ok(res) => format!("webhook {}: status {}", self.url, res.status()),
+                Err(e) => format!("webhook {}: {}", self.url, e),
+            };
+
+            if attempt >= self.retries {
+                return Err(error);
+            }
+            attempt += 1;
+            sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
+
+// 以 JSON Lines 追加写入本地文件
+pub struct FileSink {
+    path: String,
+}
+
+impl FileSink {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+
+    async fn send(&self, event: &Event) -> Result<(), String> {
+        let mut line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.as_str())
+            .await
+            .map_err(|e| e.to_string())?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        file.flush().await.map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+pub enum Sink {
+    Webhook(WebhookSink),
+    File(FileSink),
+}
+
+impl Sink {
+    async fn send(&self, event: &Event) -> Result<(), String> {
+        match self {
+            Sink::Webhook(s) => s.send(event).await,
+            Sink::File(s) => s.send(event).await,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Dispatcher {
+    sinks: Vec<Sink>,
+}
+
+impl Dispatcher {
+    pub fn add_sink(&mut self, sink: Sink) {
+        self.sinks.push(sink);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    // 将事件依次发送到所有出口, 某个出口失败不影响其他出口
+    pub async fn dispatch(&self, events: &[Event]) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for event in events {
+            for sink in &self.sinks {
+                if let Err(e) = sink.send(event).await {
+                    errors.push(e);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+async fn http_stand_in(
+    statuses: Vec<u16>,
+) -> (String, tokio::task::JoinHandle<Vec<(String, String)>>) {
+    let responses = statuses.into_iter().map(|s| (s, String::new())).collect();
+    http_stand_in_with_headers(responses).await
+}
+
+// 响应中附带额外的响应头, 每个响应头以 "\r\n" 结尾
+#[cfg(test)]
+pub(crate) async fn http_stand_in_with_headers(
+    responses: Vec<(u16, String)>,
+) -> (String, tokio::task::JoinHandle<Vec<(String, String)>>) {
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    // 依次以给定状态码响应请求, 返回收到的 (请求头, 请求体)
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for (status, headers) in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let (head, body) = loop {
+                let mut chunk = [0u8; 4096];
+                let n = stream.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                let text = String::from_utf8_lossy(&buf).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|v| v.parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break (head.to_string(), body.to_string());
+                    }
+                }
+            };
+            let res = format!(
+                "HTTP/1.1 {} X\r\n{}content-length: 0\r\nconnection: close\r\n\r\n",
+                status, headers
+            );
+            stream.write_all(res.as_bytes()).await.unwrap();
+            requests.push((head, body));
+        }
+        requests
+    });
+
+    (url, handle)
+}
+
+#[cfg(test)]
+fn test_event() -> Event {
+    Event::PaymentReceived {
+        journal_id: 1,
+        date: DateTime::from_timestamp_secs(1760000000).unwrap(),
+        character_id: Some(2),
+        amount: Decimal::new(5000000000, 2),
+    }
+}
+
+#[tokio::test]
+async fn test_webhook_sink_signature() {
+    let (url, handle) = http_stand_in(vec![200]).await;
+    let sink = WebhookSink::new(url, Some("secret".to_string()), 0);
+    sink.send(&test_event()).await.unwrap();
+
+    let requests = handle.await.unwrap();
+    let (head, body) = &requests[0];
+    let header = format!("{}: {}", SIGNATURE_HEADER, sign("secret", body.as_bytes()));
+    assert!(head.to_lowercase().contains(&header.to_lowercase()));
+    assert!(body.contains(r#""event":"payment_received""#));
+}
+
+#[tokio::test]
+async fn test_webhook_sink_retry() {
+    let (url, handle) = http_stand_in(vec![500, 502, 200]).await;
+    let mut sink = WebhookSink::new(url.clone(), None, 2);
+    sink.retry_delay = Duration::from_millis(1);
+    sink.send(&test_event()).await.unwrap();
+    assert_eq!(handle.await.unwrap().len(), 3);
+
+    let (url, _handle) = http_stand_in(vec![500, 500]).await;
+    let mut sink = WebhookSink::new(url, None, 1);
+    sink.retry_delay = Duration::from_millis(1);
+    assert!(sink.send(&test_event()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_file_sink() {
+    let path = std::env::temp_dir().join(format!("events_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut dispatcher = Dispatcher::default();
+    dispatcher.add_sink(Sink::File(FileSink::new(
+        path.to_string_lossy().to_string(),
+    )));
+    dispatcher
+        .dispatch(&[test_event(), test_event()])
+        .await
+        .unwrap();
+
+    let s = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(s.lines().count(), 2);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_event_queue() {
+    let item = |id: i64, ref_type: JournalRefType, amount: i64| ResCorporationWalletJournalItem {
+        id,
+        date: DateTime::from_timestamp_secs(1760000000 + id).unwrap(),
+        ref_type,
+        description: String::new(),
+        amount: Some(Decimal::from(amount)),
+        balance: None,
+        context_id: None,
+        context_id_type: None,
+        reason: None,
+        first_party_id: Some(1001),
+        second_party_id: Some(2001),
+        tax: None,
+        tax_receiver_id: None,
+    };
+
+    // 阈值取绝对值, 支出等于阈值时也视为大额
+    let mut events = EventQueue::new(Decimal::from(-1000));
+    for i in [
+        item(1, JournalRefType::PlayerDonation, 500),
+        item(2, JournalRefType::PlayerDonation, -500),
+        item(3, JournalRefType::CorporationAccountWithdrawal, -1000),
+        item(4, JournalRefType::CorporationAccountWithdrawal, -999),
+        item(5, JournalRefType::CorporationDividendPayment, -2000),
+        item(6, JournalRefType::CorporationAccountWithdrawal, 5000),
+        item(7, JournalRefType::AgentMissionReward, -5000),
+    ] {
+        events.on_journal_inserted(&i);
+    }
+    let mut no_amount = item(8, JournalRefType::PlayerDonation, 0);
+    no_amount.amount = None;
+    events.on_journal_inserted(&no_amount);
+
+    let kinds: Vec<(&str, i64)> = events
+        .events()
+        .iter()
+        .map(|e| match e {
+            Event::PaymentReceived { journal_id, .. } => ("payment", *journal_id),
+            Event::LargeWithdrawal { journal_id, .. } => ("withdrawal", *journal_id),
+            Event::MemberOverdue { user_id, .. } => ("overdue", *user_id as i64),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![("payment", 1), ("withdrawal", 3), ("withdrawal", 5)]
+    );
+}
+
+// 逾期按累计欠税计算, 只看当月会漏掉更早月份的欠税
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_event_queue_overdue() {
+    use crate::{db_op::test_tax_database, report::SheetTaxList};
+
+    let db = test_tax_database().await;
+    let utc = chrono::FixedOffset::east_opt(0).unwrap();
+    let october = YearMonth::new(2025, 10);
+
+    let single = SheetTaxList::select_from_db(&db, october, october, utc)
+        .await
+        .unwrap();
+    let users: Vec<i32> = single.arrears().iter().map(|a| a.user_id).collect();
+    assert_eq!(users, vec![2]);
+
+    // 用户1 9月欠 5300万 已缴 200, 用户2 10月欠 5000万 已缴 100
+    let september = YearMonth::new(2025, 9);
+    let cumulative = SheetTaxList::select_cumulative(&db, october, utc)
+        .await
+        .unwrap();
+    let previous = SheetTaxList::select_cumulative(&db, september, utc)
+        .await
+        .unwrap();
+    let mut events = EventQueue::new(Decimal::ZERO);
+    events.on_month_closed(september, &previous.arrears(), &[]);
+    events.on_month_closed(october, &cumulative.arrears(), &previous.arrears());
+    // 第一次同步时没有上月数据, 所有欠税成员均通知
+    let mut first_sync = EventQueue::new(Decimal::ZERO);
+    first_sync.on_month_closed(october, &cumulative.arrears(), &[]);
+    assert_eq!(first_sync.events().len(), 2);
+    let overdue: Vec<(i32, String, Decimal)> = events
+        .events()
+        .iter()
+        .filter_map(|e| match e {
+            Event::MemberOverdue {
+                user_id,
+                year_month,
+                amount,
+                ..
+            } => Some((*user_id, year_month.clone(), *amount)),
+            _ => None,
+        })
+        .collect();
+    // 用户1 自9月起欠税, 10月不再重复通知
+    assert_eq!(
+        overdue,
+        vec![
+            (1, "2025-09".to_string(), Decimal::from(52999800)),
+            (2, "2025-10".to_string(), Decimal::from(49999900)),
+        ]
+    );
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::db_op::{YearMonth, local_midnight};
+
+// 预设的报表期间, 均按报表时区的当前日期计算
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum PeriodPreset {
+    // 本月1日至今天
+    ThisMonth,
+    // 上月整月
+    LastMonth,
+    // 本月之前的3个整月
+    #[value(name = "last-3-months")]
+    #[serde(rename = "last-3-months")]
+    Last3Months,
+    // 今年1月1日至今天
+    Ytd,
+}
+
+// 报表期间, 起止日期均包含, 日期的边界按 offset 时区划分
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReportPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub offset: FixedOffset,
+}
+
+impl ReportPeriod {
+    pub fn new(start: NaiveDate, end: NaiveDate, offset: FixedOffset) -> Result<Self, String> {
+        if start > end {
+            return Err(format!("start {} is after end {}", start, end));
+        }
+        Ok(ReportPeriod { start, end, offset })
+    }
+
+    pub fn from_preset(preset: PeriodPreset, today: NaiveDate, offset: FixedOffset) -> Self {
+        let this_month = YearMonth::from_date(&today);
+        let (start, end) = match preset {
+            PeriodPreset::ThisMonth => (this_month.first_day(), today),
+            PeriodPreset::LastMonth => {
+                let last_month = this_month.add_month(-1);
+                (last_month.first_day(), last_month.last_day())
+            }
+            PeriodPreset::Last3Months => (
+                this_month.add_month(-3).first_day(),
+                this_month.add_month(-1).last_day(),
+            ),
+            PeriodPreset::Ytd => (YearMonth::new(this_month.year, 1).first_day(), today),
+        };
+        ReportPeriod { start, end, offset }
+    }
+
+    // 起止均可为 YYYY-MM 或 YYYY-MM-DD, 月份作为起点时取1日, 作为终点时取月末
+    pub fn from_range(start: &str, end: &str, offset: FixedOffset) -> Result<Self, String> {
+        let (start, _) = parse_bound(start)?;
+        let (_, end) = parse_bound(end)?;
+        ReportPeriod::new(start, end, offset)
+    }
+
+    // 报表时区的今天
+    pub fn today(offset: FixedOffset) -> NaiveDate {
+        Utc::now().with_timezone(&offset).date_naive()
+    }
+
+    pub fn start_month(&self) -> YearMonth {
+        YearMonth::from_date(&self.start)
+    }
+
+    pub fn end_month(&self) -> YearMonth {
+        YearMonth::from_date(&self.end)
+    }
+
+    // 期间的起点, 包含
+    pub fn lower(&self) -> DateTime<Utc> {
+        local_midnight(self.start, self.offset)
+    }
+
+    // 期间的终点, 即结束日次日的零点, 不包含
+    pub fn upper(&self) -> DateTime<Utc> {
+        local_midnight(self.end.succ_opt().unwrap(), self.offset)
+    }
+}
+
+// 日期为当天, 月份为该月的1日与月末
+fn parse_bound(s: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok((date, date));
+    }
+    let ym = YearMonth::from_str(s)?;
+    Ok((ym.first_day(), ym.last_day()))
+}
+
+#[test]
+fn test_report_period() {
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let today = date(2026, 2, 10);
+    let period = |preset| {
+        let p = ReportPeriod::from_preset(preset, today, utc8);
+        (p.start, p.end)
+    };
+
+    assert_eq!(
+        period(PeriodPreset::ThisMonth),
+        (date(2026, 2, 1), date(2026, 2, 10))
+    );
+    assert_eq!(
+        period(PeriodPreset::LastMonth),
+        (date(2026, 1, 1), date(2026, 1, 31))
+    );
+    assert_eq!(
+        period(PeriodPreset::Last3Months),
+        (date(2025, 11, 1), date(2026, 1, 31))
+    );
+    assert_eq!(
+        period(PeriodPreset::Ytd),
+        (date(2026, 1, 1), date(2026, 2, 10))
+    );
+    assert_eq!(
+        PeriodPreset::from_str("last-3-months", false),
+        Ok(PeriodPreset::Last3Months)
+    );
+
+    // UTC+8 的 2025年8月 为 UTC 7月31日 16:00 至 8月31日 16:00
+    let p = ReportPeriod::from_range("2025-08", "2025-08", utc8).unwrap();
+    assert_eq!((p.start, p.end), (date(2025, 8, 1), date(2025, 8, 31)));
+    assert_eq!(p.lower().to_rfc3339(), "2025-07-31T16:00:00+00:00");
+    assert_eq!(p.upper().to_rfc3339(), "2025-08-31T16:00:00+00:00");
+    assert_eq!(p.lower(), YearMonth::new(2025, 8).lower(utc8));
+    assert_eq!(p.upper(), YearMonth::new(2025, 8).upper(utc8));
+
+    let p = ReportPeriod::from_range("2025-08-15", "2025-09", utc8).unwrap();
+    assert_eq!((p.start, p.end), (date(2025, 8, 15), date(2025, 9, 30)));
+    assert_eq!(p.start_month(), YearMonth::new(2025, 8));
+    assert_eq!(p.end_month(), YearMonth::new(2025, 9));
+    let p = ReportPeriod::from_range("2024-02", "2024-02-29", utc8).unwrap();
+    assert_eq!(p.upper().to_rfc3339(), "2024-02-29T16:00:00+00:00");
+
+    assert!(ReportPeriod::from_range("2025-09", "2025-08-31", utc8).is_err());
+    assert!(ReportPeriod::from_range("2025-13", "2025-12", utc8).is_err());
+
+    // 月末 UTC 16:00 之后的流水属于 UTC+8 的下个月
+    let t = DateTime::parse_from_rfc3339("2025-08-31T16:30:00Z")
+        .unwrap()
+        .to_utc();
+    assert_eq!(YearMonth::from_datetime(&t, utc8), YearMonth::new(2025, 9));
+    let utc = FixedOffset::east_opt(0).unwrap();
+    assert_eq!(YearMonth::from_datetime(&t, utc), YearMonth::new(2025, 8));
+}
@@ -0,0 +1,152 @@
+use sea_orm::ConnectionTrait;
+
+use crate::{
+    db_op::get_user_we_chat_nickname, locale::Lang, report::UserArrears, statement::format_isk_text,
+};
+
+// 默认催缴消息模板
+// 可用占位符: {nickname} 微信群昵称, {character} 主角色名, {amount} 欠税金额,
+// {months} 欠缴月份, {month_count} 欠缴月份数
+const DEFAULT_TEMPLATE_ZH: &str =
+    "@{nickname} 你的角色 {character} 当前欠税 {amount}, 欠缴月份: {months}, 请尽快补缴。";
+const DEFAULT_TEMPLATE_EN: &str = "@{nickname} your character {character} owes {amount} in tax for {months}, please pay as soon as possible.";
+
+pub fn default_template(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Zh => DEFAULT_TEMPLATE_ZH,
+        Lang::En => DEFAULT_TEMPLATE_EN,
+    }
+}
+
+pub struct ReminderConfig {
+    pub template_path: Option<String>, // 消息模板文件, 为 None 时使用 lang 对应的默认模板
+    pub max_length: usize,             // 每组消息的最大字符数
+    pub lang: Lang,                    // 默认模板的语言及月份名称的格式
+}
+
+// 分组之间的分隔行
+const GROUP_SEPARATOR: &str = "----------";
+
+// 单个欠税用户的催缴消息
+pub struct Reminder {
+    nickname: String,
+    arrears: UserArrears,
+}
+
+impl Reminder {
+    // 月份名称按 lang 格式化
+    pub fn render(&self, template: &str, lang: Lang) -> String {
+        let months: Vec<String> = self
+            .arrears
+            .year_months
+            .iter()
+            .map(|ym| lang.year_month(*ym))
+            .collect();
+        let separator = match lang {
+            Lang::Zh => "、",
+            Lang::En => ", ",
+        };
+
+        template
+            .replace("{nickname}", self.nickname.as_str())
+            .replace("{character}", self.arrears.character_name.as_str())
+            .replace("{amount}", format_isk_text(self.arrears.amount).as_str())
+            .replace("{months}", months.join(separator).as_str())
+            .replace("{month_count}", months.len().to_string().as_str())
+    }
+
+    // 为所有欠税用户生成催缴消息, 没有微信群昵称的用户以主角色名代替
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        arrears: Vec<UserArrears>,
+    ) -> Result<Vec<Reminder>, String> {
+        let mut reminders = Vec::with_capacity(arrears.len());
+        for a in arrears {
+            let nickname = get_user_we_chat_nickname(db, a.user_id)
+                .await?
+                .unwrap_or_else(|| a.character_name.clone());
+            reminders.push(Reminder {
+                nickname,
+                arrears: a,
+            });
+        }
+        Ok(reminders)
+    }
+}
+
+// 将消息按行合并为若干组, 每组字符数不超过 max_length, 便于分条发送
+// 单条消息超过 max_length 时独占一组
+pub fn group_messages(messages: Vec<String>, max_length: usize) -> Vec<String> {
+    let mut groups = Vec::new();
+    let mut current = String::new();
+    let mut current_length = 0;
+
+    for message in messages {
+        let length = message.chars().count();
+        if current.is_empty() == false && current_length + 1 + length > max_length {
+            groups.push(std::mem::take(&mut current));
+            current_length = 0;
+        }
+        if current.is_empty() == false {
+            current.push('\n');
+            current_length += 1;
+        }
+        current.push_str(message.as_str());
+        current_length += length;
+    }
+
+    if current.is_empty() == false {
+        groups.push(current);
+    }
+
+    groups
+}
+
+// 将分组后的消息拼接为最终输出文本
+pub fn join_groups(groups: &[String]) -> String {
+    groups.join(format!("\n{}\n", GROUP_SEPARATOR).as_str())
+}
+
+#[test]
+fn test_render() {
+    use crate::db_op::YearMonth;
+    use rust_decimal::Decimal;
+
+    let reminder = Reminder {
+        nickname: "n1".to_string(),
+        arrears: UserArrears {
+            user_id: 1,
+            character_name: "c1001".to_string(),
+            amount: Decimal::from(1500000),
+            year_months: vec![YearMonth::new(2025, 9), YearMonth::new(2025, 10)],
+        },
+    };
+    assert_eq!(
+        reminder.render(default_template(Lang::Zh), Lang::Zh),
+        "@n1 你的角色 c1001 当前欠税 1,500,000 isk, 欠缴月份: 2025年9月、2025年10月, 请尽快补缴。"
+    );
+    assert_eq!(
+        reminder.render(default_template(Lang::En), Lang::En),
+        "@n1 your character c1001 owes 1,500,000 isk in tax for Sep 2025, Oct 2025, please pay as soon as possible."
+    );
+    assert_eq!(
+        reminder.render("{character} {month_count}", Lang::En),
+        "c1001 2"
+    );
+}
+
+#[test]
+fn test_group_messages() {
+    let messages = vec!["aaaa".to_string(), "bbbb".to_string(), "cccc".to_string()];
+    let groups = group_messages(messages.clone(), 9);
+    assert_eq!(groups, vec!["aaaa\nbbbb", "cccc"]);
+
+    let groups = group_messages(messages.clone(), 2);
+    assert_eq!(groups, vec!["aaaa", "bbbb", "cccc"]);
+
+    let groups = group_messages(messages, 100);
+    assert_eq!(groups, vec!["aaaa\nbbbb\ncccc"]);
+
+    let groups = group_messages(vec!["欠税".to_string(), "催缴".to_string()], 5);
+    assert_eq!(groups, vec!["欠税\n催缴"]);
+}
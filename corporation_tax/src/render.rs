@@ -0,0 +1,337 @@
+use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use umya_spreadsheet::{new_file_empty_worksheet, writer};
+
+use crate::{
+    charts::SheetCharts,
+    html::HtmlRenderer,
+    locale::{Lang, Text},
+    report::Report,
+    statement::format_isk_text,
+};
+use db_wallet::JournalRefType;
+
+// 报表中的单元格值, 与输出格式无关
+#[derive(Clone)]
+pub enum Value {
+    Empty,
+    Text(String),
+    Integer(i64),
+    Isk(Decimal),
+    DateTime(DateTime<FixedOffset>), // 带时区, csv 与 json 中输出时区偏移
+    RefType(JournalRefType),
+}
+
+impl Value {
+    // 机器可读的文本, 用于 csv
+    pub(crate) fn to_plain(&self) -> String {
+        match self {
+            Value::Empty => String::new(),
+            Value::Text(s) => s.clone(),
+            Value::Integer(v) => v.to_string(),
+            Value::Isk(d) => d.normalize().to_string(),
+            Value::DateTime(t) => t.to_rfc3339(),
+            Value::RefType(r) => r.as_ref().to_string(),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Empty => serde_json::Value::Null,
+            Value::Integer(v) => serde_json::Value::from(*v),
+            Value::Isk(d) => serde_json::Value::from(d.to_f64().unwrap()),
+            _ => serde_json::Value::String(self.to_plain()),
+        }
+    }
+
+    // 便于阅读的文本, 用于 markdown
+    pub(crate) fn to_display(&self, lang: Lang) -> String {
+        match self {
+            Value::Isk(d) => format_isk_text(*d),
+            Value::DateTime(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+            Value::RefType(r) => lang.ref_type(*r).to_string(),
+            _ => self.to_plain(),
+        }
+    }
+}
+
+pub struct TableColumn {
+    pub key: String,    // 稳定的英文键名, 不随展示标题变化
+    pub header: String, // 展示标题
+}
+
+impl TableColumn {
+    pub fn new<K: Into<String>, H: Into<String>>(key: K, header: H) -> Self {
+        TableColumn {
+            key: key.into(),
+            header: header.into(),
+        }
+    }
+}
+
+// 与输出格式无关的二维表
+pub struct Table {
+    pub key: &'static str, // 稳定的英文表名
+    pub title: String,     // 展示标题, 与 xlsx 工作表名一致
+    pub columns: Vec<TableColumn>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub trait Renderer {
+    fn render(&self, report: &Report, output_path: &Path) -> Result<(), String>;
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    Xlsx,
+    Csv,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    // csv 与 json 只输出稳定键名, 与语言无关
+    pub fn renderer(&self, lang: Lang) -> Box<dyn Renderer> {
+        match self {
+            ReportFormat::Xlsx => Box::new(XlsxRenderer { lang }),
+            ReportFormat::Csv => Box::new(CsvRenderer),
+            ReportFormat::Json => Box::new(JsonRenderer),
+            ReportFormat::Markdown => Box::new(MarkdownRenderer { lang }),
+            ReportFormat::Html => Box::new(HtmlRenderer { lang }),
+        }
+    }
+}
+
+pub struct XlsxRenderer {
+    lang: Lang,
+}
+
+impl Renderer for XlsxRenderer {
+    fn render(&self, report: &Report, output_path: &Path) -> Result<(), String> {
+        let lang = self.lang;
+        let template = &report.template;
+        let sheet_wallet_journal = report.sheet_name(Text::SheetWalletJournal, lang);
+        let sheet_tax_list = report.sheet_name(Text::SheetTaxList, lang);
+        let sheet_summary = report.sheet_name(Text::SheetSummary, lang);
+        let sheet_tax_inputs = report.sheet_name(Text::SheetTaxInputs, lang);
+        let mut book = new_file_empty_worksheet();
+
+        // 所选范围内流水不连续时, 以首个工作表提示数据可能缺失
+        if report.verify.issues().is_empty() == false {
+            let worksheet = book
+                .new_sheet(lang.text(Text::SheetJournalGaps))
+                .map_err(|e| e.to_string())?;
+            report.verify.insert_worksheet(worksheet, lang, template);
+        }
+
+        let worksheet = book
+            .new_sheet(sheet_wallet_journal.as_str())
+            .map_err(|e| e.to_string())?;
+        report
+            .wallet_journal
+            .insert_worksheet(worksheet, lang, template);
+
+        let worksheet = book
+            .new_sheet(sheet_tax_list.as_str())
+            .map_err(|e| e.to_string())?;
+        report
+            .tax_list
+            .insert_worksheet(worksheet, sheet_tax_inputs.as_str(), lang, template);
+
+        let worksheet = book
+            .new_sheet(sheet_summary.as_str())
+            .map_err(|e| e.to_string())?;
+        report.summary.insert_worksheet(worksheet, lang, template);
+
+        // 图表引用以上工作表的数据
+        let data_charts = SheetCharts::new(report.start, report.end, lang)
+            .with_wallet_journal(sheet_wallet_journal.as_str(), &report.wallet_journal)
+            .with_tax_list(sheet_tax_list.as_str(), &report.tax_list)
+            .with_summary(sheet_summary.as_str(), &report.summary);
+        let worksheet = book
+            .new_sheet(lang.text(Text::SheetCharts))
+            .map_err(|e| e.to_string())?;
+        data_charts.insert_worksheet(worksheet, template);
+
+        // 税收清单引用的原始数据, 隐藏以免误改
+        let worksheet = book
+            .new_sheet(sheet_tax_inputs.as_str())
+            .map_err(|e| e.to_string())?;
+        report
+            .tax_list
+            .insert_inputs_worksheet(worksheet, lang, template);
+        worksheet.set_sheet_state("hidden".to_string());
+
+        writer::xlsx::write(&book, output_path).map_err(|e| e.to_string())
+    }
+}
+
+// 每个表输出为一个文件, 例如 report.csv 输出为 report.wallet_journal.csv 等
+pub struct CsvRenderer;
+
+impl CsvRenderer {
+    fn table_path(output_path: &Path, table: &Table) -> PathBuf {
+        output_path.with_extension(format!("{}.csv", table.key))
+    }
+
+    fn to_csv(table: &Table) -> Result<Vec<u8>, String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer
+            .write_record(table.columns.iter().map(|c| c.key.as_str()))
+            .map_err(|e| e.to_string())?;
+        for row in &table.rows {
+            writer
+                .write_record(row.iter().map(|v| v.to_plain()))
+                .map_err(|e| e.to_string())?;
+        }
+        writer.into_inner().map_err(|e| e.to_string())
+    }
+}
+
+impl Renderer for CsvRenderer {
+    fn render(&self, report: &Report, output_path: &Path) -> Result<(), String> {
+        for table in report.tables(Lang::Zh) {
+            let path = CsvRenderer::table_path(output_path, &table);
+            std::fs::write(path, CsvRenderer::to_csv(&table)?).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+// 输出为一个对象, 各表以表名为键, 每行为以列键名为键的对象
+pub struct JsonRenderer;
+
+impl JsonRenderer {
+    fn to_json(report: &Report) -> serde_json::Value {
+        let mut root = serde_json::Map::new();
+        root.insert("start".to_string(), report.start.to_key().into());
+        root.insert("end".to_string(), report.end.to_key().into());
+        for table in report.tables(Lang::Zh) {
+            let rows: Vec<serde_json::Value> = table
+                .rows
+                .iter()
+                .map(|row| {
+                    let object: serde_json::Map<String, serde_json::Value> = table
+                        .columns
+                        .iter()
+                        .zip(row.iter())
+                        .map(|(c, v)| (c.key.clone(), v.to_json()))
+                        .collect();
+                    serde_json::Value::Object(object)
+                })
+                .collect();
+            root.insert(table.key.to_string(), serde_json::Value::Array(rows));
+        }
+        serde_json::Value::Object(root)
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn render(&self, report: &Report, output_path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(&JsonRenderer::to_json(report))
+            .map_err(|e| e.to_string())?;
+        std::fs::write(output_path, text).map_err(|e| e.to_string())
+    }
+}
+
+// 所有表输出到一个 markdown 文件, 每个表一个小节
+pub struct MarkdownRenderer {
+    lang: Lang,
+}
+
+impl MarkdownRenderer {
+    fn to_markdown(table: &Table, lang: Lang) -> String {
+        let escape = |s: String| s.replace('|', "\\|").replace('\n', " ");
+        let mut text = format!("## {}\n\n", table.title);
+        let headers: Vec<String> = table
+            .columns
+            .iter()
+            .map(|c| escape(c.header.clone()))
+            .collect();
+        text += &format!("| {} |\n", headers.join(" | "));
+        text += &format!("|{}\n", "---|".repeat(headers.len()));
+        for row in &table.rows {
+            let cells: Vec<String> = row.iter().map(|v| escape(v.to_display(lang))).collect();
+            text += &format!("| {} |\n", cells.join(" | "));
+        }
+        text
+    }
+}
+
+impl Renderer for MarkdownRenderer {
+    fn render(&self, report: &Report, output_path: &Path) -> Result<(), String> {
+        let lang = self.lang;
+        let mut text = format!(
+            "# {} {} ~ {}\n",
+            lang.text(Text::ReportTitle),
+            lang.year_month(report.start),
+            lang.year_month(report.end)
+        );
+        for table in report.tables(lang) {
+            text += "\n";
+            text += &MarkdownRenderer::to_markdown(&table, lang);
+        }
+        std::fs::write(output_path, text).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+fn test_table() -> Table {
+    Table {
+        key: "test",
+        title: "测试".to_string(),
+        columns: vec![
+            TableColumn::new("ref_type", "类型"),
+            TableColumn::new("amount", "金额"),
+            TableColumn::new("description", "备注"),
+        ],
+        rows: vec![
+            vec![
+                Value::RefType(JournalRefType::PlayerDonation),
+                Value::Isk(Decimal::new(-123456789, 2)),
+                Value::Text("a|b".to_string()),
+            ],
+            vec![Value::Empty, Value::Isk(Decimal::from(5)), Value::Empty],
+        ],
+    }
+}
+
+#[test]
+fn test_csv() {
+    let text = CsvRenderer::to_csv(&test_table()).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "ref_type,amount,description\nplayer_donation,-1234567.89,a|b\n,5,\n"
+    );
+    let path = CsvRenderer::table_path(Path::new("target/report.csv"), &test_table());
+    assert_eq!(path, PathBuf::from("target/report.test.csv"));
+}
+
+#[test]
+fn test_markdown() {
+    let text = MarkdownRenderer::to_markdown(&test_table(), Lang::Zh);
+    assert_eq!(
+        text,
+        "## 测试\n\n| 类型 | 金额 | 备注 |\n|---|---|---|\n| 玩家捐助 | -1,234,568 isk | a\\|b |\n|  | 5 isk |  |\n"
+    );
+    let text = MarkdownRenderer::to_markdown(&test_table(), Lang::En);
+    assert!(text.contains("| Player Donation | -1,234,568 isk | a\\|b |"));
+}
+
+#[test]
+fn test_json_value() {
+    assert_eq!(
+        Value::Isk(Decimal::new(150, 2)).to_json(),
+        serde_json::json!(1.5)
+    );
+    assert_eq!(
+        Value::RefType(JournalRefType::BountyPrizes).to_json(),
+        serde_json::json!("bounty_prizes")
+    );
+    assert_eq!(Value::Empty.to_json(), serde_json::Value::Null);
+}
@@ -1,20 +1,31 @@
-use chrono::{DateTime, Timelike, Utc};
+use chrono::{DateTime, FixedOffset, Timelike, Utc};
 use rust_decimal::{Decimal, prelude::ToPrimitive};
 use sea_orm::{
     ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, QueryFilter, QueryOrder,
     QuerySelect,
 };
-use std::collections::BTreeMap;
-use strum::{AsRefStr, EnumCount, EnumIter, IntoEnumIterator};
+use std::collections::{BTreeMap, BTreeSet};
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
 use umya_spreadsheet::{
-    Alignment, HorizontalAlignmentValues, NumberingFormat, Style, VerticalAlignmentValues,
+    Alignment, ConditionalFormatValues, ConditionalFormatting, ConditionalFormattingRule, Formula,
+    HorizontalAlignmentValues, NumberingFormat, PatternValues, Style, VerticalAlignmentValues,
     Worksheet, helper::coordinate::string_from_column_index,
 };
 
-use crate::db_op::{
-    RangeYearMonth, YearMonth, check_id, decimal_from_i64, find_user_year_month_pay_tax,
-    get_character_name, get_corporation_name, get_user_main_character_name, get_user_tax,
-    get_users_ids,
+use crate::{
+    db_op::{
+        PartyNames, RangeYearMonth, TaxLedger, YearMonth, decimal_from_i64, get_first_taxable_month,
+    },
+    images::{
+        EMBEDDED_PORTRAIT_COLUMN_WIDTH, EMBEDDED_PORTRAIT_ROW_HEIGHT, embed_image,
+        main_character_portraits,
+    },
+    locale::{Lang, Text},
+    period::ReportPeriod,
+    render::{ReportFormat, Table, TableColumn, Value},
+    summary::{SheetSummary, SummaryCategories},
+    template::ReportTemplate,
+    verify::SheetVerify,
 };
 use db_wallet::{
     JournalRefType,
@@ -23,41 +34,54 @@ use db_wallet::{
     },
 };
 
-#[derive(EnumIter, EnumCount, AsRefStr, Clone, Copy)]
+// 不使用报表模板的工作表 (对账单, 异常告警) 的高亮背景色, ARGB
+pub const HIGHLIGHT_RED: &str = "FFFFC7CE";
+pub const HIGHLIGHT_GREEN: &str = "FFC6EFCE";
+
+#[derive(EnumIter, EnumCount, Clone, Copy)]
 pub enum ColumnWalletJournal {
-    #[strum(serialize = "日期时间")]
     DateTime = 1,
-    #[strum(serialize = "类型")]
     RefType = 2,
-    #[strum(serialize = "收支金额")]
     Amount = 3,
-    #[strum(serialize = "账户余额")]
     Balance = 4,
-    #[strum(serialize = "相关角色")]
     Character = 5,
-    #[strum(serialize = "备注")]
     Description = 6,
 }
 impl ColumnWalletJournal {
-    fn get_style(&self) -> Style {
+    pub fn text(&self) -> Text {
+        match self {
+            ColumnWalletJournal::DateTime => Text::DateTime,
+            ColumnWalletJournal::RefType => Text::RefType,
+            ColumnWalletJournal::Amount => Text::Amount,
+            ColumnWalletJournal::Balance => Text::Balance,
+            ColumnWalletJournal::Character => Text::Party,
+            ColumnWalletJournal::Description => Text::Description,
+        }
+    }
+
+    // csv, json 等输出使用的稳定键名
+    pub fn key(&self) -> &'static str {
+        match self {
+            ColumnWalletJournal::DateTime => "date_time",
+            ColumnWalletJournal::RefType => "ref_type",
+            ColumnWalletJournal::Amount => "amount",
+            ColumnWalletJournal::Balance => "balance",
+            ColumnWalletJournal::Character => "party",
+            ColumnWalletJournal::Description => "description",
+        }
+    }
+
+    fn get_style(&self, template: &ReportTemplate) -> Style {
         let mut style = Style::default();
-        let format_str = match self {
-            ColumnWalletJournal::DateTime => r#"yyyy-mm-dd hh:mm:ss"#,
-            ColumnWalletJournal::RefType => r#"@"#,
-            ColumnWalletJournal::Amount => {
-                // r#"_ [$isk]\ * #,##0.00_ ;_ [$isk]\ * \-#,##0.00_ ;_ [$isk]\ * "-"?_ ;"#
-                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
-            }
-            ColumnWalletJournal::Balance => {
-                // r#"_ [$isk]\ * #,##0.00_ ;_ [$isk]\ * \-#,##0.00_ ;_ [$isk]\ * "-"?_ ;"#
-                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
-            }
-            ColumnWalletJournal::Character => r#"@"#,
-            ColumnWalletJournal::Description => r#"@"#,
+        let numbering_format = match self {
+            ColumnWalletJournal::DateTime => template.date_time_format(),
+            ColumnWalletJournal::Amount | ColumnWalletJournal::Balance => template.isk_format(),
+            ColumnWalletJournal::RefType
+            | ColumnWalletJournal::Character
+            | ColumnWalletJournal::Description => NumberingFormat::default()
+                .set_format_code(r#"@"#)
+                .to_owned(),
         };
-        let numbering_format = NumberingFormat::default()
-            .set_format_code(format_str)
-            .to_owned();
         style.set_numbering_format(numbering_format);
 
         style
@@ -65,7 +89,7 @@ impl ColumnWalletJournal {
 }
 
 pub struct RowWalletJournal {
-    date_time: DateTime<Utc>,
+    date_time: DateTime<FixedOffset>, // 报表时区的时间
     ref_type: JournalRefType,
     amount: Decimal,
     balance: Decimal,
@@ -75,36 +99,97 @@ pub struct RowWalletJournal {
 
 pub struct SheetWalletJournal {
     data: Vec<RowWalletJournal>,
+    columns: Vec<ColumnWalletJournal>, // 输出的列及其顺序
 }
 
 impl SheetWalletJournal {
-    pub fn insert_worksheet(&self, w: &mut Worksheet) {
+    pub fn with_columns(mut self, columns: Vec<ColumnWalletJournal>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    // 列所在位置, 未输出该列时为 None
+    pub fn column_index(&self, column: ColumnWalletJournal) -> Option<u32> {
+        let i = self.columns.iter().position(|c| c.key() == column.key())?;
+        Some(i as u32 + 1)
+    }
+
+    // 数据所在的首行与末行, 没有流水时为 None
+    pub fn data_rows(&self) -> Option<(u32, u32)> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some((2, self.data.len() as u32 + 1))
+        }
+    }
+
+    pub fn to_table(&self, lang: Lang) -> Table {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| TableColumn::new(c.key(), lang.text(c.text())))
+            .collect();
+        let rows = self
+            .data
+            .iter()
+            .map(|data| {
+                self.columns
+                    .iter()
+                    .map(|column| match column {
+                        ColumnWalletJournal::DateTime => Value::DateTime(data.date_time),
+                        ColumnWalletJournal::RefType => Value::RefType(data.ref_type),
+                        ColumnWalletJournal::Amount => Value::Isk(data.amount),
+                        ColumnWalletJournal::Balance => Value::Isk(data.balance),
+                        ColumnWalletJournal::Character => Value::Text(data.character.clone()),
+                        ColumnWalletJournal::Description => Value::Text(data.description.clone()),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Table {
+            key: "wallet_journal",
+            title: lang.text(Text::SheetWalletJournal).to_string(),
+            columns,
+            rows,
+        }
+    }
+
+    pub fn insert_worksheet(&self, w: &mut Worksheet, lang: Lang, template: &ReportTemplate) {
         // 插入标题
-        for column in ColumnWalletJournal::iter() {
-            let cell = w.get_cell_mut((column as u32, 1));
-            cell.set_value_string(column.as_ref());
+        for (i, column) in self.columns.iter().enumerate() {
+            let col = i as u32 + 1;
+            let cell = w.get_cell_mut((col, 1));
+            cell.set_value_string(lang.text(column.text()));
             let mut alignment = Alignment::default();
             alignment.set_horizontal(HorizontalAlignmentValues::Center);
             cell.get_style_mut().set_alignment(alignment);
+
+            if let Some(width) = template.wallet_journal.width(column.key()) {
+                w.get_column_dimension_mut(string_from_column_index(&col).as_str())
+                    .set_width(width);
+            }
         }
 
         // 插入数据
         for (i, data) in self.data.iter().enumerate() {
             let row = (i + 2) as u32;
-            for column in ColumnWalletJournal::iter() {
-                let col = column as u32;
-                let cell = w.get_cell_mut((col, row));
-                let mut style = column.get_style();
-                if data.amount.is_sign_negative() {
-                    // 支出标红
-                    style.set_background_color("FFFFC7CE");
-                } else {
-                    if data.ref_type == JournalRefType::PlayerDonation
-                        || data.ref_type == JournalRefType::CorporationAccountWithdrawal
-                    {
-                        // 交税与对公转账收入标绿
-                        style.set_background_color("FFC6EFCE");
-                    }
+            for (col, column) in self.columns.iter().enumerate() {
+                let cell = w.get_cell_mut((col as u32 + 1, row));
+                let mut style = column.get_style(template);
+                let cell_value = match column {
+                    ColumnWalletJournal::Amount => Some(data.amount),
+                    ColumnWalletJournal::Balance => Some(data.balance),
+                    _ => None,
+                };
+                let color = template.wallet_journal.highlight(
+                    column.key(),
+                    Some(data.ref_type),
+                    Some(data.amount),
+                    cell_value,
+                );
+                if let Some(color) = color {
+                    style.set_background_color(color);
                 }
                 cell.set_style(style);
 
@@ -116,7 +201,7 @@ impl SheetWalletJournal {
                         cell.set_value_number(days);
                     }
                     ColumnWalletJournal::RefType => {
-                        cell.set_value_string(data.ref_type.zh_str());
+                        cell.set_value_string(lang.ref_type(data.ref_type));
                     }
                     ColumnWalletJournal::Amount => {
                         cell.set_value_number(data.amount.to_f64().unwrap());
@@ -142,10 +227,12 @@ impl SheetWalletJournal {
         // w.get_column_dimension_mut("F").set_auto_width(true);
     }
 
+    // 流水时间按 offset 时区显示
     pub async fn select_from_db<DB: ConnectionTrait>(
         db: &DB,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
+        offset: FixedOffset,
     ) -> Result<SheetWalletJournal, String> {
         #[derive(FromQueryResult)]
         struct Journal {
@@ -182,6 +269,14 @@ impl SheetWalletJournal {
             .await
             .map_err(|e| e.to_string())?;
 
+        // 一次查询出所有相关方的名称
+        let ids: BTreeSet<i64> = journals
+            .iter()
+            .flat_map(|j| [j.first_party_id, j.second_party_id])
+            .flatten()
+            .collect();
+        let names = PartyNames::select_from_db(db, &ids).await?;
+
         let mut data = Vec::with_capacity(journals.len());
 
         for journal in journals {
@@ -215,7 +310,7 @@ impl SheetWalletJournal {
                     } else {
                         journal.second_party_id.unwrap()
                     };
-                    let r = check_id(db, id).await?;
+                    let r = names.kind(id);
                     if let Some(flag) = r {
                         if flag {
                             character_id = Some(id);
@@ -226,7 +321,7 @@ impl SheetWalletJournal {
                 }
                 JournalRefType::CorporationDividendPayment => {
                     let id = journal.second_party_id.unwrap();
-                    let r = check_id(db, id).await?;
+                    let r = names.kind(id);
                     if let Some(flag) = r {
                         if flag {
                             character_id = Some(id);
@@ -249,7 +344,7 @@ impl SheetWalletJournal {
                 }
                 _ => {
                     let id = journal.second_party_id.unwrap();
-                    let r = check_id(db, id).await?;
+                    let r = names.kind(id);
                     if let Some(flag) = r {
                         if flag {
                             character_id = Some(id);
@@ -261,18 +356,20 @@ impl SheetWalletJournal {
             }
 
             if let Some(character_id) = character_id {
-                if let Some(n) = get_character_name(db, character_id).await? {
-                    character = n;
+                if let Some(n) = names.character_name(character_id) {
+                    character = n.to_string();
                 }
             }
 
             if let Some(corporation_id) = corporation_id {
-                if let Some(n) = get_corporation_name(db, corporation_id).await? {
-                    character = n;
+                if let Some(n) = names.corporation_name(corporation_id) {
+                    character = n.to_string();
                 }
             }
 
-            let date_time: DateTime<Utc> = DateTime::from_timestamp_secs(journal.date).unwrap();
+            let date_time = DateTime::from_timestamp_secs(journal.date)
+                .unwrap()
+                .with_timezone(&offset);
             let balance = decimal_from_i64(journal.balance.unwrap());
             let description = journal.description;
 
@@ -287,14 +384,19 @@ impl SheetWalletJournal {
             data.push(row);
         }
 
-        Ok(SheetWalletJournal { data })
+        Ok(SheetWalletJournal {
+            data,
+            columns: ColumnWalletJournal::iter().collect(),
+        })
     }
 }
 
 struct UserTaxList {
+    user_id: i32,
     character_name: String,          // 主角色名
     amount_of_unpaid_taxes: Decimal, // 欠税金额
     list: BTreeMap<YearMonth, MonthTax>,
+    note: String, // 备注, 可在表格中修改后由 ingest_workbook 读回
 }
 
 #[derive(Clone)]
@@ -308,111 +410,443 @@ pub struct SheetTaxList {
     start: YearMonth,
     end: YearMonth,
     data: Vec<UserTaxList>,
+    portraits: Option<BTreeMap<i32, Vec<u8>>>, // 主角色头像, 为 None 时不生成头像列
+}
+
+// 欠税用户
+pub struct UserArrears {
+    pub user_id: i32,
+    pub character_name: String,      // 主角色名
+    pub amount: Decimal,             // 欠税金额
+    pub year_months: Vec<YearMonth>, // 应缴大于实缴的月份
 }
 
 impl SheetTaxList {
-    pub fn insert_worksheet(&self, w: &mut Worksheet) {
-        self.generate_sheet_header(w);
-        self.generate_sheet_data(w);
+    // 获取所有欠税用户, 按欠税金额降序
+    pub fn arrears(&self) -> Vec<UserArrears> {
+        let mut arrears: Vec<UserArrears> = self
+            .data
+            .iter()
+            .filter(|u| u.amount_of_unpaid_taxes > Decimal::ZERO)
+            .map(|u| UserArrears {
+                user_id: u.user_id,
+                character_name: u.character_name.clone(),
+                amount: u.amount_of_unpaid_taxes,
+                year_months: u
+                    .list
+                    .iter()
+                    .filter(|(_, mt)| mt.pap_tax + mt.poll_tax > mt.paid_up_tax)
+                    .map(|(ym, _)| *ym)
+                    .collect(),
+            })
+            .collect();
+        arrears.sort_by_key(|a| std::cmp::Reverse(a.amount));
+        arrears
     }
 
-    fn generate_sheet_header(&self, w: &mut Worksheet) {
+    // 在主角色名之后嵌入各用户主角色的 64px 头像
+    pub async fn with_portraits<DB: ConnectionTrait>(mut self, db: &DB) -> Result<Self, String> {
+        let users_ids: Vec<i32> = self.data.iter().map(|u| u.user_id).collect();
+        self.portraits = Some(main_character_portraits(db, &users_ids).await?);
+        Ok(self)
+    }
+
+    pub fn has_portraits(&self) -> bool {
+        self.portraits.is_some()
+    }
+
+    pub fn portrait(&self, user_id: i32) -> Option<&[u8]> {
+        self.portraits
+            .as_ref()
+            .and_then(|p| p.get(&user_id))
+            .map(|p| p.as_slice())
+    }
+
+    // 欠税额所在列, 有头像列时右移一列
+    fn unpaid_column(&self) -> u32 {
+        if self.has_portraits() { 3 } else { 2 }
+    }
+
+    // 备注所在列, 位于各月税额之后
+    pub fn note_column(&self) -> u32 {
+        self.unpaid_column() + RangeYearMonth::new(self.start, self.end).count() as u32 * 3 + 1
+    }
+
+    // 用户ID所在列, 位于备注之后并隐藏, 读回税收清单时按用户ID匹配用户
+    pub fn user_id_column(&self) -> u32 {
+        self.note_column() + 1
+    }
+
+    // 数据所在的首行与末行, 没有用户时为 None
+    pub fn data_rows(&self) -> Option<(u32, u32)> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some((3, self.data.len() as u32 + 2))
+        }
+    }
+
+    // 各月 PAP税额, 人头税额, 实缴税额 所在的列
+    pub fn month_columns(&self) -> Vec<(u32, u32, u32)> {
+        let unpaid_column = self.unpaid_column();
+        RangeYearMonth::new(self.start, self.end)
+            .enumerate()
+            .map(|(i, _)| {
+                let col = i as u32 * 3 + unpaid_column + 1;
+                (col, col + 1, col + 2)
+            })
+            .collect()
+    }
+
+    pub fn to_table(&self, lang: Lang) -> Table {
+        let mut columns = vec![
+            TableColumn::new("user_id", lang.text(Text::UserId)),
+            TableColumn::new("character_name", lang.text(Text::MainCharacter)),
+            TableColumn::new("unpaid_tax", lang.text(Text::UnpaidTax)),
+        ];
+        for ym in RangeYearMonth::new(self.start, self.end) {
+            let (key, name) = (ym.to_key(), lang.year_month(ym));
+            for (column, text) in [
+                ("pap_tax", Text::PapTax),
+                ("poll_tax", Text::PollTax),
+                ("paid_up_tax", Text::PaidUpTax),
+            ] {
+                columns.push(TableColumn::new(
+                    format!("{}.{}", key, column),
+                    format!("{} {}", name, lang.text(text)),
+                ));
+            }
+        }
+        columns.push(TableColumn::new("note", lang.text(Text::Note)));
+
+        let rows = self
+            .data
+            .iter()
+            .map(|u| {
+                let mut row = vec![
+                    Value::Integer(u.user_id as i64),
+                    Value::Text(u.character_name.clone()),
+                    Value::Isk(u.amount_of_unpaid_taxes),
+                ];
+                for mt in u.list.values() {
+                    row.extend([
+                        Value::Isk(mt.pap_tax),
+                        Value::Isk(mt.poll_tax),
+                        Value::Isk(mt.paid_up_tax),
+                    ]);
+                }
+                row.push(Value::Text(u.note.clone()));
+                row
+            })
+            .collect();
+
+        Table {
+            key: "tax_list",
+            title: lang.text(Text::SheetTaxList).to_string(),
+            columns,
+            rows,
+        }
+    }
+
+    // 税收清单中的税额均为引用原始数据工作表的公式, 欠税额与合计行由公式计算,
+    // 便于直接修改单元格并查看结果
+    pub fn insert_worksheet(
+        &self,
+        w: &mut Worksheet,
+        inputs_sheet_name: &str,
+        lang: Lang,
+        template: &ReportTemplate,
+    ) {
+        self.generate_sheet_header(w, lang, template);
+        self.generate_sheet_data(w, inputs_sheet_name, template);
+        self.generate_sheet_user_ids(w, lang);
+        self.generate_sheet_total(w, lang, template);
+    }
+
+    // 原始数据工作表, 布局与税收清单相同, 仅包含各月税额
+    pub fn insert_inputs_worksheet(
+        &self,
+        w: &mut Worksheet,
+        lang: Lang,
+        template: &ReportTemplate,
+    ) {
+        self.generate_sheet_header(w, lang, template);
+        for (row, user_tax_list) in self.data.iter().enumerate() {
+            let row = (row + 3) as u32;
+            w.get_cell_mut((1, row))
+                .set_value_string(user_tax_list.character_name.clone());
+
+            let columns = self.month_columns();
+            for ((pap, poll, paid), (_, month_tax)) in columns.iter().zip(user_tax_list.list.iter())
+            {
+                for (col, v) in [
+                    (pap, month_tax.pap_tax),
+                    (poll, month_tax.poll_tax),
+                    (paid, month_tax.paid_up_tax),
+                ] {
+                    let c = w.get_cell_mut((*col, row));
+                    c.set_value_number(v.to_f64().unwrap());
+                    c.get_style_mut()
+                        .set_numbering_format(template.isk_format());
+                }
+            }
+        }
+    }
+
+    fn generate_sheet_header(&self, w: &mut Worksheet, lang: Lang, template: &ReportTemplate) {
         let mut alignment = Alignment::default();
         alignment.set_horizontal(HorizontalAlignmentValues::Center);
         alignment.set_vertical(VerticalAlignmentValues::Center);
 
         let c = w.get_cell_mut("A1");
-        c.set_value_string("主角色名");
+        c.set_value_string(lang.text(Text::MainCharacter));
         c.get_style_mut().set_alignment(alignment.clone());
         w.add_merge_cells("A1:A2");
+        if let Some(width) = template.tax_list.width("character_name") {
+            w.get_column_dimension_mut("A").set_width(width);
+        }
 
-        let c = w.get_cell_mut("B1");
-        c.set_value_string("欠税额");
+        if self.portraits.is_some() {
+            let c = w.get_cell_mut("B1");
+            c.set_value_string(lang.text(Text::Portrait));
+            c.get_style_mut().set_alignment(alignment.clone());
+            w.add_merge_cells("B1:B2");
+            w.get_column_dimension_mut("B")
+                .set_width(EMBEDDED_PORTRAIT_COLUMN_WIDTH);
+        }
+
+        let unpaid_column = self.unpaid_column();
+        let c = w.get_cell_mut((unpaid_column, 1));
+        c.set_value_string(lang.text(Text::UnpaidTax));
         c.get_style_mut().set_alignment(alignment.clone());
-        w.add_merge_cells("B1:B2");
+        let col = string_from_column_index(&unpaid_column);
+        w.add_merge_cells(format!("{}1:{}2", col, col));
+        if let Some(width) = template.tax_list.width("unpaid_tax") {
+            w.get_column_dimension_mut(col.as_str()).set_width(width);
+        }
 
         let range_ym = RangeYearMonth::new(self.start, self.end);
         for (i, ym) in range_ym.enumerate() {
-            let i = (i * 3 + 3) as u32;
+            let i = i as u32 * 3 + unpaid_column + 1;
 
             let c = w.get_cell_mut((i, 1));
-            c.set_value_string(ym.to_string_zh());
+            c.set_value_string(lang.year_month(ym));
             c.get_style_mut().set_alignment(alignment.clone());
             let start_col = string_from_column_index(&i);
             let end_col = string_from_column_index(&(i + 2));
             w.add_merge_cells(format!("{}1:{}1", start_col, end_col));
 
             let c = w.get_cell_mut((i, 2));
-            c.set_value_string("PAP税额");
+            c.set_value_string(lang.text(Text::PapTax));
             c.get_style_mut().set_alignment(alignment.clone());
 
             let c = w.get_cell_mut((i + 1, 2));
-            c.set_value_string("人头税额");
+            c.set_value_string(lang.text(Text::PollTax));
             c.get_style_mut().set_alignment(alignment.clone());
 
             let c = w.get_cell_mut((i + 2, 2));
-            c.set_value_string("实缴税额");
+            c.set_value_string(lang.text(Text::PaidUpTax));
             c.get_style_mut().set_alignment(alignment.clone());
+
+            for (col, key) in [(i, "pap_tax"), (i + 1, "poll_tax"), (i + 2, "paid_up_tax")] {
+                if let Some(width) = template.tax_list.width(key) {
+                    w.get_column_dimension_mut(string_from_column_index(&col).as_str())
+                        .set_width(width);
+                }
+            }
+        }
+
+        let note_column = self.note_column();
+        let c = w.get_cell_mut((note_column, 1));
+        c.set_value_string(lang.text(Text::Note));
+        c.get_style_mut().set_alignment(alignment.clone());
+        let col = string_from_column_index(&note_column);
+        w.add_merge_cells(format!("{}1:{}2", col, col));
+        if let Some(width) = template.tax_list.width("note") {
+            w.get_column_dimension_mut(col.as_str()).set_width(width);
         }
     }
 
-    fn generate_sheet_data(&self, w: &mut Worksheet) {
+    fn generate_sheet_data(
+        &self,
+        w: &mut Worksheet,
+        inputs_sheet_name: &str,
+        template: &ReportTemplate,
+    ) {
+        let unpaid_column = self.unpaid_column();
+        let columns = self.month_columns();
         for (row, user_tax_list) in self.data.iter().enumerate() {
             let row = (row + 3) as u32;
 
             // 主角色名
-            let c = w.get_cell_mut((1, row));
-            c.set_value_string(user_tax_list.character_name.clone());
-
-            // 欠税额
-            let c = w.get_cell_mut((2, row));
-            let v = user_tax_list.amount_of_unpaid_taxes.to_f64().unwrap();
-            c.set_value_number(v);
-            c.get_style_mut().set_numbering_format(format_isk());
-            if v > 0.0 {
-                // 标红
-                c.get_style_mut().set_background_color("FFFFC7CE");
+            w.get_cell_mut((1, row))
+                .set_value_string(user_tax_list.character_name.clone());
+
+            // 头像
+            let portrait = self
+                .portraits
+                .as_ref()
+                .and_then(|p| p.get(&user_tax_list.user_id));
+            if let Some(portrait) = portrait {
+                w.get_row_dimension_mut(&row)
+                    .set_height(EMBEDDED_PORTRAIT_ROW_HEIGHT);
+                if let Err(e) = embed_image(w, 2, row, portrait) {
+                    println!("embed portrait of user {}: {}", user_tax_list.user_id, e);
+                }
+            }
+
+            // 欠税额 = 各月PAP税额与人头税额之和 - 各月实缴税额之和
+            let assessed: Vec<String> = columns
+                .iter()
+                .flat_map(|(pap, poll, _)| [cell_name(*pap, row), cell_name(*poll, row)])
+                .collect();
+            let paid: Vec<String> = columns
+                .iter()
+                .map(|(_, _, paid)| cell_name(*paid, row))
+                .collect();
+            let c = w.get_cell_mut((unpaid_column, row));
+            c.set_formula(format!(
+                "SUM({})-SUM({})",
+                assessed.join(","),
+                paid.join(",")
+            ));
+            c.get_style_mut()
+                .set_numbering_format(template.isk_format());
+
+            // PAP税额, 人头税额, 实缴税额 引用原始数据
+            for (pap, poll, paid) in &columns {
+                for col in [pap, poll, paid] {
+                    let c = w.get_cell_mut((*col, row));
+                    c.set_formula(format!("'{}'!{}", inputs_sheet_name, cell_name(*col, row)));
+                    c.get_style_mut()
+                        .set_numbering_format(template.isk_format());
+                }
             }
 
-            for (index, (_, month_tax)) in user_tax_list.list.iter().enumerate() {
-                let col = (index * 3 + 3) as u32;
-
-                // PAP税额
-                let c = w.get_cell_mut((col, row));
-                let v = month_tax.pap_tax.to_f64().unwrap();
-                c.set_value_number(v);
-                c.get_style_mut().set_numbering_format(format_isk());
-
-                // 人头税额
-                let c = w.get_cell_mut((col + 1, row));
-                let v = month_tax.poll_tax.to_f64().unwrap();
-                c.set_value_number(v);
-                c.get_style_mut().set_numbering_format(format_isk());
-
-                // 实缴税额
-                let c = w.get_cell_mut((col + 2, row));
-                let v = month_tax.paid_up_tax.to_f64().unwrap();
-                c.set_value_number(v);
-                c.get_style_mut().set_numbering_format(format_isk());
+            // 备注
+            w.get_cell_mut((self.note_column(), row))
+                .set_value_string(user_tax_list.note.clone());
+        }
+        self.generate_sheet_highlights(w, template);
+    }
+
+    // 隐藏的用户ID列, 主角色名可能重复, 不能作为读回时的依据
+    fn generate_sheet_user_ids(&self, w: &mut Worksheet, lang: Lang) {
+        let user_id_column = self.user_id_column();
+        w.get_cell_mut((user_id_column, 1))
+            .set_value_string(lang.text(Text::UserId));
+        let col = string_from_column_index(&user_id_column);
+        w.add_merge_cells(format!("{}1:{}2", col, col));
+        w.get_column_dimension_mut(col.as_str()).set_hidden(true);
+
+        for (row, user_tax_list) in self.data.iter().enumerate() {
+            w.get_cell_mut((user_id_column, (row + 3) as u32))
+                .set_value_number(user_tax_list.user_id as f64);
+        }
+    }
+
+    // 高亮规则转为条件格式, 修改单元格后高亮随公式结果更新
+    // 整行规则以欠税额判断, 按规则顺序设置优先级, 首个满足的规则生效
+    fn generate_sheet_highlights(&self, w: &mut Worksheet, template: &ReportTemplate) {
+        let (first, last) = match self.data_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let unpaid_column = self.unpaid_column();
+        let columns = self.month_columns();
+
+        let mut priority = 0;
+        for rule in &template.tax_list.highlights {
+            // (判断的单元格, 生效的区域)
+            let targets: Vec<(String, String)> = match rule.column.as_deref() {
+                None => vec![(
+                    format!("${}", cell_name(unpaid_column, first)),
+                    format!(
+                        "{}:{}",
+                        cell_name(1, first),
+                        cell_name(self.note_column(), last)
+                    ),
+                )],
+                Some(key) => {
+                    let cols: Vec<u32> = match key {
+                        "unpaid_tax" => vec![unpaid_column],
+                        "pap_tax" => columns.iter().map(|c| c.0).collect(),
+                        "poll_tax" => columns.iter().map(|c| c.1).collect(),
+                        "paid_up_tax" => columns.iter().map(|c| c.2).collect(),
+                        // 主角色名与备注不是金额, 仅整行规则生效
+                        _ => vec![],
+                    };
+                    cols.into_iter()
+                        .map(|col| {
+                            (
+                                cell_name(col, first),
+                                format!("{}:{}", cell_name(col, first), cell_name(col, last)),
+                            )
+                        })
+                        .collect()
+                }
+            };
+            for (value, sqref) in targets {
+                let formula = match rule.formula(value.as_str()) {
+                    Some(formula) => formula,
+                    None => continue,
+                };
+                priority += 1;
+                w.add_conditional_formatting_collection(conditional_highlight(
+                    sqref.as_str(),
+                    formula.as_str(),
+                    rule.color.as_str(),
+                    priority,
+                ));
             }
         }
     }
 
+    // 合计行, 对欠税额与各月税额按列求和
+    fn generate_sheet_total(&self, w: &mut Worksheet, lang: Lang, template: &ReportTemplate) {
+        let (first, last) = match self.data_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let row = last + 1;
+
+        let c = w.get_cell_mut((1, row));
+        c.set_value_string(lang.text(Text::Total));
+        c.get_style_mut().get_font_mut().set_bold(true);
+
+        let mut total_columns = vec![self.unpaid_column()];
+        for (pap, poll, paid) in self.month_columns() {
+            total_columns.extend([pap, poll, paid]);
+        }
+        for col in total_columns {
+            let c = w.get_cell_mut((col, row));
+            c.set_formula(format!(
+                "SUM({}:{})",
+                cell_name(col, first),
+                cell_name(col, last)
+            ));
+            c.get_style_mut()
+                .set_numbering_format(template.isk_format());
+            c.get_style_mut().get_font_mut().set_bold(true);
+        }
+    }
+
     pub async fn select_from_db<DB: ConnectionTrait>(
         db: &DB,
         start: YearMonth,
         end: YearMonth,
+        offset: FixedOffset,
     ) -> Result<SheetTaxList, String> {
         let mut users_tax_list = Vec::new();
-        let users_ids = get_users_ids(db).await?;
+        let ledger = TaxLedger::select_from_db(db, start, end, offset).await?;
 
-        for user_id in users_ids {
-            let character_name = get_user_main_character_name(db, user_id).await?;
+        for user_id in ledger.users_ids().iter().copied() {
+            let character_name = ledger.main_character_name(user_id)?;
             let mut list = BTreeMap::new();
             let range_ym = RangeYearMonth::new(start, end);
             for ym in range_ym {
-                let (poll_tax, pap_tax) = get_user_tax(db, user_id, ym).await?;
-                let paid_up_tax = find_user_year_month_pay_tax(db, user_id, ym).await?;
+                let (poll_tax, pap_tax) = ledger.user_tax(user_id, ym)?;
+                let paid_up_tax = ledger.paid_up_tax(user_id, ym);
                 let month_tax = MonthTax {
                     pap_tax,
                     poll_tax,
@@ -422,9 +856,11 @@ impl SheetTaxList {
             }
             let amount_of_unpaid_taxes = compute_unpaid_tax(&list);
             let user_tax_list = UserTaxList {
+                user_id,
                 character_name,
                 amount_of_unpaid_taxes,
                 list,
+                note: ledger.note(user_id).unwrap_or_default().to_string(),
             };
             users_tax_list.push(user_tax_list);
         }
@@ -433,8 +869,22 @@ impl SheetTaxList {
             start,
             end,
             data: users_tax_list,
+            portraits: None,
         })
     }
+
+    // 自首个计税月份至 end 的税收清单, 欠税额包含更早月份未缴清的部分
+    pub async fn select_cumulative<DB: ConnectionTrait>(
+        db: &DB,
+        end: YearMonth,
+        offset: FixedOffset,
+    ) -> Result<SheetTaxList, String> {
+        let start = match get_first_taxable_month(db).await? {
+            Some(first) if first < end => first,
+            _ => end,
+        };
+        SheetTaxList::select_from_db(db, start, end, offset).await
+    }
 }
 
 fn compute_unpaid_tax(data: &BTreeMap<YearMonth, MonthTax>) -> Decimal {
@@ -448,8 +898,238 @@ fn compute_unpaid_tax(data: &BTreeMap<YearMonth, MonthTax>) -> Decimal {
     unpaid - paid
 }
 
-fn format_isk() -> NumberingFormat {
-    NumberingFormat::default()
-        .set_format_code(r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#)
-        .to_owned()
+pub struct ReportConfig {
+    pub with_portraits: bool,          // 在税收清单中嵌入主角色头像
+    pub categories: SummaryCategories, // 收支汇总的分类
+    pub template: ReportTemplate,      // 工作表名, 列, 数字格式与高亮规则
+    pub format: ReportFormat,
+    pub lang: Lang,
+}
+
+// 生成报表所需的全部数据, 与输出格式无关
+pub struct Report {
+    pub start: YearMonth,
+    pub end: YearMonth,
+    pub verify: SheetVerify,
+    pub wallet_journal: SheetWalletJournal,
+    pub tax_list: SheetTaxList,
+    pub summary: SheetSummary,
+    pub template: ReportTemplate,
+}
+
+impl Report {
+    // 流水与收支汇总按期间的起止时刻筛选, 税收清单包含期间涉及的整月
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        period: &ReportPeriod,
+        categories: &SummaryCategories,
+    ) -> Result<Report, String> {
+        let (start, end) = (period.start_month(), period.end_month());
+        let (lower, upper) = (period.lower(), period.upper());
+        let verify =
+            SheetVerify::select_from_db(db, Some(lower), Some(upper), period.offset).await?;
+        let wallet_journal =
+            SheetWalletJournal::select_from_db(db, lower, upper, period.offset).await?;
+        let tax_list = SheetTaxList::select_from_db(db, start, end, period.offset).await?;
+        let summary = SheetSummary::select_from_db(db, period, categories).await?;
+
+        Ok(Report {
+            start,
+            end,
+            verify,
+            wallet_journal,
+            tax_list,
+            summary,
+            template: ReportTemplate::default(),
+        })
+    }
+
+    // 按模板设置工作表名, 流水的列, 数字格式与高亮
+    pub fn with_template(mut self, template: ReportTemplate) -> Self {
+        self.wallet_journal = self
+            .wallet_journal
+            .with_columns(template.wallet_journal_columns());
+        self.template = template;
+        self
+    }
+
+    pub async fn with_portraits<DB: ConnectionTrait>(mut self, db: &DB) -> Result<Self, String> {
+        self.tax_list = self.tax_list.with_portraits(db).await?;
+        Ok(self)
+    }
+
+    // csv, json, markdown 输出的各表
+    pub fn tables(&self, lang: Lang) -> Vec<Table> {
+        let mut wallet_journal = self.wallet_journal.to_table(lang);
+        wallet_journal.title = self.sheet_name(Text::SheetWalletJournal, lang);
+        let mut tax_list = self.tax_list.to_table(lang);
+        tax_list.title = self.sheet_name(Text::SheetTaxList, lang);
+        let mut summary = self.summary.to_table(lang);
+        summary.title = self.sheet_name(Text::SheetSummary, lang);
+
+        vec![
+            self.verify.to_table(lang),
+            wallet_journal,
+            tax_list,
+            summary,
+        ]
+    }
+
+    // 工作表名, 模板中未设置时按语言使用默认名称
+    pub fn sheet_name(&self, text: Text, lang: Lang) -> String {
+        match text {
+            Text::SheetWalletJournal => self.template.wallet_journal.name(lang, text),
+            Text::SheetTaxList => self.template.tax_list.name(lang, text),
+            Text::SheetSummary => self.template.summary.name(lang, text),
+            _ => lang.text(text).to_string(),
+        }
+    }
+}
+
+// 单元格名称, 例如 D3
+fn cell_name(col: u32, row: u32) -> String {
+    format!("{}{}", string_from_column_index(&col), row)
+}
+
+// 满足公式时以 color 填充 sqref 区域的条件格式, 满足后不再判断优先级更低的规则
+fn conditional_highlight(
+    sqref: &str,
+    formula: &str,
+    color: &str,
+    priority: i32,
+) -> ConditionalFormatting {
+    let mut style = Style::default();
+    // 条件格式的纯色填充使用背景色
+    style
+        .get_fill_mut()
+        .get_pattern_fill_mut()
+        .set_pattern_type(PatternValues::Solid)
+        .get_background_color_mut()
+        .set_argb(color);
+
+    let mut rule = ConditionalFormattingRule::default();
+    rule.set_type(ConditionalFormatValues::Expression)
+        .set_priority(priority)
+        .set_stop_if_true(true)
+        .set_style(style)
+        .set_formula(Formula::default().set_string_value(formula).to_owned());
+
+    let mut conditional = ConditionalFormatting::default();
+    conditional
+        .get_sequence_of_references_mut()
+        .set_sqref(sqref);
+    conditional.add_conditional_collection(rule);
+    conditional
+}
+
+#[test]
+fn test_cell_name() {
+    assert_eq!(cell_name(4, 3), "D3");
+    assert_eq!(cell_name(28, 12), "AB12");
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_tax_list_portraits() {
+    use crate::{
+        db_op::{save_party_image, test_tax_database},
+        images::{EMBEDDED_PORTRAIT_SIZE, test_image},
+    };
+    use image::ImageFormat;
+    use umya_spreadsheet::new_file_empty_worksheet;
+
+    let db = test_tax_database().await;
+    save_party_image(&db, 1001, test_image(256, ImageFormat::Png))
+        .await
+        .unwrap();
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let (start, end) = (YearMonth::new(2025, 9), YearMonth::new(2025, 10));
+    let template = ReportTemplate::default();
+
+    // 无头像时欠税额在 B 列, 备注在 2 + 2 * 3 + 1 = 9 列
+    let tax_list = SheetTaxList::select_from_db(&db, start, end, utc)
+        .await
+        .unwrap();
+    assert_eq!((tax_list.unpaid_column(), tax_list.note_column()), (2, 9));
+
+    // 有头像时各列右移一列, 头像嵌入 B 列
+    let tax_list = tax_list.with_portraits(&db).await.unwrap();
+    assert_eq!((tax_list.unpaid_column(), tax_list.note_column()), (3, 10));
+    assert_eq!(tax_list.month_columns()[0], (4, 5, 6));
+
+    let mut book = new_file_empty_worksheet();
+    let w = book.new_sheet("清单").unwrap();
+    tax_list.insert_worksheet(w, "输入", Lang::Zh, &template);
+    assert_eq!(w.get_value("B1"), Lang::Zh.text(Text::Portrait));
+    assert_eq!(w.get_value("C1"), Lang::Zh.text(Text::UnpaidTax));
+    assert_eq!(w.get_value("J1"), Lang::Zh.text(Text::Note));
+    assert!(
+        w.get_cell("C3")
+            .unwrap()
+            .get_formula()
+            .starts_with("SUM(D3,E3,G3,H3)")
+    );
+
+    // 只有用户 1 的主角色有头像
+    let images = w.get_image_collection();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].get_coordinate(), "B3");
+    let image = image::load_from_memory(images[0].get_image_data()).unwrap();
+    assert_eq!(image.width(), EMBEDDED_PORTRAIT_SIZE);
+    assert!(images[0].get_image_name().ends_with(".png"));
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_tax_list_formulas() {
+    use crate::db_op::test_tax_database;
+    use umya_spreadsheet::new_file_empty_worksheet;
+
+    let db = test_tax_database().await;
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let (start, end) = (YearMonth::new(2025, 9), YearMonth::new(2025, 10));
+    let tax_list = SheetTaxList::select_from_db(&db, start, end, utc)
+        .await
+        .unwrap();
+    let template = ReportTemplate::default();
+
+    let mut book = new_file_empty_worksheet();
+    let w = book.new_sheet("清单").unwrap();
+    tax_list.insert_worksheet(w, "输入", Lang::Zh, &template);
+    let formula = |cell: &str| w.get_cell(cell).unwrap().get_formula().to_string();
+
+    // 欠税额 = 各月PAP税额与人头税额之和 - 各月实缴税额之和, 税额引用原始数据
+    assert_eq!(formula("B3"), "SUM(C3,D3,F3,G3)-SUM(E3,H3)");
+    assert_eq!(formula("B4"), "SUM(C4,D4,F4,G4)-SUM(E4,H4)");
+    assert_eq!(formula("C3"), "'输入'!C3");
+    assert_eq!(formula("H4"), "'输入'!H4");
+
+    // 合计行
+    assert_eq!(w.get_value("A5"), Lang::Zh.text(Text::Total));
+    for col in ["B", "C", "D", "E", "F", "G", "H"] {
+        assert_eq!(
+            formula(&format!("{}5", col)),
+            format!("SUM({0}3:{0}4)", col)
+        );
+    }
+    assert!(w.get_cell("I5").is_none());
+
+    // 隐藏的用户ID列
+    assert_eq!(w.get_value("J1"), Lang::Zh.text(Text::UserId));
+    assert_eq!(
+        (w.get_value("J3"), w.get_value("J4")),
+        ("1".into(), "2".into())
+    );
+    assert!(w.get_column_dimension("J").unwrap().get_hidden());
+
+    // 欠税标红为条件格式, 随欠税额公式的结果变化
+    let conditionals = w.get_conditional_formatting_collection();
+    assert_eq!(conditionals.len(), 1);
+    assert_eq!(
+        conditionals[0].get_sequence_of_references().get_sqref(),
+        "B3:B4"
+    );
+    let rule = &conditionals[0].get_conditional_collection()[0];
+    assert_eq!(rule.get_formula().unwrap().get_address_str(), "B3>0");
+    assert!(w.get_cell("B3").unwrap().get_style().get_fill().is_none());
 }
@@ -0,0 +1,514 @@
+use chrono::{DateTime, FixedOffset, TimeZone, Timelike};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sea_orm::ConnectionTrait;
+use std::collections::BTreeMap;
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
+use umya_spreadsheet::{
+    Alignment, HorizontalAlignmentValues, NumberingFormat, Style, Worksheet,
+    helper::coordinate::string_from_column_index,
+};
+
+use crate::{
+    db_op::{
+        RangeYearMonth, TaxLedger, YearMonth, find_user_pay_tax_journal, get_user_characters,
+        get_user_main_character_name, get_user_taxable_year_months,
+    },
+    images::{
+        EMBEDDED_PORTRAIT_COLUMN_WIDTH, EMBEDDED_PORTRAIT_ROW_HEIGHT, embed_image,
+        main_character_portraits,
+    },
+    locale::{Lang, Text},
+    report::{HIGHLIGHT_GREEN, HIGHLIGHT_RED},
+};
+
+#[derive(EnumIter, EnumCount, Clone, Copy)]
+pub enum ColumnStatement {
+    YearMonth = 1,
+    Item = 2,
+    Character = 3,
+    DateTime = 4,
+    Pap = 5,
+    Amount = 6,
+    Balance = 7,
+}
+
+impl ColumnStatement {
+    pub fn text(&self) -> Text {
+        match self {
+            ColumnStatement::YearMonth => Text::Month,
+            ColumnStatement::Item => Text::Item,
+            ColumnStatement::Character => Text::Character,
+            ColumnStatement::DateTime => Text::DateTime,
+            ColumnStatement::Pap => Text::Pap,
+            ColumnStatement::Amount => Text::Amount,
+            ColumnStatement::Balance => Text::ArrearsBalance,
+        }
+    }
+
+    fn get_style(&self) -> Style {
+        let mut style = Style::default();
+        let format_str = match self {
+            ColumnStatement::YearMonth => r#"@"#,
+            ColumnStatement::Item => r#"@"#,
+            ColumnStatement::Character => r#"@"#,
+            ColumnStatement::DateTime => r#"yyyy-mm-dd hh:mm:ss"#,
+            ColumnStatement::Pap => r#"0.00"#,
+            ColumnStatement::Amount => {
+                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
+            }
+            ColumnStatement::Balance => {
+                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
+            }
+        };
+        let numbering_format = NumberingFormat::default()
+            .set_format_code(format_str)
+            .to_owned();
+        style.set_numbering_format(numbering_format);
+
+        style
+    }
+}
+
+// 对账单中的一笔缴税记录
+struct Payment {
+    date_time: DateTime<FixedOffset>, // 对账单时区的时间
+    character: String,
+    amount: Decimal,
+}
+
+// 对账单中某个月份的明细
+struct MonthStatement {
+    year_month: YearMonth,
+    poll_tax: Decimal,                      // 人头税额
+    pap_tax: Decimal,                       // PAP税额
+    character_paps: Vec<(String, Decimal)>, // 各角色PAP分
+    payments: Vec<Payment>,                 // 实缴税额明细
+    balance: Decimal,                       // 截至本月末的欠税余额
+}
+
+impl MonthStatement {
+    fn pap(&self) -> Decimal {
+        self.character_paps.iter().map(|(_, pap)| *pap).sum()
+    }
+}
+
+// 对账单工作表中的一行
+struct RowStatement {
+    year_month: String,
+    item: Text,
+    character: String,
+    date_time: Option<DateTime<FixedOffset>>,
+    pap: Option<Decimal>,
+    amount: Option<Decimal>,
+    balance: Option<Decimal>,
+}
+
+// 单个成员的税收对账单
+pub struct UserStatement {
+    user_id: i32,
+    character_name: String, // 主角色名
+    months: Vec<MonthStatement>,
+    portrait: Option<Vec<u8>>, // 主角色头像, 为 None 时不生成头像列
+}
+
+impl UserStatement {
+    // 当前欠税额, 正数表示欠税
+    pub fn balance(&self) -> Decimal {
+        self.months
+            .last()
+            .map(|m| m.balance)
+            .unwrap_or(Decimal::ZERO)
+    }
+
+    // 在明细右侧显示主角色名与主角色的 64px 头像
+    pub async fn with_portrait<DB: ConnectionTrait>(mut self, db: &DB) -> Result<Self, String> {
+        let mut portraits = main_character_portraits(db, &[self.user_id]).await?;
+        self.portrait = portraits.remove(&self.user_id);
+        Ok(self)
+    }
+
+    // 展开为逐行明细, 每行之后的欠税余额随之滚动
+    fn rows(&self, lang: Lang) -> Vec<RowStatement> {
+        let mut rows = Vec::new();
+        let mut balance = Decimal::ZERO;
+        for month in &self.months {
+            let year_month = lang.year_month(month.year_month);
+
+            balance += month.poll_tax;
+            rows.push(RowStatement {
+                year_month: year_month.clone(),
+                item: Text::PollTax,
+                character: String::new(),
+                date_time: None,
+                pap: None,
+                amount: Some(month.poll_tax),
+                balance: Some(balance),
+            });
+
+            balance += month.pap_tax;
+            rows.push(RowStatement {
+                year_month: year_month.clone(),
+                item: Text::PapTax,
+                character: String::new(),
+                date_time: None,
+                pap: Some(month.pap()),
+                amount: Some(month.pap_tax),
+                balance: Some(balance),
+            });
+
+            for (name, pap) in &month.character_paps {
+                rows.push(RowStatement {
+                    year_month: year_month.clone(),
+                    item: Text::Pap,
+                    character: name.clone(),
+                    date_time: None,
+                    pap: Some(*pap),
+                    amount: None,
+                    balance: None,
+                });
+            }
+
+            for payment in &month.payments {
+                balance -= payment.amount;
+                rows.push(RowStatement {
+                    year_month: year_month.clone(),
+                    item: Text::PaidUpTax,
+                    character: payment.character.clone(),
+                    date_time: Some(payment.date_time),
+                    pap: None,
+                    amount: Some(-payment.amount),
+                    balance: Some(balance),
+                });
+            }
+        }
+        rows
+    }
+
+    pub fn insert_worksheet(&self, w: &mut Worksheet, lang: Lang) {
+        // 插入标题
+        for column in ColumnStatement::iter() {
+            let cell = w.get_cell_mut((column as u32, 1));
+            cell.set_value_string(lang.text(column.text()));
+            let mut alignment = Alignment::default();
+            alignment.set_horizontal(HorizontalAlignmentValues::Center);
+            cell.get_style_mut().set_alignment(alignment);
+        }
+
+        // 插入数据
+        for (i, data) in self.rows(lang).iter().enumerate() {
+            let row = (i + 2) as u32;
+            for column in ColumnStatement::iter() {
+                let cell = w.get_cell_mut((column as u32, row));
+                let mut style = column.get_style();
+                if data.amount.is_some_and(|a| a.is_sign_negative()) {
+                    // 缴税标绿
+                    style.set_background_color(HIGHLIGHT_GREEN);
+                }
+                cell.set_style(style);
+
+                match column {
+                    ColumnStatement::YearMonth => {
+                        cell.set_value_string(data.year_month.as_str());
+                    }
+                    ColumnStatement::Item => {
+                        cell.set_value_string(lang.text(data.item));
+                    }
+                    ColumnStatement::Character => {
+                        cell.set_value_string(data.character.as_str());
+                    }
+                    ColumnStatement::DateTime => {
+                        if let Some(date_time) = data.date_time {
+                            cell.set_value_number(excel_datetime(&date_time));
+                        }
+                    }
+                    ColumnStatement::Pap => {
+                        if let Some(pap) = data.pap {
+                            cell.set_value_number(pap.to_f64().unwrap());
+                        }
+                    }
+                    ColumnStatement::Amount => {
+                        if let Some(amount) = data.amount {
+                            cell.set_value_number(amount.to_f64().unwrap());
+                        }
+                    }
+                    ColumnStatement::Balance => {
+                        if let Some(balance) = data.balance {
+                            cell.set_value_number(balance.to_f64().unwrap());
+                            if balance > Decimal::ZERO {
+                                // 欠税标红
+                                cell.get_style_mut().set_background_color(HIGHLIGHT_RED);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(portrait) = &self.portrait {
+            let name_column = ColumnStatement::COUNT as u32 + 1;
+            for (col, title) in [
+                (name_column, Text::MainCharacter),
+                (name_column + 1, Text::Portrait),
+            ] {
+                let cell = w.get_cell_mut((col, 1));
+                cell.set_value_string(lang.text(title));
+                let mut alignment = Alignment::default();
+                alignment.set_horizontal(HorizontalAlignmentValues::Center);
+                cell.get_style_mut().set_alignment(alignment);
+            }
+            w.get_cell_mut((name_column, 2))
+                .set_value_string(self.character_name.as_str());
+            w.get_column_dimension_mut(&string_from_column_index(&(name_column + 1)))
+                .set_width(EMBEDDED_PORTRAIT_COLUMN_WIDTH);
+            w.get_row_dimension_mut(&2)
+                .set_height(EMBEDDED_PORTRAIT_ROW_HEIGHT);
+            if let Err(e) = embed_image(w, name_column + 1, 2, portrait) {
+                println!("embed portrait of user {}: {}", self.user_id, e);
+            }
+        }
+    }
+
+    // 生成适合粘贴到聊天窗口的纯文本对账单
+    pub fn to_text(&self, lang: Lang) -> String {
+        let mut lines = Vec::new();
+        lines.push(format!(
+            "【{}】{}",
+            lang.text(Text::TaxStatement),
+            self.character_name
+        ));
+
+        for month in &self.months {
+            lines.push(String::new());
+            lines.push(lang.year_month(month.year_month));
+            lines.push(format!(
+                "  {}: {}",
+                lang.text(Text::PollTax),
+                format_isk_text(month.poll_tax)
+            ));
+            lines.push(format!(
+                "  {}: {} (PAP {})",
+                lang.text(Text::PapTax),
+                format_isk_text(month.pap_tax),
+                month.pap().round_dp(2)
+            ));
+            for (name, pap) in &month.character_paps {
+                lines.push(format!("    {} PAP {}", name, pap.round_dp(2)));
+            }
+            for payment in &month.payments {
+                lines.push(format!(
+                    "  {}: {} {} {}",
+                    lang.text(Text::PaidUpTax),
+                    payment.date_time.format("%Y-%m-%d %H:%M:%S"),
+                    payment.character,
+                    format_isk_text(payment.amount)
+                ));
+            }
+            lines.push(format!(
+                "  {}: {}",
+                lang.text(Text::ArrearsBalance),
+                format_isk_text(month.balance)
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "{}: {}",
+            lang.text(Text::UnpaidTax),
+            format_isk_text(self.balance())
+        ));
+        lines.join("\n")
+    }
+
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        user_id: i32,
+        offset: FixedOffset,
+    ) -> Result<UserStatement, String> {
+        let character_name = get_user_main_character_name(db, user_id).await?;
+        let characters = get_user_characters(db, user_id).await?;
+        let character_names: BTreeMap<i64, String> = characters.iter().cloned().collect();
+
+        // 对账单覆盖登记过应税月份及有缴税流水的所有月份
+        let taxable_year_months = get_user_taxable_year_months(db, user_id).await?;
+        let journal = find_user_pay_tax_journal(db, user_id).await?;
+
+        let mut payments: BTreeMap<YearMonth, Vec<Payment>> = BTreeMap::new();
+        for item in journal {
+            let ym = YearMonth::from_datetime(&item.date_time, offset);
+            let character = character_names
+                .get(&item.character_id)
+                .cloned()
+                .unwrap_or_else(|| item.character_id.to_string());
+            payments.entry(ym).or_default().push(Payment {
+                date_time: item.date_time.with_timezone(&offset),
+                character,
+                amount: item.amount,
+            });
+        }
+
+        let start = taxable_year_months
+            .first()
+            .into_iter()
+            .chain(payments.keys().next())
+            .min()
+            .copied();
+        let end = taxable_year_months
+            .last()
+            .into_iter()
+            .chain(payments.keys().next_back())
+            .max()
+            .copied();
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                return Err(format!("no tax records found for user {}", user_id));
+            }
+        };
+
+        let ledger = TaxLedger::select_from_db(db, start, end, offset).await?;
+        let mut months = Vec::new();
+        let mut balance = Decimal::ZERO;
+        for ym in RangeYearMonth::new(start, end) {
+            let (poll_tax, pap_tax) = ledger.user_tax(user_id, ym)?;
+
+            let mut character_paps = Vec::with_capacity(characters.len());
+            for (character_id, name) in &characters {
+                let pap = ledger.character_pap(*character_id, ym);
+                if pap.is_zero() == false {
+                    character_paps.push((name.clone(), pap));
+                }
+            }
+
+            let payments = payments.remove(&ym).unwrap_or_default();
+            let paid: Decimal = payments.iter().map(|p| p.amount).sum();
+            balance += poll_tax + pap_tax - paid;
+
+            months.push(MonthStatement {
+                year_month: ym,
+                poll_tax,
+                pap_tax,
+                character_paps,
+                payments,
+                balance,
+            });
+        }
+
+        Ok(UserStatement {
+            user_id,
+            character_name,
+            months,
+            portrait: None,
+        })
+    }
+}
+
+// Excel 的日期时间序列值, 使用 t 所在时区的本地时间
+pub fn excel_datetime<Tz: TimeZone>(t: &DateTime<Tz>) -> f64 {
+    let date = t.date_naive().to_epoch_days() as f64;
+    let time = t.time().num_seconds_from_midnight() as f64;
+    25569.0 + date + (time / (3600.0 * 24.0))
+}
+
+// 格式化为带千分位的整数 isk 文本, 例如 "-1,234,567 isk"
+pub fn format_isk_text(d: Decimal) -> String {
+    let d = d.round();
+    let digits = d.abs().to_string();
+    let mut s = String::with_capacity(digits.len() + digits.len() / 3 + 5);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            s.push(',');
+        }
+        s.push(c);
+    }
+    if d.is_sign_negative() && d.is_zero() == false {
+        s.insert(0, '-');
+    }
+    s.push_str(" isk");
+    s
+}
+
+#[test]
+fn test_format_isk_text() {
+    assert_eq!(format_isk_text(Decimal::ZERO), "0 isk");
+    assert_eq!(format_isk_text(Decimal::new(999, 0)), "999 isk");
+    assert_eq!(format_isk_text(Decimal::new(1000, 0)), "1,000 isk");
+    assert_eq!(format_isk_text(Decimal::new(123456789, 2)), "1,234,568 isk");
+    assert_eq!(
+        format_isk_text(Decimal::new(-50000000, 0)),
+        "-50,000,000 isk"
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_statement_time_zone() {
+    use crate::{
+        db_op::{db_upgrade_wall_journal, test_tax_database},
+        esi::{ResCorporationWalletJournal, ResCorporationWalletJournalItem},
+        notify::EventQueue,
+    };
+    use db_wallet::JournalRefType;
+    use umya_spreadsheet::new_file_empty_worksheet;
+
+    let db = test_tax_database().await;
+    // UTC 8月31日 17:00 的缴税为 UTC+8 的 9月1日 01:00
+    let item = ResCorporationWalletJournalItem {
+        id: 4,
+        date: DateTime::parse_from_rfc3339("2025-08-31T17:00:00Z")
+            .unwrap()
+            .to_utc(),
+        ref_type: JournalRefType::PlayerDonation,
+        description: String::new(),
+        amount: Some(Decimal::from(100)),
+        balance: Some(Decimal::from(400)),
+        context_id: None,
+        context_id_type: None,
+        reason: None,
+        first_party_id: Some(1001),
+        second_party_id: Some(98000001),
+        tax: None,
+        tax_receiver_id: None,
+    };
+    let mut events = EventQueue::new(Decimal::ZERO);
+    db_upgrade_wall_journal(&db, ResCorporationWalletJournal(vec![item]), &mut events)
+        .await
+        .unwrap();
+
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let statement = UserStatement::select_from_db(&db, 1, utc8).await.unwrap();
+    assert_eq!(statement.months[0].year_month, YearMonth::new(2025, 9));
+    let payment = &statement.months[0].payments[0];
+    assert_eq!(payment.date_time.to_rfc3339(), "2025-09-01T01:00:00+08:00");
+    assert!(
+        statement
+            .to_text(Lang::Zh)
+            .contains("实缴税额: 2025-09-01 01:00:00 c1001 100 isk")
+    );
+
+    // 英文对账单
+    let text = statement.to_text(Lang::En);
+    assert!(text.starts_with("【Tax Statement】c1001\n\nSep 2025\n"));
+    assert!(text.contains("Paid Tax: 2025-09-01 01:00:00 c1001 100 isk"));
+
+    // xlsx 中同为 UTC+8 的时间
+    let mut book = new_file_empty_worksheet();
+    let w = book.new_sheet(Lang::En.text(Text::SheetStatement)).unwrap();
+    statement.insert_worksheet(w, Lang::En);
+    assert_eq!(w.get_value((ColumnStatement::Item as u32, 1)), "Item");
+    assert_eq!(
+        w.get_value((ColumnStatement::YearMonth as u32, 2)),
+        "Sep 2025"
+    );
+    let row = (2..=w.get_highest_row())
+        .find(|row| w.get_value((ColumnStatement::Item as u32, *row)) == "Paid Tax")
+        .unwrap();
+    let cell = w.get_cell((ColumnStatement::DateTime as u32, row)).unwrap();
+    let expected = excel_datetime(
+        &chrono::NaiveDate::from_ymd_opt(2025, 9, 1)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap()
+            .and_utc(),
+    );
+    assert_eq!(cell.get_value_number(), Some(expected));
+}
@@ -0,0 +1,454 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, QueryFilter, QuerySelect,
+};
+use std::{collections::BTreeMap, str::FromStr};
+use umya_spreadsheet::{
+    Alignment, HorizontalAlignmentValues, VerticalAlignmentValues, Worksheet,
+    helper::coordinate::string_from_column_index,
+};
+
+use crate::{
+    db_op::{RangeYearMonth, YearMonth, decimal_from_i64},
+    locale::{Lang, Text},
+    period::ReportPeriod,
+    render::{Table, TableColumn, Value},
+    template::ReportTemplate,
+};
+use db_wallet::{
+    JournalRefType,
+    entities::corporation_wallet_journal::{
+        Column as CCorporationWalletJournal, Entity as ECorporationWalletJournal,
+    },
+};
+
+// 每个月份占用的列: 收入, 支出, 净额, 环比
+const MONTH_COLUMNS: [(&str, Text); 4] = [
+    ("income", Text::Income),
+    ("expense", Text::Expense),
+    ("net", Text::Net),
+    ("change", Text::Change),
+];
+// 合计占用的列: 收入, 支出, 净额
+const TOTAL_COLUMNS: [(&str, Text); 3] = [
+    ("income", Text::Income),
+    ("expense", Text::Expense),
+    ("net", Text::Net),
+];
+
+// 流水类型到汇总分类的映射, 未配置的类型以其自身名称作为分类
+#[derive(Default)]
+pub struct SummaryCategories {
+    map: BTreeMap<i32, String>,
+}
+
+impl SummaryCategories {
+    // 从 json 解析, 格式为 {"分类名": ["ref_type", ...]}, ref_type 使用 snake_case 名称
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let groups: BTreeMap<String, Vec<String>> =
+            serde_json::from_str(text).map_err(|e| e.to_string())?;
+
+        let mut map = BTreeMap::new();
+        for (category, ref_types) in groups {
+            for ref_type in ref_types {
+                let r = JournalRefType::from_str(ref_type.as_str())
+                    .map_err(|_| format!("unknown ref_type: {}", ref_type))?;
+                if let Some(old) = map.insert(r as i32, category.clone()) {
+                    return Err(format!(
+                        "ref_type {} is in both {} and {}",
+                        ref_type, old, category
+                    ));
+                }
+            }
+        }
+
+        Ok(SummaryCategories { map })
+    }
+
+    fn category(&self, ref_type: JournalRefType) -> Category {
+        match self.map.get(&(ref_type as i32)) {
+            Some(category) => Category::Named(category.clone()),
+            None => Category::RefType(ref_type),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+struct MonthFlow {
+    income: Decimal,  // 收入, 正数
+    expense: Decimal, // 支出, 负数
+}
+
+impl MonthFlow {
+    fn add(&mut self, amount: Decimal) {
+        if amount.is_sign_negative() {
+            self.expense += amount;
+        } else {
+            self.income += amount;
+        }
+    }
+
+    fn merge(&mut self, other: &MonthFlow) {
+        self.income += other.income;
+        self.expense += other.expense;
+    }
+
+    fn net(&self) -> Decimal {
+        self.income + self.expense
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Category {
+    Named(String),           // 配置文件中的分类
+    RefType(JournalRefType), // 未配置的流水类型
+    Total,                   // 合计行
+}
+
+impl Category {
+    fn label(&self, lang: Lang) -> String {
+        match self {
+            Category::Named(name) => name.clone(),
+            Category::RefType(ref_type) => lang.ref_type(*ref_type).to_string(),
+            Category::Total => lang.text(Text::Total).to_string(),
+        }
+    }
+}
+
+struct RowSummary {
+    category: Category,
+    months: BTreeMap<YearMonth, MonthFlow>,
+}
+
+impl RowSummary {
+    fn total(&self) -> MonthFlow {
+        let mut total = MonthFlow::default();
+        for flow in self.months.values() {
+            total.merge(flow);
+        }
+        total
+    }
+}
+
+pub struct SheetSummary {
+    start: YearMonth,
+    end: YearMonth,
+    data: Vec<RowSummary>,
+}
+
+impl SheetSummary {
+    // 按分类与月份汇总, 分类按收支总额降序, 月份按报表时区划分
+    fn from_journals(
+        period: &ReportPeriod,
+        journals: &[(DateTime<Utc>, JournalRefType, Decimal)],
+        categories: &SummaryCategories,
+    ) -> SheetSummary {
+        let (start, end) = (period.start_month(), period.end_month());
+        let mut rows: BTreeMap<Category, BTreeMap<YearMonth, MonthFlow>> = BTreeMap::new();
+        for (date_time, ref_type, amount) in journals {
+            let ym = YearMonth::from_datetime(date_time, period.offset);
+            let months = rows
+                .entry(categories.category(*ref_type))
+                .or_insert_with(|| {
+                    RangeYearMonth::new(start, end)
+                        .map(|ym| (ym, MonthFlow::default()))
+                        .collect()
+                });
+            if let Some(flow) = months.get_mut(&ym) {
+                flow.add(*amount);
+            }
+        }
+
+        let mut data: Vec<RowSummary> = rows
+            .into_iter()
+            .map(|(category, months)| RowSummary { category, months })
+            .collect();
+        data.sort_by_key(|r| {
+            let total = r.total();
+            std::cmp::Reverse(total.income - total.expense)
+        });
+
+        SheetSummary { start, end, data }
+    }
+
+    // 所有分类的逐月合计
+    fn grand_total(&self) -> RowSummary {
+        let mut months: BTreeMap<YearMonth, MonthFlow> = RangeYearMonth::new(self.start, self.end)
+            .map(|ym| (ym, MonthFlow::default()))
+            .collect();
+        for row in &self.data {
+            for (ym, flow) in &row.months {
+                if let Some(total) = months.get_mut(ym) {
+                    total.merge(flow);
+                }
+            }
+        }
+        RowSummary {
+            category: Category::Total,
+            months,
+        }
+    }
+
+    pub fn to_table(&self, lang: Lang) -> Table {
+        let mut columns = vec![TableColumn::new("category", lang.text(Text::Category))];
+        for ym in RangeYearMonth::new(self.start, self.end) {
+            for (key, text) in MONTH_COLUMNS {
+                columns.push(TableColumn::new(
+                    format!("{}.{}", ym.to_key(), key),
+                    format!("{} {}", lang.year_month(ym), lang.text(text)),
+                ));
+            }
+        }
+        for (key, text) in TOTAL_COLUMNS {
+            columns.push(TableColumn::new(
+                format!("total.{}", key),
+                format!("{} {}", lang.text(Text::Total), lang.text(text)),
+            ));
+        }
+
+        let grand_total = self.grand_total();
+        let rows = self
+            .data
+            .iter()
+            .chain([&grand_total])
+            .map(|data| {
+                let mut row = vec![Value::Text(data.category.label(lang))];
+                row.extend(row_values(data).into_iter().map(|v| match v {
+                    Some(v) => Value::Isk(v),
+                    None => Value::Empty,
+                }));
+                row
+            })
+            .collect();
+
+        Table {
+            key: "summary",
+            title: lang.text(Text::SheetSummary).to_string(),
+            columns,
+            rows,
+        }
+    }
+
+    // 各分类每月收入所在的单元格 (列, 行), 不含合计行
+    pub fn income_cells(&self, lang: Lang) -> Vec<(String, Vec<(u32, u32)>)> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let cells = (0..row.months.len())
+                    .map(|m| (m as u32 * MONTH_COLUMNS.len() as u32 + 2, i as u32 + 3))
+                    .collect();
+                (row.category.label(lang), cells)
+            })
+            .collect()
+    }
+
+    pub fn insert_worksheet(&self, w: &mut Worksheet, lang: Lang, template: &ReportTemplate) {
+        self.generate_sheet_header(w, lang);
+
+        for (i, row) in self.data.iter().enumerate() {
+            generate_sheet_row(w, i as u32 + 3, row, false, lang, template);
+        }
+        let grand_total = self.grand_total();
+        let row = self.data.len() as u32 + 3;
+        generate_sheet_row(w, row, &grand_total, true, lang, template);
+    }
+
+    fn generate_sheet_header(&self, w: &mut Worksheet, lang: Lang) {
+        let mut alignment = Alignment::default();
+        alignment.set_horizontal(HorizontalAlignmentValues::Center);
+        alignment.set_vertical(VerticalAlignmentValues::Center);
+
+        let c = w.get_cell_mut("A1");
+        c.set_value_string(lang.text(Text::Category));
+        c.get_style_mut().set_alignment(alignment.clone());
+        w.add_merge_cells("A1:A2");
+
+        let mut groups: Vec<(String, &[(&str, Text)])> = RangeYearMonth::new(self.start, self.end)
+            .map(|ym| (lang.year_month(ym), &MONTH_COLUMNS[..]))
+            .collect();
+        groups.push((lang.text(Text::Total).to_string(), &TOTAL_COLUMNS[..]));
+
+        let mut col = 2;
+        for (title, columns) in groups {
+            let c = w.get_cell_mut((col, 1));
+            c.set_value_string(title);
+            c.get_style_mut().set_alignment(alignment.clone());
+            let start_col = string_from_column_index(&col);
+            let end_col = string_from_column_index(&(col + columns.len() as u32 - 1));
+            w.add_merge_cells(format!("{}1:{}1", start_col, end_col));
+
+            for (i, (_, text)) in columns.iter().enumerate() {
+                let c = w.get_cell_mut((col + i as u32, 2));
+                c.set_value_string(lang.text(*text));
+                c.get_style_mut().set_alignment(alignment.clone());
+            }
+            col += columns.len() as u32;
+        }
+    }
+
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        period: &ReportPeriod,
+        categories: &SummaryCategories,
+    ) -> Result<SheetSummary, String> {
+        #[derive(FromQueryResult)]
+        struct Journal {
+            date: i64,
+            ref_type: i32,
+            amount: Option<i64>,
+        }
+
+        let journals = ECorporationWalletJournal::find()
+            .select_only()
+            .column(CCorporationWalletJournal::Date)
+            .column(CCorporationWalletJournal::RefType)
+            .column(CCorporationWalletJournal::Amount)
+            .filter(
+                Condition::all()
+                    .add(CCorporationWalletJournal::Date.gte(period.lower().timestamp()))
+                    .add(CCorporationWalletJournal::Date.lt(period.upper().timestamp())),
+            )
+            .into_model::<Journal>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut data = Vec::with_capacity(journals.len());
+        for journal in journals {
+            let date_time = DateTime::from_timestamp_secs(journal.date).unwrap();
+            let ref_type = JournalRefType::from_repr(journal.ref_type)
+                .ok_or(format!("unknown ref_type: {}", journal.ref_type))?;
+            let amount = decimal_from_i64(journal.amount.unwrap_or(0));
+            data.push((date_time, ref_type, amount));
+        }
+
+        Ok(SheetSummary::from_journals(period, &data, categories))
+    }
+}
+
+// 依次为各月 收入, 支出, 净额, 环比 以及合计 收入, 支出, 净额
+fn row_values(data: &RowSummary) -> Vec<Option<Decimal>> {
+    let mut values = Vec::new();
+    let mut previous: Option<Decimal> = None;
+    for flow in data.months.values() {
+        values.push(Some(flow.income));
+        values.push(Some(flow.expense));
+        values.push(Some(flow.net()));
+        // 首月没有上月数据, 环比留空
+        values.push(previous.map(|p| flow.net() - p));
+        previous = Some(flow.net());
+    }
+    let total = data.total();
+    values.extend([Some(total.income), Some(total.expense), Some(total.net())]);
+    values
+}
+
+fn generate_sheet_row(
+    w: &mut Worksheet,
+    row: u32,
+    data: &RowSummary,
+    bold: bool,
+    lang: Lang,
+    template: &ReportTemplate,
+) {
+    let c = w.get_cell_mut((1, row));
+    c.set_value_string(data.category.label(lang));
+    if bold {
+        c.get_style_mut().get_font_mut().set_bold(true);
+    }
+
+    for (i, value) in row_values(data).into_iter().enumerate() {
+        let c = w.get_cell_mut((i as u32 + 2, row));
+        if let Some(v) = value {
+            c.set_value_number(v.to_f64().unwrap());
+            // 汇总表没有固定列, 规则对每个金额单元格生效
+            if let Some(color) = template.summary.highlight("", None, Some(v), Some(v)) {
+                c.get_style_mut().set_background_color(color);
+            }
+        }
+        c.get_style_mut()
+            .set_numbering_format(template.isk_format());
+        if bold {
+            c.get_style_mut().get_font_mut().set_bold(true);
+        }
+    }
+}
+
+#[test]
+fn test_summary() {
+    let categories = SummaryCategories::from_json(
+        r#"{"任务": ["agent_mission_reward", "agent_mission_time_bonus_reward"]}"#,
+    )
+    .unwrap();
+    let t = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+    let journals = vec![
+        (
+            t("2025-08-03T00:00:00Z"),
+            JournalRefType::AgentMissionReward,
+            Decimal::from(100),
+        ),
+        (
+            t("2025-08-04T00:00:00Z"),
+            JournalRefType::AgentMissionTimeBonusReward,
+            Decimal::from(50),
+        ),
+        (
+            t("2025-09-01T00:00:00Z"),
+            JournalRefType::AgentMissionReward,
+            Decimal::from(30),
+        ),
+        (
+            t("2025-09-02T00:00:00Z"),
+            JournalRefType::CorporationAccountWithdrawal,
+            Decimal::from(-500),
+        ),
+        (
+            t("2025-09-03T00:00:00Z"),
+            JournalRefType::CorporationAccountWithdrawal,
+            Decimal::from(20),
+        ),
+    ];
+    let utc = chrono::FixedOffset::east_opt(0).unwrap();
+    let period = ReportPeriod::from_range("2025-08", "2025-09", utc).unwrap();
+    let sheet = SheetSummary::from_journals(&period, &journals, &categories);
+    let (start, end) = (period.start_month(), period.end_month());
+
+    // 按收支总额降序
+    let names: Vec<String> = sheet
+        .data
+        .iter()
+        .map(|r| r.category.label(Lang::Zh))
+        .collect();
+    assert_eq!(names, vec!["军团账户支取", "任务"]);
+    let names: Vec<String> = sheet
+        .data
+        .iter()
+        .map(|r| r.category.label(Lang::En))
+        .collect();
+    assert_eq!(names, vec!["Corporation Account Withdrawal", "任务"]);
+
+    let mission = &sheet.data[1];
+    assert_eq!(mission.months[&start].income, Decimal::from(150));
+    assert_eq!(mission.months[&end].income, Decimal::from(30));
+    assert_eq!(mission.total().net(), Decimal::from(180));
+
+    let withdrawal = &sheet.data[0];
+    assert_eq!(withdrawal.months[&start], MonthFlow::default());
+    assert_eq!(withdrawal.months[&end].expense, Decimal::from(-500));
+    assert_eq!(withdrawal.months[&end].net(), Decimal::from(-480));
+
+    let grand_total = sheet.grand_total();
+    assert_eq!(grand_total.months[&start].net(), Decimal::from(150));
+    assert_eq!(grand_total.months[&end].net(), Decimal::from(-450));
+    assert_eq!(grand_total.total().expense, Decimal::from(-500));
+}
+
+#[test]
+fn test_summary_categories() {
+    let r = SummaryCategories::from_json(r#"{"a": ["player_donation"], "b": ["player_donation"]}"#);
+    assert!(r.is_err());
+    let r = SummaryCategories::from_json(r#"{"a": ["no_such_type"]}"#);
+    assert!(r.is_err());
+}
@@ -0,0 +1,375 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path};
+use strum::IntoEnumIterator;
+use umya_spreadsheet::NumberingFormat;
+
+use crate::{
+    locale::{Lang, Text},
+    report::ColumnWalletJournal,
+    verify::ColumnVerify,
+};
+use db_wallet::JournalRefType;
+
+// 默认模板, 即原有的报表布局
+const DEFAULT_TEMPLATE: &str = include_str!("../report_template.toml");
+
+// 税收清单可设置列宽的列, 各月的税额列使用相同列宽
+const TAX_LIST_COLUMNS: [&str; 6] = [
+    "character_name",
+    "unpaid_tax",
+    "pap_tax",
+    "poll_tax",
+    "paid_up_tax",
+    "note",
+];
+
+// 报表模板, 描述工作表名, 列, 数字格式与高亮规则
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReportTemplate {
+    pub isk_format: String,
+    pub date_time_format: String,
+    pub wallet_journal: SheetTemplate,
+    pub tax_list: SheetTemplate,
+    pub summary: SheetTemplate,
+    pub journal_gaps: SheetTemplate,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SheetTemplate {
+    pub name: Option<String>, // 工作表名, 为 None 时按语言使用默认名称
+    pub columns: Vec<ColumnTemplate>,
+    pub highlights: Vec<HighlightRule>,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnTemplate {
+    pub key: String,
+    #[serde(default)]
+    pub width: Option<f64>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Sign {
+    Positive,
+    Negative,
+}
+
+// 条件高亮, 所有条件均满足时生效
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct HighlightRule {
+    pub color: String, // ARGB
+    #[serde(default)]
+    pub column: Option<String>, // 为 None 时对整行生效
+    #[serde(default)]
+    pub ref_types: Vec<String>, // 流水类型, snake_case
+    #[serde(default)]
+    pub sign: Option<Sign>,
+    #[serde(default)]
+    pub above: Option<Decimal>,
+    #[serde(default)]
+    pub below: Option<Decimal>,
+}
+
+impl Default for ReportTemplate {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_TEMPLATE).unwrap()
+    }
+}
+
+impl ReportTemplate {
+    // 按扩展名读取 toml 或 json 模板, 未出现的部分使用默认模板
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let is_json = path.extension().is_some_and(|e| e == "json");
+        ReportTemplate::from_text(text.as_str(), is_json)
+    }
+
+    fn from_text(text: &str, is_json: bool) -> Result<Self, String> {
+        let value: serde_json::Value = if is_json {
+            serde_json::from_str(text).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(text).map_err(|e| e.to_string())?
+        };
+        let mut merged: serde_json::Value = toml::from_str(DEFAULT_TEMPLATE).unwrap();
+        merge(&mut merged, value);
+
+        let template: ReportTemplate = serde_json::from_value(merged).map_err(|e| e.to_string())?;
+        template.validate()?;
+        Ok(template)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let journal_columns: Vec<&str> = ColumnWalletJournal::iter().map(|c| c.key()).collect();
+        self.wallet_journal
+            .validate("wallet_journal", &journal_columns)?;
+        self.tax_list.validate("tax_list", &TAX_LIST_COLUMNS)?;
+        self.summary.validate("summary", &[])?;
+        let verify_columns: Vec<&str> = ColumnVerify::iter().map(|c| c.key()).collect();
+        self.journal_gaps.validate("journal_gaps", &verify_columns)
+    }
+
+    pub fn isk_format(&self) -> NumberingFormat {
+        NumberingFormat::default()
+            .set_format_code(self.isk_format.as_str())
+            .to_owned()
+    }
+
+    pub fn date_time_format(&self) -> NumberingFormat {
+        NumberingFormat::default()
+            .set_format_code(self.date_time_format.as_str())
+            .to_owned()
+    }
+
+    // 流水工作表输出的列, 未配置时输出全部列
+    pub fn wallet_journal_columns(&self) -> Vec<ColumnWalletJournal> {
+        if self.wallet_journal.columns.is_empty() {
+            return ColumnWalletJournal::iter().collect();
+        }
+        self.wallet_journal
+            .columns
+            .iter()
+            .filter_map(|c| ColumnWalletJournal::iter().find(|column| column.key() == c.key))
+            .collect()
+    }
+}
+
+impl SheetTemplate {
+    fn validate(&self, sheet: &str, keys: &[&str]) -> Result<(), String> {
+        let mut columns = BTreeSet::new();
+        for column in &self.columns {
+            if keys.contains(&column.key.as_str()) == false {
+                return Err(format!("{}: unknown column {}", sheet, column.key));
+            }
+            if columns.insert(column.key.as_str()) == false {
+                return Err(format!("{}: duplicated column {}", sheet, column.key));
+            }
+        }
+
+        for rule in &self.highlights {
+            let is_argb =
+                rule.color.len() == 8 && rule.color.chars().all(|c| c.is_ascii_hexdigit());
+            if is_argb == false {
+                return Err(format!("{}: color {} is not ARGB", sheet, rule.color));
+            }
+            if let Some(column) = &rule.column {
+                if keys.contains(&column.as_str()) == false {
+                    return Err(format!("{}: unknown column {}", sheet, column));
+                }
+            }
+            for ref_type in &rule.ref_types {
+                if JournalRefType::iter().any(|r| r.as_ref() == ref_type) == false {
+                    return Err(format!("{}: unknown ref type {}", sheet, ref_type));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn name(&self, lang: Lang, text: Text) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => lang.text(text).to_string(),
+        }
+    }
+
+    pub fn width(&self, key: &str) -> Option<f64> {
+        self.columns.iter().find(|c| c.key == key)?.width
+    }
+
+    // 单元格的高亮颜色, 使用首个匹配的规则
+    // row_value 为整行规则判断的金额, cell_value 为本单元格的金额
+    pub fn highlight(
+        &self,
+        column: &str,
+        ref_type: Option<JournalRefType>,
+        row_value: Option<Decimal>,
+        cell_value: Option<Decimal>,
+    ) -> Option<&str> {
+        for rule in &self.highlights {
+            let value = match &rule.column {
+                None => row_value,
+                Some(c) if c == column => cell_value,
+                Some(_) => None,
+            };
+            if let Some(value) = value {
+                if rule.matches(ref_type, value) {
+                    return Some(rule.color.as_str());
+                }
+            }
+        }
+        None
+    }
+}
+
+impl HighlightRule {
+    fn matches(&self, ref_type: Option<JournalRefType>, value: Decimal) -> bool {
+        if self.ref_types.is_empty() == false {
+            let in_ref_types =
+                ref_type.is_some_and(|r| self.ref_types.iter().any(|t| t == r.as_ref()));
+            if in_ref_types == false {
+                return false;
+            }
+        }
+        let sign = match self.sign {
+            Some(Sign::Positive) => value > Decimal::ZERO,
+            Some(Sign::Negative) => value < Decimal::ZERO,
+            None => true,
+        };
+        sign && self.above.is_none_or(|above| value > above)
+            && self.below.is_none_or(|below| value < below)
+    }
+
+    // 与 matches 等价的 Excel 条件格式公式, cell 为判断金额所在的单元格
+    // 限定流水类型的规则无法用公式表达, 返回 None
+    pub fn formula(&self, cell: &str) -> Option<String> {
+        if self.ref_types.is_empty() == false {
+            return None;
+        }
+        let mut conditions = Vec::new();
+        match self.sign {
+            Some(Sign::Positive) => conditions.push(format!("{}>0", cell)),
+            Some(Sign::Negative) => conditions.push(format!("{}<0", cell)),
+            None => {}
+        }
+        if let Some(above) = self.above {
+            conditions.push(format!("{}>{}", cell, above));
+        }
+        if let Some(below) = self.below {
+            conditions.push(format!("{}<{}", cell, below));
+        }
+        match conditions.len() {
+            0 => Some("TRUE".to_string()),
+            1 => conditions.pop(),
+            _ => Some(format!("AND({})", conditions.join(","))),
+        }
+    }
+}
+
+// 以 patch 覆盖 base, 对象逐键合并, 其余类型直接替换
+fn merge(base: &mut serde_json::Value, patch: serde_json::Value) {
+    match (base, patch) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+#[test]
+fn test_template() {
+    let template = ReportTemplate::default();
+    template.validate().unwrap();
+    assert_eq!(
+        template.wallet_journal_columns().len(),
+        ColumnWalletJournal::iter().count()
+    );
+
+    // 与原有的高亮规则相同
+    let journal = &template.wallet_journal;
+    let donation = Some(JournalRefType::PlayerDonation);
+    let amount = |v: i64| Some(Decimal::from(v));
+    assert_eq!(
+        journal.highlight("amount", donation, amount(-1), amount(-1)),
+        Some("FFFFC7CE")
+    );
+    assert_eq!(
+        journal.highlight("party", donation, amount(1), None),
+        Some("FFC6EFCE")
+    );
+    let bounty = Some(JournalRefType::BountyPrizes);
+    assert_eq!(
+        journal.highlight("amount", bounty, amount(1), amount(1)),
+        None
+    );
+    let tax_list = &template.tax_list;
+    assert_eq!(
+        tax_list.highlight("unpaid_tax", None, amount(1), amount(1)),
+        Some("FFFFC7CE")
+    );
+    assert_eq!(
+        tax_list.highlight("unpaid_tax", None, amount(0), amount(0)),
+        None
+    );
+    assert_eq!(
+        tax_list.highlight("pap_tax", None, amount(1), amount(1)),
+        None
+    );
+    assert_eq!(
+        template.summary.highlight("", None, amount(-1), amount(-1)),
+        Some("FFFFC7CE")
+    );
+    assert_eq!(
+        template.summary.highlight("", None, amount(0), amount(0)),
+        None
+    );
+    let gaps = &template.journal_gaps;
+    assert_eq!(
+        gaps.highlight("missing_amount", None, amount(-5), amount(-5)),
+        Some("FFFFC7CE")
+    );
+    assert_eq!(gaps.highlight("balance", None, amount(-5), amount(1)), None);
+
+    // 部分覆盖, 其余使用默认值
+    let toml = r#"
+        [wallet_journal]
+        name = "流水"
+        columns = [{ key = "amount", width = 20 }, { key = "date_time" }]
+    "#;
+    let custom = ReportTemplate::from_text(toml, false).unwrap();
+    assert_eq!(
+        custom
+            .wallet_journal
+            .name(Lang::En, Text::SheetWalletJournal),
+        "流水"
+    );
+    assert_eq!(custom.wallet_journal.width("amount"), Some(20.0));
+    assert_eq!(custom.wallet_journal.highlights, journal.highlights);
+    assert_eq!(custom.tax_list, template.tax_list);
+    assert_eq!(custom.wallet_journal_columns().len(), 2);
+
+    let json = r#"{"tax_list": {"highlights": [{"color": "FF00FF00", "below": -1.5}]}}"#;
+    let custom = ReportTemplate::from_text(json, true).unwrap();
+    assert_eq!(
+        custom.tax_list.highlight("pap_tax", None, amount(-2), None),
+        Some("FF00FF00")
+    );
+    assert_eq!(
+        custom.tax_list.highlight("pap_tax", None, amount(-1), None),
+        None
+    );
+    assert_eq!(custom.tax_list.columns, template.tax_list.columns);
+
+    // 条件格式公式
+    assert_eq!(
+        template.tax_list.highlights[0].formula("C3").as_deref(),
+        Some("C3>0")
+    );
+    assert_eq!(
+        custom.tax_list.highlights[0].formula("$C3").as_deref(),
+        Some("$C3<-1.5")
+    );
+    assert_eq!(journal.highlights[1].formula("C2"), None);
+
+    assert!(
+        ReportTemplate::from_text(r#"{"summary": {"columns": [{"key": "x"}]}}"#, true).is_err()
+    );
+    let unknown = r#"[[wallet_journal.highlights]]
+        color = "FF00FF00"
+        ref_types = ["not_a_ref_type"]"#;
+    assert!(ReportTemplate::from_text(unknown, false).is_err());
+    assert!(ReportTemplate::from_text("isk = 1", false).is_err());
+}
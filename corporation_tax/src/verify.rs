@@ -0,0 +1,445 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+use std::collections::BTreeSet;
+use strum::{EnumCount, EnumIter, IntoEnumIterator};
+use umya_spreadsheet::{Alignment, HorizontalAlignmentValues, NumberingFormat, Style, Worksheet};
+
+use crate::{
+    db_op::decimal_from_i64,
+    locale::{Lang, Text},
+    render::{Table, TableColumn, Value},
+    statement::{excel_datetime, format_isk_text},
+    template::ReportTemplate,
+};
+use db_wallet::entities::corporation_wallet_journal::{
+    Column as CCorporationWalletJournal, Entity as ECorporationWalletJournal,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IssueKind {
+    ChainBreak,
+    DuplicateId,
+}
+
+impl IssueKind {
+    pub fn key(&self) -> &'static str {
+        match self {
+            IssueKind::ChainBreak => "chain_break",
+            IssueKind::DuplicateId => "duplicate_id",
+        }
+    }
+
+    pub fn text(&self) -> Text {
+        match self {
+            IssueKind::ChainBreak => Text::ChainBreak,
+            IssueKind::DuplicateId => Text::DuplicateId,
+        }
+    }
+}
+
+pub struct JournalRow {
+    pub id: i64,
+    pub date_time: DateTime<FixedOffset>, // 报表时区的时间
+    pub amount: Option<Decimal>,
+    pub balance: Option<Decimal>,
+}
+
+pub struct Issue {
+    kind: IssueKind,
+    previous_id: Option<i64>,
+    id: i64,
+    window_start: Option<DateTime<FixedOffset>>, // 疑似缺失时间段起点, 即前一条流水的时间
+    window_end: DateTime<FixedOffset>,           // 疑似缺失时间段终点, 即本条流水的时间
+    expected_balance: Option<Decimal>,           // 前一条余额 + 本条金额
+    balance: Option<Decimal>,
+}
+
+impl Issue {
+    // 实际余额与期望余额的差额, 即缺失流水的金额合计
+    fn missing_amount(&self) -> Option<Decimal> {
+        Some(self.balance? - self.expected_balance?)
+    }
+
+    pub fn to_text(&self, lang: Lang) -> String {
+        match self.kind {
+            IssueKind::ChainBreak => format!(
+                "{}: {} ~ {}, {} {} -> {}, {} {}, {} {}, {} {}",
+                lang.text(self.kind.text()),
+                self.window_start
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default(),
+                self.window_end.format("%Y-%m-%d %H:%M:%S"),
+                lang.text(Text::JournalId),
+                self.previous_id.unwrap_or_default(),
+                self.id,
+                lang.text(Text::ExpectedBalance),
+                self.expected_balance
+                    .map(format_isk_text)
+                    .unwrap_or_default(),
+                lang.text(Text::ActualBalance),
+                self.balance.map(format_isk_text).unwrap_or_default(),
+                lang.text(Text::MissingAmount),
+                self.missing_amount()
+                    .map(format_isk_text)
+                    .unwrap_or_default(),
+            ),
+            IssueKind::DuplicateId => format!(
+                "{}: {} {}, {}",
+                lang.text(self.kind.text()),
+                lang.text(Text::JournalId),
+                self.id,
+                self.window_end.format("%Y-%m-%d %H:%M:%S"),
+            ),
+        }
+    }
+}
+
+// 按时间与ID顺序检查流水, 要求 前一条余额 + 本条金额 == 本条余额
+// 余额不连续处即为疑似缺失的时间段
+pub fn verify(rows: &[JournalRow]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut ids = BTreeSet::new();
+    let mut previous: Option<&JournalRow> = None;
+
+    for row in rows {
+        if ids.insert(row.id) == false {
+            issues.push(Issue {
+                kind: IssueKind::DuplicateId,
+                previous_id: None,
+                id: row.id,
+                window_start: None,
+                window_end: row.date_time,
+                expected_balance: None,
+                balance: row.balance,
+            });
+            continue;
+        }
+
+        if let Some(p) = previous {
+            if let (Some(p_balance), Some(amount), Some(balance)) =
+                (p.balance, row.amount, row.balance)
+            {
+                let expected_balance = p_balance + amount;
+                if expected_balance != balance {
+                    issues.push(Issue {
+                        kind: IssueKind::ChainBreak,
+                        previous_id: Some(p.id),
+                        id: row.id,
+                        window_start: Some(p.date_time),
+                        window_end: row.date_time,
+                        expected_balance: Some(expected_balance),
+                        balance: Some(balance),
+                    });
+                }
+            }
+        }
+
+        previous = Some(row);
+    }
+
+    issues
+}
+
+#[derive(EnumIter, EnumCount, Clone, Copy)]
+pub enum ColumnVerify {
+    Kind = 1,
+    WindowStart = 2,
+    WindowEnd = 3,
+    PreviousId = 4,
+    Id = 5,
+    ExpectedBalance = 6,
+    Balance = 7,
+    MissingAmount = 8,
+}
+
+impl ColumnVerify {
+    // csv, json 等输出使用的稳定键名
+    pub fn key(&self) -> &'static str {
+        match self {
+            ColumnVerify::Kind => "kind",
+            ColumnVerify::WindowStart => "window_start",
+            ColumnVerify::WindowEnd => "window_end",
+            ColumnVerify::PreviousId => "previous_id",
+            ColumnVerify::Id => "id",
+            ColumnVerify::ExpectedBalance => "expected_balance",
+            ColumnVerify::Balance => "balance",
+            ColumnVerify::MissingAmount => "missing_amount",
+        }
+    }
+
+    pub fn text(&self) -> Text {
+        match self {
+            ColumnVerify::Kind => Text::IssueKind,
+            ColumnVerify::WindowStart => Text::WindowStart,
+            ColumnVerify::WindowEnd => Text::WindowEnd,
+            ColumnVerify::PreviousId => Text::PreviousId,
+            ColumnVerify::Id => Text::JournalId,
+            ColumnVerify::ExpectedBalance => Text::ExpectedBalance,
+            ColumnVerify::Balance => Text::ActualBalance,
+            ColumnVerify::MissingAmount => Text::MissingAmount,
+        }
+    }
+
+    fn get_style(&self) -> Style {
+        let mut style = Style::default();
+        let format_str = match self {
+            ColumnVerify::Kind => r#"@"#,
+            ColumnVerify::WindowStart => r#"yyyy-mm-dd hh:mm:ss"#,
+            ColumnVerify::WindowEnd => r#"yyyy-mm-dd hh:mm:ss"#,
+            ColumnVerify::PreviousId => r#"0"#,
+            ColumnVerify::Id => r#"0"#,
+            ColumnVerify::ExpectedBalance | ColumnVerify::Balance | ColumnVerify::MissingAmount => {
+                r#"_ [$isk]\ * #,##0_ ;_ [$isk]\ * \-#,##0_ ;_ [$isk]\ * "-"?_ ;"#
+            }
+        };
+        let numbering_format = NumberingFormat::default()
+            .set_format_code(format_str)
+            .to_owned();
+        style.set_numbering_format(numbering_format);
+
+        style
+    }
+}
+
+pub struct SheetVerify {
+    data: Vec<Issue>,
+}
+
+impl SheetVerify {
+    pub fn issues(&self) -> &[Issue] {
+        &self.data
+    }
+
+    pub fn to_table(&self, lang: Lang) -> Table {
+        let columns = ColumnVerify::iter()
+            .map(|c| TableColumn::new(c.key(), lang.text(c.text())))
+            .collect();
+        let rows = self
+            .data
+            .iter()
+            .map(|issue| {
+                ColumnVerify::iter()
+                    .map(|column| match column {
+                        ColumnVerify::Kind => Value::Text(issue.kind.key().to_string()),
+                        ColumnVerify::WindowStart => {
+                            issue.window_start.map_or(Value::Empty, Value::DateTime)
+                        }
+                        ColumnVerify::WindowEnd => Value::DateTime(issue.window_end),
+                        ColumnVerify::PreviousId => {
+                            issue.previous_id.map_or(Value::Empty, Value::Integer)
+                        }
+                        ColumnVerify::Id => Value::Integer(issue.id),
+                        ColumnVerify::ExpectedBalance => {
+                            issue.expected_balance.map_or(Value::Empty, Value::Isk)
+                        }
+                        ColumnVerify::Balance => issue.balance.map_or(Value::Empty, Value::Isk),
+                        ColumnVerify::MissingAmount => {
+                            issue.missing_amount().map_or(Value::Empty, Value::Isk)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Table {
+            key: "journal_gaps",
+            title: lang.text(Text::SheetJournalGaps).to_string(),
+            columns,
+            rows,
+        }
+    }
+
+    pub fn insert_worksheet(&self, w: &mut Worksheet, lang: Lang, template: &ReportTemplate) {
+        // 插入标题
+        for column in ColumnVerify::iter() {
+            let cell = w.get_cell_mut((column as u32, 1));
+            cell.set_value_string(lang.text(column.text()));
+            let mut alignment = Alignment::default();
+            alignment.set_horizontal(HorizontalAlignmentValues::Center);
+            cell.get_style_mut().set_alignment(alignment);
+        }
+
+        // 插入数据
+        for (i, issue) in self.data.iter().enumerate() {
+            let row = (i + 2) as u32;
+            for column in ColumnVerify::iter() {
+                let cell = w.get_cell_mut((column as u32, row));
+                cell.set_style(column.get_style());
+
+                match column {
+                    ColumnVerify::Kind => {
+                        cell.set_value_string(lang.text(issue.kind.text()));
+                    }
+                    ColumnVerify::WindowStart => {
+                        if let Some(t) = issue.window_start {
+                            cell.set_value_number(excel_datetime(&t));
+                        }
+                    }
+                    ColumnVerify::WindowEnd => {
+                        cell.set_value_number(excel_datetime(&issue.window_end));
+                    }
+                    ColumnVerify::PreviousId => {
+                        if let Some(id) = issue.previous_id {
+                            cell.set_value_number(id as f64);
+                        }
+                    }
+                    ColumnVerify::Id => {
+                        cell.set_value_number(issue.id as f64);
+                    }
+                    ColumnVerify::ExpectedBalance => {
+                        if let Some(v) = issue.expected_balance {
+                            cell.set_value_number(v.to_f64().unwrap());
+                        }
+                    }
+                    ColumnVerify::Balance => {
+                        if let Some(v) = issue.balance {
+                            cell.set_value_number(v.to_f64().unwrap());
+                        }
+                    }
+                    ColumnVerify::MissingAmount => {
+                        if let Some(v) = issue.missing_amount() {
+                            cell.set_value_number(v.to_f64().unwrap());
+                        }
+                    }
+                }
+
+                // 整行规则以缺失金额判断
+                let value = match column {
+                    ColumnVerify::ExpectedBalance => issue.expected_balance,
+                    ColumnVerify::Balance => issue.balance,
+                    ColumnVerify::MissingAmount => issue.missing_amount(),
+                    _ => None,
+                };
+                let color = template.journal_gaps.highlight(
+                    column.key(),
+                    None,
+                    issue.missing_amount(),
+                    value,
+                );
+                if let Some(color) = color {
+                    cell.get_style_mut().set_background_color(color);
+                }
+            }
+        }
+    }
+
+    // 检查指定时间范围内的流水, 范围之前的最后一条流水也参与比较
+    // 未指定范围时检查全部流水, 流水时间转换到 offset 时区
+    pub async fn select_from_db<DB: ConnectionTrait>(
+        db: &DB,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        offset: FixedOffset,
+    ) -> Result<SheetVerify, String> {
+        #[derive(FromQueryResult)]
+        struct Journal {
+            id: i64,
+            date: i64,
+            amount: Option<i64>,
+            balance: Option<i64>,
+        }
+
+        let mut condition = Condition::all();
+        if let Some(start_time) = start_time {
+            condition = condition.add(CCorporationWalletJournal::Date.gte(start_time.timestamp()));
+        }
+        if let Some(end_time) = end_time {
+            condition = condition.add(CCorporationWalletJournal::Date.lt(end_time.timestamp()));
+        }
+
+        let mut journals = Vec::new();
+        if let Some(start_time) = start_time {
+            let previous = ECorporationWalletJournal::find()
+                .select_only()
+                .column(CCorporationWalletJournal::Id)
+                .column(CCorporationWalletJournal::Date)
+                .column(CCorporationWalletJournal::Amount)
+                .column(CCorporationWalletJournal::Balance)
+                .filter(CCorporationWalletJournal::Date.lt(start_time.timestamp()))
+                .order_by_desc(CCorporationWalletJournal::Date)
+                .order_by_desc(CCorporationWalletJournal::Id)
+                .into_model::<Journal>()
+                .one(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            journals.extend(previous);
+        }
+
+        let data = ECorporationWalletJournal::find()
+            .select_only()
+            .column(CCorporationWalletJournal::Id)
+            .column(CCorporationWalletJournal::Date)
+            .column(CCorporationWalletJournal::Amount)
+            .column(CCorporationWalletJournal::Balance)
+            .filter(condition)
+            .order_by_asc(CCorporationWalletJournal::Date)
+            .order_by_asc(CCorporationWalletJournal::Id)
+            .into_model::<Journal>()
+            .all(db)
+            .await
+            .map_err(|e| e.to_string())?;
+        journals.extend(data);
+
+        let rows: Vec<JournalRow> = journals
+            .into_iter()
+            .map(|j| JournalRow {
+                id: j.id,
+                date_time: DateTime::from_timestamp_secs(j.date)
+                    .unwrap()
+                    .with_timezone(&offset),
+                amount: j.amount.map(decimal_from_i64),
+                balance: j.balance.map(decimal_from_i64),
+            })
+            .collect();
+
+        Ok(SheetVerify {
+            data: verify(&rows),
+        })
+    }
+}
+
+#[test]
+fn test_verify() {
+    let utc8 = FixedOffset::east_opt(8 * 3600).unwrap();
+    let row = |id: i64, amount: i64, balance: i64| JournalRow {
+        id,
+        date_time: DateTime::from_timestamp_secs(1759276800 + id * 60)
+            .unwrap()
+            .with_timezone(&utc8),
+        amount: Some(Decimal::from(amount)),
+        balance: Some(Decimal::from(balance)),
+    };
+
+    let rows = vec![
+        row(1, 100, 100),
+        row(2, 50, 150),
+        row(3, -20, 130),
+        row(5, 10, 100), // 缺失 -40
+        row(5, 10, 100),
+        row(6, 5, 105),
+    ];
+    let issues = verify(&rows);
+    assert_eq!(issues.len(), 2);
+
+    assert_eq!(issues[0].kind, IssueKind::ChainBreak);
+    assert_eq!(issues[0].previous_id, Some(3));
+    assert_eq!(issues[0].id, 5);
+    assert_eq!(issues[0].missing_amount(), Some(Decimal::from(-40)));
+
+    assert_eq!(issues[1].kind, IssueKind::DuplicateId);
+    assert_eq!(issues[1].id, 5);
+
+    // 时间按报表时区显示, UTC 2025-10-01 00:05 为 UTC+8 的 08:05
+    assert_eq!(
+        issues[1].to_text(Lang::En),
+        format!(
+            "{}: {} 5, 2025-10-01 08:05:00",
+            Lang::En.text(Text::DuplicateId),
+            Lang::En.text(Text::JournalId)
+        )
+    );
+}
@@ -1,8 +1,9 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.17
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "characters")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
@@ -14,14 +15,7 @@ pub struct Model {
     pub name: String,
     pub user_id: Option<i32>,
     pub main: bool,
-    #[sea_orm(column_type = "Binary(1)", nullable)]
-    pub portrait64: Option<Vec<u8>>,
-    #[sea_orm(column_type = "Binary(1)", nullable)]
-    pub portrait128: Option<Vec<u8>>,
-    #[sea_orm(column_type = "Binary(1)", nullable)]
-    pub portrait256: Option<Vec<u8>>,
-    #[sea_orm(column_type = "Binary(1)", nullable)]
-    pub portrait512: Option<Vec<u8>>,
+    pub last_refreshed: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
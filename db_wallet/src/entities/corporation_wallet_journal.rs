@@ -1,8 +1,9 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.17
 
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "corporation_wallet_journal")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
@@ -21,6 +22,7 @@ pub struct Model {
     pub second_party_id: Option<i64>,
     pub tax: Option<i64>,
     pub tax_receiver_id: Option<i64>,
+    pub source: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
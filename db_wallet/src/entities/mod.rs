@@ -5,7 +5,10 @@ pub mod prelude;
 pub mod characters;
 pub mod corporation_wallet_journal;
 pub mod corporations;
+pub mod images;
 pub mod pap_journal;
+pub mod party_history;
+pub mod party_images;
 pub mod tax_parameters;
 pub mod taxable_list;
 pub mod users;
@@ -3,7 +3,10 @@
 pub use super::characters::Entity as Characters;
 pub use super::corporation_wallet_journal::Entity as CorporationWalletJournal;
 pub use super::corporations::Entity as Corporations;
+pub use super::images::Entity as Images;
 pub use super::pap_journal::Entity as PapJournal;
+pub use super::party_history::Entity as PartyHistory;
+pub use super::party_images::Entity as PartyImages;
 pub use super::tax_parameters::Entity as TaxParameters;
 pub use super::taxable_list::Entity as TaxableList;
 pub use super::users::Entity as Users;
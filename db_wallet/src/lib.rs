@@ -1,8 +1,13 @@
 pub mod entities;
 mod m20220101_000001_create_table;
+mod m20251018_000001_add_journal_source;
+mod m20251101_000001_add_party_refresh;
+mod m20251108_000001_add_images;
+mod m20251201_000001_add_user_tax_note;
 
 pub use sea_orm_migration::prelude::*;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use strum::{AsRefStr, EnumIter, EnumString, FromRepr, IntoStaticStr};
 
 pub struct Migrator;
@@ -10,7 +15,13 @@ pub struct Migrator;
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20251018_000001_add_journal_source::Migration),
+            Box::new(m20251101_000001_add_party_refresh::Migration),
+            Box::new(m20251108_000001_add_images::Migration),
+            Box::new(m20251201_000001_add_user_tax_note::Migration),
+        ]
     }
 }
 
@@ -192,17 +203,339 @@ pub enum JournalRefType {
 impl JournalRefType {
     pub fn zh_str(&self) -> &'static str {
         match self {
-            JournalRefType::PlayerDonation => "玩家捐助",    // 10
-            JournalRefType::OfficeRentalFee => "办公室租金", // 13
+            JournalRefType::PlayerTrading => "玩家交易",      // 1
+            JournalRefType::MarketTransaction => "市场交易",  // 2
+            JournalRefType::GmCashTransfer => "GM转账",       // 3
+            JournalRefType::MissionReward => "任务奖励",      // 7
+            JournalRefType::CloneActivation => "克隆激活",    // 8
+            JournalRefType::Inheritance => "遗产继承",        // 9
+            JournalRefType::PlayerDonation => "玩家捐助",     // 10
+            JournalRefType::CorporationPayment => "军团支付", // 11
+            JournalRefType::DockingFee => "停靠费",           // 12
+            JournalRefType::OfficeRentalFee => "办公室租金",  // 13
+            JournalRefType::FactorySlotRentalFee => "工厂槽位租金", // 14
+            JournalRefType::RepairBill => "维修账单",         // 15
+            JournalRefType::Bounty => "赏金",                 // 16
+            JournalRefType::BountyPrize => "赏金奖励",        // 17
+            JournalRefType::Insurance => "保险",              // 19
+            JournalRefType::MissionExpiration => "任务过期",  // 20
+            JournalRefType::MissionCompletion => "任务完成",  // 21
+            JournalRefType::Shares => "股份",                 // 22
+            JournalRefType::CourierMissionEscrow => "运输任务保证金", // 23
+            JournalRefType::MissionCost => "任务费用",        // 24
+            JournalRefType::AgentMiscellaneous => "代理人杂项", // 25
+            JournalRefType::LpStore => "忠诚点商店",          // 26
+            JournalRefType::AgentLocationServices => "代理人定位服务", // 27
+            JournalRefType::AgentDonation => "代理人捐助",    // 28
+            JournalRefType::AgentSecurityServices => "代理人安全服务", // 29
+            JournalRefType::AgentMissionCollateralPaid => "代理人任务抵押金支付", // 30
+            JournalRefType::AgentMissionCollateralRefunded => "代理人任务抵押金退还", // 31
+            JournalRefType::AgentsPreward => "代理人预付奖励", // 32
             JournalRefType::AgentMissionReward => "代理人任务奖励", // 33
             JournalRefType::AgentMissionTimeBonusReward => "代理人任务时间加成奖励", // 34
+            JournalRefType::Cspa => "CSPA费用",               // 35
+            JournalRefType::Cspaofflinerefund => "CSPA离线退款", // 36
             JournalRefType::CorporationAccountWithdrawal => "军团账户支取", // 37
             JournalRefType::CorporationDividendPayment => "军团奖励支付", // 38
-            JournalRefType::BountyPrizes => "追击赏金",      // 85
+            JournalRefType::CorporationRegistrationFee => "军团注册费", // 39
+            JournalRefType::CorporationLogoChangeCost => "军团标志更改费用", // 40
+            JournalRefType::ReleaseOfImpoundedProperty => "释放扣押财产", // 41
+            JournalRefType::MarketEscrow => "市场托管",       // 42
+            JournalRefType::AgentServicesRendered => "代理人服务费", // 43
+            JournalRefType::MarketFinePaid => "市场罚款",     // 44
+            JournalRefType::CorporationLiquidation => "军团清算", // 45
+            JournalRefType::BrokersFee => "经纪费",           // 46
+            JournalRefType::CorporationBulkPayment => "军团批量支付", // 47
+            JournalRefType::AllianceRegistrationFee => "联盟注册费", // 48
+            JournalRefType::WarFee => "战争费用",             // 49
+            JournalRefType::AllianceMaintainanceFee => "联盟维护费", // 50
+            JournalRefType::ContrabandFine => "违禁品罚款",   // 51
+            JournalRefType::CloneTransfer => "克隆转移",      // 52
+            JournalRefType::AccelerationGateFee => "加速门费用", // 53
+            JournalRefType::TransactionTax => "交易税",       // 54
+            JournalRefType::JumpCloneInstallationFee => "远距克隆安装费", // 55
+            JournalRefType::Manufacturing => "制造",          // 56
+            JournalRefType::ResearchingTechnology => "科技研究", // 57
+            JournalRefType::ResearchingTimeProductivity => "时间效率研究", // 58
+            JournalRefType::ResearchingMaterialProductivity => "材料效率研究", // 59
+            JournalRefType::Copying => "复制",                // 60
+            JournalRefType::ReverseEngineering => "逆向工程", // 62
+            JournalRefType::ContractAuctionBid => "合同拍卖出价", // 63
+            JournalRefType::ContractAuctionBidRefund => "合同拍卖出价退还", // 64
+            JournalRefType::ContractCollateral => "合同抵押金", // 65
+            JournalRefType::ContractRewardRefund => "合同报酬退还", // 66
+            JournalRefType::ContractAuctionSold => "合同拍卖成交", // 67
+            JournalRefType::ContractReward => "合同报酬",     // 68
+            JournalRefType::ContractCollateralRefund => "合同抵押金退还", // 69
+            JournalRefType::ContractCollateralPayout => "合同抵押金赔付", // 70
+            JournalRefType::ContractPrice => "合同价格",      // 71
+            JournalRefType::ContractBrokersFee => "合同经纪费", // 72
+            JournalRefType::ContractSalesTax => "合同销售税", // 73
+            JournalRefType::ContractDeposit => "合同保证金",  // 74
+            JournalRefType::ContractDepositSalesTax => "合同保证金销售税", // 75
+            JournalRefType::ContractAuctionBidCorp => "军团合同拍卖出价", // 77
+            JournalRefType::ContractCollateralDepositedCorp => "军团合同抵押金存入", // 78
+            JournalRefType::ContractPricePaymentCorp => "军团合同价格支付", // 79
+            JournalRefType::ContractBrokersFeeCorp => "军团合同经纪费", // 80
+            JournalRefType::ContractDepositCorp => "军团合同保证金", // 81
+            JournalRefType::ContractDepositRefund => "合同保证金退还", // 82
+            JournalRefType::ContractRewardDeposited => "合同报酬存入", // 83
+            JournalRefType::ContractRewardDepositedCorp => "军团合同报酬存入", // 84
+            JournalRefType::BountyPrizes => "追击赏金",       // 85
+            JournalRefType::AdvertisementListingFee => "广告发布费", // 86
+            JournalRefType::MedalCreation => "勋章创建",      // 87
+            JournalRefType::MedalIssued => "勋章颁发",        // 88
+            JournalRefType::DnaModificationFee => "基因改造费", // 90
+            JournalRefType::SovereignityBill => "主权账单",   // 91
+            JournalRefType::BountyPrizeCorporationTax => "赏金军团税", // 92
+            JournalRefType::AgentMissionRewardCorporationTax => "代理人任务奖励军团税", // 93
+            JournalRefType::AgentMissionTimeBonusRewardCorporationTax => {
+                "代理人任务时间加成奖励军团税"
+            } // 94
+            JournalRefType::UpkeepAdjustmentFee => "维护调整费", // 95
+            JournalRefType::PlanetaryImportTax => "行星进口税", // 96
+            JournalRefType::PlanetaryExportTax => "行星出口税", // 97
+            JournalRefType::PlanetaryConstruction => "行星建设", // 98
+            JournalRefType::CorporateRewardPayout => "军团奖励发放", // 99
+            JournalRefType::BountySurcharge => "赏金附加费",  // 101
+            JournalRefType::ContractReversal => "合同撤销",   // 102
+            JournalRefType::CorporateRewardTax => "军团奖励税", // 103
+            JournalRefType::StorePurchase => "商店购买",      // 106
+            JournalRefType::StorePurchaseRefund => "商店购买退款", // 107
+            JournalRefType::DatacoreFee => "数据核心费用",    // 112
+            JournalRefType::WarFeeSurrender => "战争投降费",  // 113
+            JournalRefType::WarAllyContract => "战争盟友合同", // 114
+            JournalRefType::BountyReimbursement => "赏金补偿", // 115
+            JournalRefType::KillRightFee => "击杀权费用",     // 116
+            JournalRefType::SecurityProcessingFee => "安全处理费", // 117
+            JournalRefType::IndustryJobTax => "工业任务税",   // 120
+            JournalRefType::InfrastructureHubMaintenance => "基础设施枢纽维护费", // 122
+            JournalRefType::AssetSafetyRecoveryTax => "资产安全回收税", // 123
+            JournalRefType::OpportunityReward => "机遇奖励",  // 124
             JournalRefType::ProjectDiscoveryReward => "探索计划奖励", // 125
+            JournalRefType::ProjectDiscoveryTax => "探索计划税", // 126
+            JournalRefType::ReprocessingTax => "提炼税",      // 127
+            JournalRefType::JumpCloneActivationFee => "远距克隆激活费", // 128
+            JournalRefType::OperationBonus => "行动奖励",     // 129
+            JournalRefType::ResourceWarsReward => "资源战争奖励", // 131
+            JournalRefType::DuelWagerEscrow => "决斗赌注托管", // 132
+            JournalRefType::DuelWagerPayment => "决斗赌注支付", // 133
+            JournalRefType::DuelWagerRefund => "决斗赌注退还", // 134
+            JournalRefType::Reaction => "反应",               // 135
+            JournalRefType::ExternalTradeFreeze => "外部交易冻结", // 136
+            JournalRefType::ExternalTradeThaw => "外部交易解冻", // 137
+            JournalRefType::ExternalTradeDelivery => "外部交易交付", // 138
+            JournalRefType::SeasonChallengeReward => "赛季挑战奖励", // 139
+            JournalRefType::SkillPurchase => "技能购买",      // 141
+            JournalRefType::ItemTraderPayment => "物品商人支付", // 142
+            JournalRefType::FluxTicketSale => "通量彩票销售", // 143
+            JournalRefType::FluxPayout => "通量彩票派奖",     // 144
+            JournalRefType::FluxTax => "通量彩票税",          // 145
+            JournalRefType::FluxTicketRepayment => "通量彩票退款", // 146
+            JournalRefType::RedeemedIskToken => "兑换星币代币", // 147
+            JournalRefType::DailyChallengeReward => "每日挑战奖励", // 148
+            JournalRefType::MarketProviderTax => "市场服务商税", // 149
             JournalRefType::EssEscrowTransfer => "事件监测装置保证金支付", // 155
+            JournalRefType::MilestoneRewardPayment => "里程碑奖励支付", // 156
+            JournalRefType::UnderConstruction => "建设中",    // 166
+            JournalRefType::AllignmentBasedGateToll => "阵营星门通行费", // 168
+            JournalRefType::ProjectPayouts => "项目报酬",     // 170
+            JournalRefType::InsurgencyCorruptionContributionReward => "叛乱腐化贡献奖励", // 172
+            JournalRefType::InsurgencySuppressionContributionReward => "叛乱镇压贡献奖励", // 173
             JournalRefType::DailyGoalPayouts => "每日目标奖励", // 174
-            _ => self.into(),
+            JournalRefType::DailyGoalPayoutsTax => "每日目标奖励税", // 175
+            JournalRefType::CosmeticMarketComponentItemPurchase => "外观市场组件购买", // 178
+            JournalRefType::CosmeticMarketSkinSaleBrokerFee => "外观市场涂装销售经纪费", // 179
+            JournalRefType::CosmeticMarketSkinPurchase => "外观市场涂装购买", // 180
+            JournalRefType::CosmeticMarketSkinSale => "外观市场涂装销售", // 181
+            JournalRefType::CosmeticMarketSkinSaleTax => "外观市场涂装销售税", // 182
+            JournalRefType::CosmeticMarketSkinTransaction => "外观市场涂装交易", // 183
+            JournalRefType::SkyhookClaimFee => "天钩领取费",  // 184
+            JournalRefType::AirCareerProgramReward => "AIR职业计划奖励", // 185
+            JournalRefType::FreelanceJobsDurationFee => "自由职业时长费", // 186
+            JournalRefType::FreelanceJobsBroadcastingFee => "自由职业广播费", // 187
+            JournalRefType::FreelanceJobsRewardEscrow => "自由职业报酬托管", // 188
+            JournalRefType::FreelanceJobsReward => "自由职业报酬", // 189
+            JournalRefType::FreelanceJobsEscrowRefund => "自由职业托管退款", // 190
+            JournalRefType::FreelanceJobsRewardCorporationTax => "自由职业报酬军团税", // 191
+            JournalRefType::GmPlexFeeRefund => "GM伊甸币费用退还", // 192
+        }
+    }
+
+    pub fn en_str(&self) -> &'static str {
+        match self {
+            JournalRefType::PlayerTrading => "Player Trading", // 1
+            JournalRefType::MarketTransaction => "Market Transaction", // 2
+            JournalRefType::GmCashTransfer => "GM Cash Transfer", // 3
+            JournalRefType::MissionReward => "Mission Reward", // 7
+            JournalRefType::CloneActivation => "Clone Activation", // 8
+            JournalRefType::Inheritance => "Inheritance",      // 9
+            JournalRefType::PlayerDonation => "Player Donation", // 10
+            JournalRefType::CorporationPayment => "Corporation Payment", // 11
+            JournalRefType::DockingFee => "Docking Fee",       // 12
+            JournalRefType::OfficeRentalFee => "Office Rental Fee", // 13
+            JournalRefType::FactorySlotRentalFee => "Factory Slot Rental Fee", // 14
+            JournalRefType::RepairBill => "Repair Bill",       // 15
+            JournalRefType::Bounty => "Bounty",                // 16
+            JournalRefType::BountyPrize => "Bounty Prize",     // 17
+            JournalRefType::Insurance => "Insurance",          // 19
+            JournalRefType::MissionExpiration => "Mission Expiration", // 20
+            JournalRefType::MissionCompletion => "Mission Completion", // 21
+            JournalRefType::Shares => "Shares",                // 22
+            JournalRefType::CourierMissionEscrow => "Courier Mission Escrow", // 23
+            JournalRefType::MissionCost => "Mission Cost",     // 24
+            JournalRefType::AgentMiscellaneous => "Agent Miscellaneous", // 25
+            JournalRefType::LpStore => "LP Store",             // 26
+            JournalRefType::AgentLocationServices => "Agent Location Services", // 27
+            JournalRefType::AgentDonation => "Agent Donation", // 28
+            JournalRefType::AgentSecurityServices => "Agent Security Services", // 29
+            JournalRefType::AgentMissionCollateralPaid => "Agent Mission Collateral Paid", // 30
+            JournalRefType::AgentMissionCollateralRefunded => "Agent Mission Collateral Refunded", // 31
+            JournalRefType::AgentsPreward => "Agents Pre-reward", // 32
+            JournalRefType::AgentMissionReward => "Agent Mission Reward", // 33
+            JournalRefType::AgentMissionTimeBonusReward => "Agent Mission Time Bonus Reward", // 34
+            JournalRefType::Cspa => "CSPA Charge",                // 35
+            JournalRefType::Cspaofflinerefund => "CSPA Offline Refund", // 36
+            JournalRefType::CorporationAccountWithdrawal => "Corporation Account Withdrawal", // 37
+            JournalRefType::CorporationDividendPayment => "Corporation Dividend Payment", // 38
+            JournalRefType::CorporationRegistrationFee => "Corporation Registration Fee", // 39
+            JournalRefType::CorporationLogoChangeCost => "Corporation Logo Change Cost", // 40
+            JournalRefType::ReleaseOfImpoundedProperty => "Release of Impounded Property", // 41
+            JournalRefType::MarketEscrow => "Market Escrow",      // 42
+            JournalRefType::AgentServicesRendered => "Agent Services Rendered", // 43
+            JournalRefType::MarketFinePaid => "Market Fine Paid", // 44
+            JournalRefType::CorporationLiquidation => "Corporation Liquidation", // 45
+            JournalRefType::BrokersFee => "Broker's Fee",         // 46
+            JournalRefType::CorporationBulkPayment => "Corporation Bulk Payment", // 47
+            JournalRefType::AllianceRegistrationFee => "Alliance Registration Fee", // 48
+            JournalRefType::WarFee => "War Fee",                  // 49
+            JournalRefType::AllianceMaintainanceFee => "Alliance Maintenance Fee", // 50
+            JournalRefType::ContrabandFine => "Contraband Fine",  // 51
+            JournalRefType::CloneTransfer => "Clone Transfer",    // 52
+            JournalRefType::AccelerationGateFee => "Acceleration Gate Fee", // 53
+            JournalRefType::TransactionTax => "Transaction Tax",  // 54
+            JournalRefType::JumpCloneInstallationFee => "Jump Clone Installation Fee", // 55
+            JournalRefType::Manufacturing => "Manufacturing",     // 56
+            JournalRefType::ResearchingTechnology => "Researching Technology", // 57
+            JournalRefType::ResearchingTimeProductivity => "Researching Time Productivity", // 58
+            JournalRefType::ResearchingMaterialProductivity => "Researching Material Productivity", // 59
+            JournalRefType::Copying => "Copying", // 60
+            JournalRefType::ReverseEngineering => "Reverse Engineering", // 62
+            JournalRefType::ContractAuctionBid => "Contract Auction Bid", // 63
+            JournalRefType::ContractAuctionBidRefund => "Contract Auction Bid Refund", // 64
+            JournalRefType::ContractCollateral => "Contract Collateral", // 65
+            JournalRefType::ContractRewardRefund => "Contract Reward Refund", // 66
+            JournalRefType::ContractAuctionSold => "Contract Auction Sold", // 67
+            JournalRefType::ContractReward => "Contract Reward", // 68
+            JournalRefType::ContractCollateralRefund => "Contract Collateral Refund", // 69
+            JournalRefType::ContractCollateralPayout => "Contract Collateral Payout", // 70
+            JournalRefType::ContractPrice => "Contract Price", // 71
+            JournalRefType::ContractBrokersFee => "Contract Broker's Fee", // 72
+            JournalRefType::ContractSalesTax => "Contract Sales Tax", // 73
+            JournalRefType::ContractDeposit => "Contract Deposit", // 74
+            JournalRefType::ContractDepositSalesTax => "Contract Deposit Sales Tax", // 75
+            JournalRefType::ContractAuctionBidCorp => "Contract Auction Bid (Corporation)", // 77
+            JournalRefType::ContractCollateralDepositedCorp => {
+                "Contract Collateral Deposited (Corporation)"
+            } // 78
+            JournalRefType::ContractPricePaymentCorp => "Contract Price Payment (Corporation)", // 79
+            JournalRefType::ContractBrokersFeeCorp => "Contract Broker's Fee (Corporation)", // 80
+            JournalRefType::ContractDepositCorp => "Contract Deposit (Corporation)",         // 81
+            JournalRefType::ContractDepositRefund => "Contract Deposit Refund",              // 82
+            JournalRefType::ContractRewardDeposited => "Contract Reward Deposited",          // 83
+            JournalRefType::ContractRewardDepositedCorp => {
+                "Contract Reward Deposited (Corporation)"
+            } // 84
+            JournalRefType::BountyPrizes => "Bounty Prizes",                                 // 85
+            JournalRefType::AdvertisementListingFee => "Advertisement Listing Fee",          // 86
+            JournalRefType::MedalCreation => "Medal Creation",                               // 87
+            JournalRefType::MedalIssued => "Medal Issued",                                   // 88
+            JournalRefType::DnaModificationFee => "DNA Modification Fee",                    // 90
+            JournalRefType::SovereignityBill => "Sovereignty Bill",                          // 91
+            JournalRefType::BountyPrizeCorporationTax => "Bounty Prize Corporation Tax",     // 92
+            JournalRefType::AgentMissionRewardCorporationTax => {
+                "Agent Mission Reward Corporation Tax"
+            } // 93
+            JournalRefType::AgentMissionTimeBonusRewardCorporationTax => {
+                "Agent Mission Time Bonus Reward Corporation Tax"
+            } // 94
+            JournalRefType::UpkeepAdjustmentFee => "Upkeep Adjustment Fee",                  // 95
+            JournalRefType::PlanetaryImportTax => "Planetary Import Tax",                    // 96
+            JournalRefType::PlanetaryExportTax => "Planetary Export Tax",                    // 97
+            JournalRefType::PlanetaryConstruction => "Planetary Construction",               // 98
+            JournalRefType::CorporateRewardPayout => "Corporate Reward Payout",              // 99
+            JournalRefType::BountySurcharge => "Bounty Surcharge",                           // 101
+            JournalRefType::ContractReversal => "Contract Reversal",                         // 102
+            JournalRefType::CorporateRewardTax => "Corporate Reward Tax",                    // 103
+            JournalRefType::StorePurchase => "Store Purchase",                               // 106
+            JournalRefType::StorePurchaseRefund => "Store Purchase Refund",                  // 107
+            JournalRefType::DatacoreFee => "Datacore Fee",                                   // 112
+            JournalRefType::WarFeeSurrender => "War Fee Surrender",                          // 113
+            JournalRefType::WarAllyContract => "War Ally Contract",                          // 114
+            JournalRefType::BountyReimbursement => "Bounty Reimbursement",                   // 115
+            JournalRefType::KillRightFee => "Kill Right Fee",                                // 116
+            JournalRefType::SecurityProcessingFee => "Security Processing Fee",              // 117
+            JournalRefType::IndustryJobTax => "Industry Job Tax",                            // 120
+            JournalRefType::InfrastructureHubMaintenance => "Infrastructure Hub Maintenance", // 122
+            JournalRefType::AssetSafetyRecoveryTax => "Asset Safety Recovery Tax",           // 123
+            JournalRefType::OpportunityReward => "Opportunity Reward",                       // 124
+            JournalRefType::ProjectDiscoveryReward => "Project Discovery Reward",            // 125
+            JournalRefType::ProjectDiscoveryTax => "Project Discovery Tax",                  // 126
+            JournalRefType::ReprocessingTax => "Reprocessing Tax",                           // 127
+            JournalRefType::JumpCloneActivationFee => "Jump Clone Activation Fee",           // 128
+            JournalRefType::OperationBonus => "Operation Bonus",                             // 129
+            JournalRefType::ResourceWarsReward => "Resource Wars Reward",                    // 131
+            JournalRefType::DuelWagerEscrow => "Duel Wager Escrow",                          // 132
+            JournalRefType::DuelWagerPayment => "Duel Wager Payment",                        // 133
+            JournalRefType::DuelWagerRefund => "Duel Wager Refund",                          // 134
+            JournalRefType::Reaction => "Reaction",                                          // 135
+            JournalRefType::ExternalTradeFreeze => "External Trade Freeze",                  // 136
+            JournalRefType::ExternalTradeThaw => "External Trade Thaw",                      // 137
+            JournalRefType::ExternalTradeDelivery => "External Trade Delivery",              // 138
+            JournalRefType::SeasonChallengeReward => "Season Challenge Reward",              // 139
+            JournalRefType::SkillPurchase => "Skill Purchase",                               // 141
+            JournalRefType::ItemTraderPayment => "Item Trader Payment",                      // 142
+            JournalRefType::FluxTicketSale => "Flux Ticket Sale",                            // 143
+            JournalRefType::FluxPayout => "Flux Payout",                                     // 144
+            JournalRefType::FluxTax => "Flux Tax",                                           // 145
+            JournalRefType::FluxTicketRepayment => "Flux Ticket Repayment",                  // 146
+            JournalRefType::RedeemedIskToken => "Redeemed ISK Token",                        // 147
+            JournalRefType::DailyChallengeReward => "Daily Challenge Reward",                // 148
+            JournalRefType::MarketProviderTax => "Market Provider Tax",                      // 149
+            JournalRefType::EssEscrowTransfer => "ESS Escrow Transfer",                      // 155
+            JournalRefType::MilestoneRewardPayment => "Milestone Reward Payment",            // 156
+            JournalRefType::UnderConstruction => "Under Construction",                       // 166
+            JournalRefType::AllignmentBasedGateToll => "Alignment Based Gate Toll",          // 168
+            JournalRefType::ProjectPayouts => "Project Payouts",                             // 170
+            JournalRefType::InsurgencyCorruptionContributionReward => {
+                "Insurgency Corruption Contribution Reward"
+            } // 172
+            JournalRefType::InsurgencySuppressionContributionReward => {
+                "Insurgency Suppression Contribution Reward"
+            } // 173
+            JournalRefType::DailyGoalPayouts => "Daily Goal Payouts",                        // 174
+            JournalRefType::DailyGoalPayoutsTax => "Daily Goal Payouts Tax",                 // 175
+            JournalRefType::CosmeticMarketComponentItemPurchase => {
+                "Cosmetic Market Component Item Purchase"
+            } // 178
+            JournalRefType::CosmeticMarketSkinSaleBrokerFee => {
+                "Cosmetic Market Skin Sale Broker Fee"
+            } // 179
+            JournalRefType::CosmeticMarketSkinPurchase => "Cosmetic Market Skin Purchase",   // 180
+            JournalRefType::CosmeticMarketSkinSale => "Cosmetic Market Skin Sale",           // 181
+            JournalRefType::CosmeticMarketSkinSaleTax => "Cosmetic Market Skin Sale Tax",    // 182
+            JournalRefType::CosmeticMarketSkinTransaction => "Cosmetic Market Skin Transaction", // 183
+            JournalRefType::SkyhookClaimFee => "Skyhook Claim Fee", // 184
+            JournalRefType::AirCareerProgramReward => "AIR Career Program Reward", // 185
+            JournalRefType::FreelanceJobsDurationFee => "Freelance Jobs Duration Fee", // 186
+            JournalRefType::FreelanceJobsBroadcastingFee => "Freelance Jobs Broadcasting Fee", // 187
+            JournalRefType::FreelanceJobsRewardEscrow => "Freelance Jobs Reward Escrow", // 188
+            JournalRefType::FreelanceJobsReward => "Freelance Jobs Reward",              // 189
+            JournalRefType::FreelanceJobsEscrowRefund => "Freelance Jobs Escrow Refund", // 190
+            JournalRefType::FreelanceJobsRewardCorporationTax => {
+                "Freelance Jobs Reward Corporation Tax"
+            } // 191
+            JournalRefType::GmPlexFeeRefund => "GM PLEX Fee Refund",                     // 192
         }
     }
 }
@@ -225,3 +558,20 @@ pub enum ContextIdType {
     SystemId = 11,
     TypeId = 12,
 }
+
+// corporation_wallet_journal 中流水的来源
+#[derive(EnumString, AsRefStr, EnumIter, FromRepr, Copy, Clone, PartialEq, Eq)]
+#[strum(serialize_all = "snake_case")]
+#[repr(i32)]
+pub enum JournalSource {
+    Esi = 1,
+    GameExport = 2,
+}
+
+// images 表的主键, 小写十六进制的 sha256, 迁移与写入图片时共用
+pub fn image_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -1,4 +1,5 @@
 use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DatabaseBackend;
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -6,8 +7,9 @@ pub struct Migration;
 #[async_trait::async_trait]
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
         let create_statements: Vec<CreateStatement> =
-            statements().iter().map(|(c, _)| c.clone()).collect();
+            statements(backend).iter().map(|(c, _)| c.clone()).collect();
 
         for CreateStatement { table, index } in create_statements {
             manager.create_table(table).await?;
@@ -20,12 +22,19 @@ impl MigrationTrait for Migration {
     }
 
     async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
-        let drop_statements: Vec<DropStatement> =
-            statements().iter().rev().map(|(_, d)| d.clone()).collect();
+        let backend = manager.get_database_backend();
+        let drop_statements: Vec<DropStatement> = statements(backend)
+            .iter()
+            .rev()
+            .map(|(_, d)| d.clone())
+            .collect();
 
         for DropStatement { table, index } in drop_statements {
-            for i in index {
-                manager.drop_index(i).await?;
+            // MySQL 不支持 DROP INDEX IF EXISTS, 索引随表一同删除
+            if backend != DatabaseBackend::MySql {
+                for i in index {
+                    manager.drop_index(i).await?;
+                }
             }
             manager.drop_table(table).await?;
         }
@@ -82,10 +91,10 @@ impl DropStatement {
     }
 }
 
-fn statements() -> Vec<(CreateStatement, DropStatement)> {
+fn statements(backend: DatabaseBackend) -> Vec<(CreateStatement, DropStatement)> {
     vec![
         (ct_users(), dt_users()),
-        (ct_characters(), dt_characters()),
+        (ct_characters(backend), dt_characters()),
         (
             ct_corporation_wallet_journal(),
             dt_corporation_wallet_journal(),
@@ -128,7 +137,7 @@ fn ct_corporation_wallet_journal() -> CreateStatement {
         )
         .col(
             ColumnDef::new(IdenCorporationWalletJournal::Date)
-                .big_unsigned()
+                .big_integer()
                 .not_null(),
         )
         .col(
@@ -214,7 +223,17 @@ enum IdenCharacters {
     Portrait512,
 }
 
-fn ct_characters() -> CreateStatement {
+// 头像等二进制数据, MySQL 的 blob 上限为 64KB, 改用 longblob
+pub(crate) fn blob_column<T: IntoIden>(name: T, backend: DatabaseBackend) -> ColumnDef {
+    let mut column = ColumnDef::new(name);
+    match backend {
+        DatabaseBackend::MySql => column.custom(Alias::new("longblob")),
+        _ => column.blob(),
+    };
+    column
+}
+
+fn ct_characters(backend: DatabaseBackend) -> CreateStatement {
     let table = Table::create()
         .if_not_exists()
         .table(IdenCharacters::Table)
@@ -238,24 +257,27 @@ fn ct_characters() -> CreateStatement {
         .col(ColumnDef::new(IdenCharacters::Name).text().not_null())
         .col(ColumnDef::new(IdenCharacters::UserId).integer())
         .col(ColumnDef::new(IdenCharacters::Main).boolean().not_null())
-        .col(ColumnDef::new(IdenCharacters::Portrait64).binary())
-        .col(ColumnDef::new(IdenCharacters::Portrait128).binary())
-        .col(ColumnDef::new(IdenCharacters::Portrait256).binary())
-        .col(ColumnDef::new(IdenCharacters::Portrait512).binary())
+        .col(blob_column(IdenCharacters::Portrait64, backend))
+        .col(blob_column(IdenCharacters::Portrait128, backend))
+        .col(blob_column(IdenCharacters::Portrait256, backend))
+        .col(blob_column(IdenCharacters::Portrait512, backend))
         .to_owned();
 
-    let index = vec![
-        Index::create()
-            .if_not_exists()
-            .name(format!(
-                "index_{}_{}",
-                IdenCharacters::Table.to_string(),
-                IdenCharacters::Name.to_string(),
-            ))
-            .table(IdenCharacters::Table)
-            .col(IdenCharacters::Name)
-            .to_owned(),
-    ];
+    let mut index_name = Index::create()
+        .if_not_exists()
+        .name(format!(
+            "index_{}_{}",
+            IdenCharacters::Table.to_string(),
+            IdenCharacters::Name.to_string(),
+        ))
+        .table(IdenCharacters::Table)
+        .to_owned();
+    // MySQL 的 text 列索引必须指定前缀长度
+    match backend {
+        DatabaseBackend::MySql => index_name.col((IdenCharacters::Name, 191)),
+        _ => index_name.col(IdenCharacters::Name),
+    };
+    let index = vec![index_name];
 
     CreateStatement::new(table, index)
 }
@@ -0,0 +1,38 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 已有数据均来自 ESI
+        let table = Table::alter()
+            .table(IdenCorporationWalletJournal::Table)
+            .add_column(
+                ColumnDef::new(IdenCorporationWalletJournal::Source)
+                    .integer()
+                    .not_null()
+                    .default(1),
+            )
+            .to_owned();
+
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(IdenCorporationWalletJournal::Table)
+            .drop_column(IdenCorporationWalletJournal::Source)
+            .to_owned();
+
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdenCorporationWalletJournal {
+    #[sea_orm(iden = "corporation_wallet_journal")]
+    Table,
+    Source, // 数据来源, 见 JournalSource
+}
@@ -0,0 +1,129 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // sqlite 每条 ALTER TABLE 只能添加一列
+        // 已有数据的刷新时间为空, 首次刷新时全部重新查询
+        let tables = [
+            Table::alter()
+                .table(IdenCharacters::Table)
+                .add_column(ColumnDef::new(IdenCharacters::LastRefreshed).big_integer())
+                .to_owned(),
+            Table::alter()
+                .table(IdenCorporations::Table)
+                .add_column(ColumnDef::new(IdenCorporations::AllianceId).big_integer())
+                .to_owned(),
+            Table::alter()
+                .table(IdenCorporations::Table)
+                .add_column(ColumnDef::new(IdenCorporations::LastRefreshed).big_integer())
+                .to_owned(),
+        ];
+        for table in tables {
+            manager.alter_table(table).await?;
+        }
+
+        let table = Table::create()
+            .if_not_exists()
+            .table(IdenPartyHistory::Table)
+            .col(
+                ColumnDef::new(IdenPartyHistory::Id)
+                    .integer()
+                    .not_null()
+                    .primary_key()
+                    .auto_increment(),
+            )
+            .col(
+                ColumnDef::new(IdenPartyHistory::PartyId)
+                    .big_integer()
+                    .not_null(),
+            )
+            .col(
+                ColumnDef::new(IdenPartyHistory::ChangedAt)
+                    .big_integer()
+                    .not_null(),
+            )
+            .col(ColumnDef::new(IdenPartyHistory::OldCorporationId).big_integer())
+            .col(ColumnDef::new(IdenPartyHistory::NewCorporationId).big_integer())
+            .col(ColumnDef::new(IdenPartyHistory::OldAllianceId).big_integer())
+            .col(ColumnDef::new(IdenPartyHistory::NewAllianceId).big_integer())
+            .col(ColumnDef::new(IdenPartyHistory::OldName).text())
+            .col(ColumnDef::new(IdenPartyHistory::NewName).text())
+            .to_owned();
+        manager.create_table(table).await?;
+
+        let index = Index::create()
+            .if_not_exists()
+            .name(format!(
+                "index_{}_{}",
+                IdenPartyHistory::Table.to_string(),
+                IdenPartyHistory::PartyId.to_string(),
+            ))
+            .table(IdenPartyHistory::Table)
+            .col(IdenPartyHistory::PartyId)
+            .to_owned();
+        manager.create_index(index).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::drop()
+            .if_exists()
+            .table(IdenPartyHistory::Table)
+            .to_owned();
+        manager.drop_table(table).await?;
+
+        let tables = [
+            Table::alter()
+                .table(IdenCharacters::Table)
+                .drop_column(IdenCharacters::LastRefreshed)
+                .to_owned(),
+            Table::alter()
+                .table(IdenCorporations::Table)
+                .drop_column(IdenCorporations::AllianceId)
+                .to_owned(),
+            Table::alter()
+                .table(IdenCorporations::Table)
+                .drop_column(IdenCorporations::LastRefreshed)
+                .to_owned(),
+        ];
+        for table in tables {
+            manager.alter_table(table).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdenCharacters {
+    #[sea_orm(iden = "characters")]
+    Table,
+    LastRefreshed, // 最后一次从 ESI 刷新的时间
+}
+
+#[derive(DeriveIden)]
+enum IdenCorporations {
+    #[sea_orm(iden = "corporations")]
+    Table,
+    AllianceId,
+    LastRefreshed, // 最后一次从 ESI 刷新的时间
+}
+
+// 角色与公司的变更记录, 未变化的字段新旧值相同
+#[derive(DeriveIden)]
+enum IdenPartyHistory {
+    #[sea_orm(iden = "party_history")]
+    Table,
+    Id,
+    PartyId, // 角色或公司ID
+    ChangedAt,
+    OldCorporationId, // 仅角色
+    NewCorporationId, // 仅角色
+    OldAllianceId,
+    NewAllianceId,
+    OldName,
+    NewName,
+}
@@ -0,0 +1,176 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend};
+use std::collections::BTreeSet;
+
+use crate::{image_hash, m20220101_000001_create_table::blob_column};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+
+        let table = Table::create()
+            .if_not_exists()
+            .table(IdenImages::Table)
+            .col(
+                ColumnDef::new(IdenImages::Hash)
+                    .string_len(64)
+                    .not_null()
+                    .primary_key(),
+            )
+            .col(blob_column(IdenImages::Data, backend).not_null())
+            .to_owned();
+        manager.create_table(table).await?;
+
+        let table = Table::create()
+            .if_not_exists()
+            .table(IdenPartyImages::Table)
+            .col(
+                ColumnDef::new(IdenPartyImages::PartyId)
+                    .big_integer()
+                    .not_null()
+                    .primary_key(),
+            )
+            .col(
+                ColumnDef::new(IdenPartyImages::ImageHash)
+                    .string_len(64)
+                    .not_null(),
+            )
+            .to_owned();
+        manager.create_table(table).await?;
+
+        // 已有头像取最大的尺寸迁入 images, 相同内容只保存一份
+        let db = manager.get_connection();
+        let select = Query::select()
+            .columns([
+                IdenCharacters::CharacterId,
+                IdenCharacters::Portrait512,
+                IdenCharacters::Portrait256,
+                IdenCharacters::Portrait128,
+                IdenCharacters::Portrait64,
+            ])
+            .from(IdenCharacters::Table)
+            .to_owned();
+        let rows = db.query_all(backend.build(&select)).await?;
+
+        let mut hashes = BTreeSet::new();
+        for row in rows {
+            let character_id: i64 = row.try_get("", "character_id")?;
+            let mut data = None;
+            for column in ["portrait512", "portrait256", "portrait128", "portrait64"] {
+                data = row.try_get::<Option<Vec<u8>>>("", column)?;
+                if data.is_some() {
+                    break;
+                }
+            }
+            let data = match data {
+                Some(data) => data,
+                None => continue,
+            };
+
+            let hash = image_hash(&data);
+            if hashes.insert(hash.clone()) {
+                let insert = Query::insert()
+                    .into_table(IdenImages::Table)
+                    .columns([IdenImages::Hash, IdenImages::Data])
+                    .values_panic([hash.clone().into(), data.into()])
+                    .to_owned();
+                db.execute(backend.build(&insert)).await?;
+            }
+            let insert = Query::insert()
+                .into_table(IdenPartyImages::Table)
+                .columns([IdenPartyImages::PartyId, IdenPartyImages::ImageHash])
+                .values_panic([character_id.into(), hash.into()])
+                .to_owned();
+            db.execute(backend.build(&insert)).await?;
+        }
+
+        // sqlite 每条 ALTER TABLE 只能删除一列
+        for column in [
+            IdenCharacters::Portrait64,
+            IdenCharacters::Portrait128,
+            IdenCharacters::Portrait256,
+            IdenCharacters::Portrait512,
+        ] {
+            let table = Table::alter()
+                .table(IdenCharacters::Table)
+                .drop_column(column)
+                .to_owned();
+            manager.alter_table(table).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+
+        for column in [
+            IdenCharacters::Portrait64,
+            IdenCharacters::Portrait128,
+            IdenCharacters::Portrait256,
+            IdenCharacters::Portrait512,
+        ] {
+            let table = Table::alter()
+                .table(IdenCharacters::Table)
+                .add_column(blob_column(column, backend))
+                .to_owned();
+            manager.alter_table(table).await?;
+        }
+
+        // 只能恢复原图, 较小的尺寸留空
+        let sql = match backend {
+            DatabaseBackend::MySql => {
+                "UPDATE characters c JOIN party_images p ON p.party_id = c.character_id \
+                 JOIN images i ON i.hash = p.image_hash SET c.portrait512 = i.data"
+            }
+            _ => {
+                "UPDATE characters SET portrait512 = (SELECT i.data FROM party_images p \
+                 JOIN images i ON i.hash = p.image_hash WHERE p.party_id = characters.character_id)"
+            }
+        };
+        manager.get_connection().execute_unprepared(sql).await?;
+
+        for table in [
+            IdenPartyImages::Table.into_iden(),
+            IdenImages::Table.into_iden(),
+        ] {
+            let table = Table::drop().if_exists().table(table).to_owned();
+            manager.drop_table(table).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdenCharacters {
+    #[sea_orm(iden = "characters")]
+    Table,
+    CharacterId,
+    Portrait64,
+    Portrait128,
+    Portrait256,
+    Portrait512,
+}
+
+// 图片内容, 以内容的 sha256 为主键, 相同的图片只保存一份
+#[derive(DeriveIden)]
+enum IdenImages {
+    #[sea_orm(iden = "images")]
+    Table,
+    Hash, // 小写十六进制的 sha256
+    Data, // 原始图片, 角色头像为 512px 的 JPEG, 公司与联盟图标为 PNG
+}
+
+// 角色头像, 公司与联盟图标
+#[derive(DeriveIden)]
+enum IdenPartyImages {
+    #[sea_orm(iden = "party_images")]
+    Table,
+    PartyId, // 角色, 公司或联盟ID
+    ImageHash,
+}
@@ -0,0 +1,30 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(IdenUsers::Table)
+            .add_column(ColumnDef::new(IdenUsers::TaxNote).text())
+            .to_owned();
+        manager.alter_table(table).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let table = Table::alter()
+            .table(IdenUsers::Table)
+            .drop_column(IdenUsers::TaxNote)
+            .to_owned();
+        manager.alter_table(table).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdenUsers {
+    #[sea_orm(iden = "users")]
+    Table,
+    TaxNote, // 税收清单中的备注, 由 ingest_workbook 从表格读回
+}